@@ -1,13 +1,14 @@
 #![allow(clippy::unwrap_used, clippy::redundant_pub_crate)]
 
 use axum::{
-    extract::{Path, State},
+    extract::Path,
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
 use rearch::Container;
+use rearch_axum::Capsule;
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -28,15 +29,27 @@ struct TodoWithId {
     content: String,
 }
 
+/// A boxed [`redb::Error`], since redb's errors are rather large to pass around by value.
+struct DbError(Box<redb::Error>);
+impl<E: Into<redb::Error>> From<E> for DbError {
+    fn from(err: E) -> Self {
+        Self(Box::new(err.into()))
+    }
+}
+
 // We define our todo db capsules here
 mod todo_db {
     use std::sync::Arc;
 
     use rearch::{CData, CapsuleHandle};
+    use rearch_axum::CapsuleFor;
     use redb::{Database, ReadOnlyTable, ReadableTable, Table, TableDefinition};
     use uuid::Uuid;
 
-    use crate::TodoWithId;
+    use crate::{DbError, TodoWithId};
+
+    /// A database operation, as exposed by the capsules below.
+    type DbAction<Arg, R> = Arc<dyn Fn(Arg) -> Result<R, DbError> + Send + Sync>;
 
     const TODOS_TABLE: TableDefinition<u128, &str> = TableDefinition::new("todos");
 
@@ -53,9 +66,9 @@ mod todo_db {
 
     fn with_read_txn_capsule<F, R>(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn(F) -> Result<R, redb::Error>
+    ) -> impl CData + Fn(F) -> Result<R, DbError>
     where
-        F: FnOnce(ReadOnlyTable<u128, &str>) -> Result<R, redb::Error>,
+        F: FnOnce(ReadOnlyTable<u128, &str>) -> Result<R, DbError>,
     {
         let db = Arc::clone(get.as_ref(db_capsule));
         move |with_table| {
@@ -67,9 +80,9 @@ mod todo_db {
 
     fn with_write_txn_capsule<F, R>(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn(F) -> Result<R, redb::Error>
+    ) -> impl CData + Fn(F) -> Result<R, DbError>
     where
-        F: FnOnce(Table<u128, &str>) -> Result<R, redb::Error>,
+        F: FnOnce(Table<u128, &str>) -> Result<R, DbError>,
     {
         let db = Arc::clone(get.as_ref(db_capsule));
         move |with_table| {
@@ -81,48 +94,75 @@ mod todo_db {
         }
     }
 
-    pub(super) fn read_todo_capsule(
+    fn read_todo_capsule(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn(Uuid) -> Result<Option<String>, redb::Error> {
+    ) -> DbAction<Uuid, Option<String>> {
         let with_txn = get.as_ref(with_read_txn_capsule).clone();
-        move |uuid| {
+        Arc::new(move |uuid| {
             with_txn(move |table| {
                 let content = table.get(uuid.as_u128())?.map(|s| s.value().to_owned());
                 Ok(content)
             })
+        })
+    }
+
+    pub(super) struct ReadTodo;
+    impl CapsuleFor for ReadTodo {
+        type Data = DbAction<Uuid, Option<String>>;
+
+        fn capsule() -> impl rearch::Capsule<Data = Self::Data> {
+            read_todo_capsule
         }
     }
 
-    pub(super) fn create_todo_capsule(
+    fn create_todo_capsule(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn(String) -> Result<TodoWithId, redb::Error> {
+    ) -> DbAction<String, TodoWithId> {
         let with_txn = get.as_ref(with_write_txn_capsule).clone();
-        move |content| {
+        Arc::new(move |content| {
             with_txn(move |mut table| {
                 let uuid = Uuid::new_v4();
                 table.insert(uuid.as_u128(), content.as_str())?;
                 Ok(TodoWithId { uuid, content })
             })
+        })
+    }
+
+    pub(super) struct CreateTodo;
+    impl CapsuleFor for CreateTodo {
+        type Data = DbAction<String, TodoWithId>;
+
+        fn capsule() -> impl rearch::Capsule<Data = Self::Data> {
+            create_todo_capsule
         }
     }
 
-    pub(super) fn delete_todo_capsule(
+    fn delete_todo_capsule(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn(Uuid) -> Result<Option<String>, redb::Error> {
+    ) -> DbAction<Uuid, Option<String>> {
         let with_txn = get.as_ref(with_write_txn_capsule).clone();
-        move |uuid| {
+        Arc::new(move |uuid| {
             with_txn(move |mut table| {
                 let removed_todo = table.remove(uuid.as_u128())?.map(|s| s.value().to_owned());
                 Ok(removed_todo)
             })
+        })
+    }
+
+    pub(super) struct DeleteTodo;
+    impl CapsuleFor for DeleteTodo {
+        type Data = DbAction<Uuid, Option<String>>;
+
+        fn capsule() -> impl rearch::Capsule<Data = Self::Data> {
+            delete_todo_capsule
         }
     }
 
-    pub(super) fn list_todos_capsule(
+    fn list_todos_capsule(
         CapsuleHandle { mut get, .. }: CapsuleHandle,
-    ) -> impl CData + Fn() -> Result<Vec<TodoWithId>, redb::Error> {
+    ) -> DbAction<(), Vec<TodoWithId>> {
         let with_txn = get.as_ref(with_read_txn_capsule).clone();
-        move || {
+        Arc::new(move |()| {
             with_txn(|table| {
                 table
                     .iter()?
@@ -132,49 +172,56 @@ mod todo_db {
                         let content = content.value().to_owned();
                         Ok(TodoWithId { uuid, content })
                     })
-                    .collect::<Result<Vec<_>, redb::Error>>()
+                    .collect::<Result<Vec<_>, DbError>>()
             })
+        })
+    }
+
+    pub(super) struct ListTodos;
+    impl CapsuleFor for ListTodos {
+        type Data = DbAction<(), Vec<TodoWithId>>;
+
+        fn capsule() -> impl rearch::Capsule<Data = Self::Data> {
+            list_todos_capsule
         }
     }
 }
 
-async fn list_todos(State(container): State<Container>) -> Result<Json<Vec<TodoWithId>>, AppError> {
-    container.read(todo_db::list_todos_capsule)()
-        .map(Json)
-        .map_err(Into::into)
+async fn list_todos(
+    Capsule(list_todos): Capsule<todo_db::ListTodos>,
+) -> Result<Json<Vec<TodoWithId>>, AppError> {
+    list_todos(()).map(Json).map_err(Into::into)
 }
 
 async fn create_todo(
-    State(container): State<Container>,
+    Capsule(create_todo): Capsule<todo_db::CreateTodo>,
     content: String,
 ) -> Result<Json<TodoWithId>, AppError> {
-    container.read(todo_db::create_todo_capsule)(content)
-        .map(Json)
-        .map_err(Into::into)
+    create_todo(content).map(Json).map_err(Into::into)
 }
 
 async fn read_todo(
-    State(container): State<Container>,
+    Capsule(read_todo): Capsule<todo_db::ReadTodo>,
     Path(uuid): Path<Uuid>,
 ) -> Result<String, AppError> {
-    container.read(todo_db::read_todo_capsule)(uuid)?.ok_or(AppError::TodoNotFound)
+    read_todo(uuid)?.ok_or(AppError::TodoNotFound)
 }
 
 async fn delete_todo(
-    State(container): State<Container>,
+    Capsule(delete_todo): Capsule<todo_db::DeleteTodo>,
     Path(uuid): Path<Uuid>,
 ) -> Result<String, AppError> {
-    container.read(todo_db::delete_todo_capsule)(uuid)?.ok_or(AppError::TodoNotFound)
+    delete_todo(uuid)?.ok_or(AppError::TodoNotFound)
 }
 
 enum AppError {
-    Redb(redb::Error),
+    Db(DbError),
     TodoNotFound,
 }
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         match self {
-            Self::Redb(e) => (
+            Self::Db(DbError(e)) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Database operation failed: {e}"),
             ),
@@ -183,8 +230,8 @@ impl IntoResponse for AppError {
         .into_response()
     }
 }
-impl From<redb::Error> for AppError {
-    fn from(err: redb::Error) -> Self {
-        Self::Redb(err)
+impl From<DbError> for AppError {
+    fn from(err: DbError) -> Self {
+        Self::Db(err)
     }
 }
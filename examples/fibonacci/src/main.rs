@@ -1,10 +1,10 @@
-use rearch::{Capsule, CapsuleHandle, CapsuleKey, Container};
+use rearch::{Capsule, CapsuleHandle, Container};
 
-struct FibonacciCapsule(u8);
-impl Capsule for FibonacciCapsule {
-    type Data = u128;
-
-    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+#[derive(Capsule)]
+#[capsule(data = u128, eq)]
+struct FibonacciCapsule(#[capsule_key] u8);
+impl FibonacciCapsule {
+    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> u128 {
         let Self(n) = self;
         match n {
             0 => 0,
@@ -12,14 +12,6 @@ impl Capsule for FibonacciCapsule {
             n => *get.as_ref(Self(n - 1)) + get.as_ref(Self(n - 2)),
         }
     }
-
-    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
-        old == new
-    }
-
-    fn key(&self) -> impl CapsuleKey {
-        self.0
-    }
 }
 
 fn main() {
@@ -1,25 +1,17 @@
 use std::collections::HashMap;
 
-use rearch::{CData, Capsule, CapsuleHandle, CapsuleKey, Container};
+use rearch::{CData, Capsule, CapsuleHandle, Container};
 use rearch_effects::{self as effects, LazyCloned};
 
 // Imagine this capsule represents a view that depends on an element of the list_capsule,
 // where the usize is the index of the list in a scrolling list view
-struct ListElementViewCapsule(usize);
-impl Capsule for ListElementViewCapsule {
-    type Data = String;
-
-    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+#[derive(Capsule)]
+#[capsule(data = String, eq)]
+struct ListElementViewCapsule(#[capsule_key] usize);
+impl ListElementViewCapsule {
+    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> String {
         get.as_ref(list_capsule).0[self.0].to_string()
     }
-
-    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
-        old == new
-    }
-
-    fn key(&self) -> impl CapsuleKey {
-        self.0
-    }
 }
 
 // Represents some list data source.
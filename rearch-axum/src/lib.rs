@@ -0,0 +1,196 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+    response::IntoResponse,
+};
+use rearch::Container;
+use rearch_tokio::read_async;
+
+/// Names a capsule so that it can be read by the [`Capsule`] and [`TryCapsule`] extractors.
+///
+/// Extractors are keyed on a type, but capsule functions have unnameable types,
+/// so implement this on a marker type (typically a unit struct) that returns the capsule.
+/// This works for both capsule functions and capsule structs.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::CapsuleHandle;
+/// # use rearch_axum::CapsuleFor;
+/// fn greeting_capsule(_: CapsuleHandle) -> String {
+///     "Hello, World!".to_owned()
+/// }
+///
+/// struct Greeting;
+/// impl CapsuleFor for Greeting {
+///     type Data = String;
+///
+///     fn capsule() -> impl rearch::Capsule<Data = String> {
+///         greeting_capsule
+///     }
+/// }
+/// ```
+pub trait CapsuleFor: 'static {
+    /// The data of the capsule returned by [`CapsuleFor::capsule`].
+    type Data: Clone + Send + Sync + 'static;
+
+    /// Returns the capsule to read.
+    fn capsule() -> impl rearch::Capsule<Data = Self::Data>;
+}
+
+/// An extractor that reads a capsule's data out of the [`Container`] in a router's state.
+///
+/// The capsule is named by a [`CapsuleFor`] implementation,
+/// and the state only needs to provide a [`Container`] via [`FromRef`].
+/// Capsules that still need to be built are read via [`rearch_tokio::read_async`]
+/// so that their builds don't hold up the async executor.
+///
+/// If the capsule's data is a [`Result`] that should reject the request on error,
+/// see [`TryCapsule`] instead.
+///
+/// # Examples
+/// ```rust
+/// # use axum::{routing::get, Router};
+/// # use rearch::{CapsuleHandle, Container};
+/// # use rearch_axum::{Capsule, CapsuleFor};
+/// fn greeting_capsule(_: CapsuleHandle) -> String {
+///     "Hello, World!".to_owned()
+/// }
+///
+/// struct Greeting;
+/// impl CapsuleFor for Greeting {
+///     type Data = String;
+///
+///     fn capsule() -> impl rearch::Capsule<Data = String> {
+///         greeting_capsule
+///     }
+/// }
+///
+/// async fn greet(Capsule(greeting): Capsule<Greeting>) -> String {
+///     greeting
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/", get(greet))
+///     .with_state(Container::new());
+/// ```
+pub struct Capsule<C: CapsuleFor>(pub C::Data);
+
+#[async_trait]
+impl<S, C> FromRequestParts<S> for Capsule<C>
+where
+    S: Send + Sync,
+    Container: FromRef<S>,
+    C: CapsuleFor,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            read_async(&Container::from_ref(state), C::capsule()).await,
+        ))
+    }
+}
+
+/// An extractor like [`Capsule`], but for capsules whose data is a [`Result`].
+///
+/// On `Ok`, the extractor yields the successful value;
+/// on `Err`, the request is rejected with the error's [`IntoResponse`] implementation.
+pub struct TryCapsule<C: ResultCapsule>(pub C::Ok);
+
+/// A [`CapsuleFor`] whose data is a [`Result`] with an error that is [`IntoResponse`].
+///
+/// This is implemented automatically for all such types; see [`TryCapsule`].
+pub trait ResultCapsule: CapsuleFor {
+    /// The success type of the capsule's data.
+    type Ok;
+
+    /// The error type of the capsule's data.
+    type Error: IntoResponse;
+
+    /// Converts the capsule's data into a [`Result`].
+    ///
+    /// # Errors
+    /// Returns the capsule's error when its data is an `Err`.
+    fn into_result(data: Self::Data) -> Result<Self::Ok, Self::Error>;
+}
+
+impl<C, T, E> ResultCapsule for C
+where
+    C: CapsuleFor<Data = Result<T, E>>,
+    E: IntoResponse,
+{
+    type Ok = T;
+    type Error = E;
+
+    fn into_result(data: Self::Data) -> Result<T, E> {
+        data
+    }
+}
+
+#[async_trait]
+impl<S, C> FromRequestParts<S> for TryCapsule<C>
+where
+    S: Send + Sync,
+    Container: FromRef<S>,
+    C: ResultCapsule,
+{
+    type Rejection = C::Error;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        C::into_result(read_async(&Container::from_ref(state), C::capsule()).await).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::FromRequestParts,
+        http::{Request, StatusCode},
+    };
+    use rearch::{CapsuleHandle, Container};
+
+    use crate::{Capsule, CapsuleFor, TryCapsule};
+
+    fn input_capsule(_: CapsuleHandle) -> String {
+        "123".to_owned()
+    }
+
+    fn parsed_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> Result<u8, StatusCode> {
+        get.as_ref(input_capsule)
+            .parse()
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    }
+
+    struct Input;
+    impl CapsuleFor for Input {
+        type Data = String;
+
+        fn capsule() -> impl rearch::Capsule<Data = String> {
+            input_capsule
+        }
+    }
+
+    struct Parsed;
+    impl CapsuleFor for Parsed {
+        type Data = Result<u8, StatusCode>;
+
+        fn capsule() -> impl rearch::Capsule<Data = Self::Data> {
+            parsed_capsule
+        }
+    }
+
+    #[tokio::test]
+    async fn extractors_read_capsules_from_state() {
+        let container = Container::new();
+        let (mut parts, ()) = Request::new(()).into_parts();
+
+        let parsed = TryCapsule::<Parsed>::from_request_parts(&mut parts, &container).await;
+        assert!(matches!(parsed, Ok(TryCapsule(123))));
+
+        let Ok(Capsule(input)) = Capsule::<Input>::from_request_parts(&mut parts, &container).await;
+        assert_eq!(input, "123");
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use rearch::{CData, SideEffect, SideEffectRegistrar};
+
+use crate::{raw, LazyCloned};
+
+/// A key-value store that a [`cached`] side effect reads from and writes through to.
+///
+/// Implement this for your persistence mechanism of choice (such as redb or sled)
+/// to decouple it from the capsules that use it.
+/// An implementation for `Mutex<HashMap<K, V, S>>` is provided for in-memory use (and testing).
+///
+/// Fallible backends are expected to handle (say, log) their own errors,
+/// treating a failed read like a missing value.
+pub trait KvBackend<K, V>: Send + Sync + 'static {
+    /// Returns the value stored under `key`, if there is one.
+    fn get(&self, key: &K) -> Option<V>;
+
+    /// Stores `value` under `key`.
+    fn set(&self, key: &K, value: &V);
+}
+
+impl<K, V, S> KvBackend<K, V> for Mutex<HashMap<K, V, S>>
+where
+    K: Clone + Hash + Eq + Send + 'static,
+    V: Clone + Send + 'static,
+    S: BuildHasher + Send + 'static,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    fn set(&self, key: &K, value: &V) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.clone(), value.clone());
+    }
+}
+
+/// Provides the value stored under `key` in the `backend` (or `None` if there is no value),
+/// alongside a setter that writes a new value through to the `backend` and then rebuilds.
+///
+/// The value is only loaded from the `backend` on the first build,
+/// and is then kept in sync by the setter;
+/// so, all writes to the value should go through the setter.
+///
+/// Note: both loading and writing the value call into the `backend` synchronously,
+/// so they will block the current thread for as long as the `backend` does.
+///
+/// # Examples
+/// ```rust
+/// # use std::{collections::HashMap, sync::{Arc, LazyLock, Mutex}};
+/// # use rearch::{CapsuleHandle, CData, Container};
+/// # use rearch_effects::cached;
+/// static SETTINGS: LazyLock<Arc<Mutex<HashMap<&str, String>>>> = LazyLock::new(Arc::default);
+///
+/// fn theme_capsule(
+///     CapsuleHandle { register, .. }: CapsuleHandle,
+/// ) -> (Option<String>, impl CData + Fn(String)) {
+///     register.register(cached(Arc::clone(&SETTINGS), "theme"))
+/// }
+///
+/// let container = Container::new();
+/// assert_eq!(container.read(theme_capsule).0, None);
+/// container.read(theme_capsule).1("dark".to_owned());
+/// assert_eq!(container.read(theme_capsule).0.as_deref(), Some("dark"));
+/// ```
+pub fn cached<K, V, Backend>(
+    backend: Arc<Backend>,
+    key: K,
+) -> impl for<'a> SideEffect<Api<'a> = (Option<V>, impl CData + Fn(V))>
+where
+    K: Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    Backend: KvBackend<K, V>,
+{
+    move |register: SideEffectRegistrar| {
+        let load = {
+            let (backend, key) = (Arc::clone(&backend), key.clone());
+            move || backend.get(&key)
+        };
+        let (value, rebuild, _) = register.register(raw::<LazyCloned<_, _>>(load));
+        let set = move |new_value: V| {
+            backend.set(&key, &new_value);
+            rebuild(Box::new(move |value| *value = Some(new_value)));
+        };
+        (value, set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, OnceLock},
+    };
+
+    use rearch::{CData, CapsuleHandle, Container};
+
+    use crate::{cached, KvBackend};
+
+    fn backend() -> Arc<Mutex<HashMap<&'static str, u32>>> {
+        static BACKEND: OnceLock<Arc<Mutex<HashMap<&'static str, u32>>>> = OnceLock::new();
+        Arc::clone(BACKEND.get_or_init(|| Arc::new(Mutex::new(HashMap::from([("a", 1)])))))
+    }
+
+    fn a_capsule(
+        CapsuleHandle { register, .. }: CapsuleHandle,
+    ) -> (Option<u32>, impl CData + Fn(u32)) {
+        register.register(cached(backend(), "a"))
+    }
+
+    fn b_capsule(
+        CapsuleHandle { register, .. }: CapsuleHandle,
+    ) -> (Option<u32>, impl CData + Fn(u32)) {
+        register.register(cached(backend(), "b"))
+    }
+
+    #[test]
+    fn cached_loads_and_writes_through() {
+        let container = Container::new();
+        assert_eq!(container.read((a_capsule, b_capsule)).0 .0, Some(1));
+        assert_eq!(container.read(b_capsule).0, None);
+
+        container.read(b_capsule).1(2);
+        assert_eq!(container.read(b_capsule).0, Some(2));
+        assert_eq!(backend().get(&"b"), Some(2));
+
+        // A new container loads the written value from the backend
+        assert_eq!(Container::new().read(b_capsule).0, Some(2));
+    }
+}
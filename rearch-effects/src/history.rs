@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use rearch::{CData, SideEffect, SideEffectRegistrar};
+
+use crate::{raw, MutRef};
+
+/// Provides a state with undo/redo support, alongside callbacks to
+/// push a new state, undo to the previous state, and redo to the next (undone) state.
+///
+/// Up to `capacity` past states are kept for undoing, with the oldest evicted first.
+/// Pushing a new state discards any states that were undone (so they can no longer be redone).
+/// Undoing (or redoing) when there is nothing to undo (or redo) leaves the state unchanged.
+/// Each callback triggers a rebuild that exposes the current state.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, CData, Container};
+/// # use rearch_effects::history;
+/// fn text_capsule(
+///     CapsuleHandle { register, .. }: CapsuleHandle,
+/// ) -> (String, impl CData + Fn(String), impl CData + Fn(), impl CData + Fn()) {
+///     register.register(history(String::new(), 100))
+/// }
+///
+/// let container = Container::new();
+/// let (_, push, undo, redo) = container.read(text_capsule);
+/// push("Hello".to_owned());
+/// push("Hello, World".to_owned());
+///
+/// undo();
+/// assert_eq!(container.read(text_capsule).0, "Hello");
+/// redo();
+/// assert_eq!(container.read(text_capsule).0, "Hello, World");
+/// ```
+#[must_use]
+pub fn history<T: Clone + Send + Sync + 'static>(
+    initial: T,
+    capacity: usize,
+) -> impl for<'a> SideEffect<Api<'a> = (T, impl CData + Fn(T), impl CData + Fn(), impl CData + Fn())>
+{
+    move |register: SideEffectRegistrar| {
+        let (history, rebuild, _) = register.register(raw::<MutRef<_>>(History {
+            past: VecDeque::new(),
+            present: initial,
+            future: Vec::new(),
+            capacity,
+        }));
+        let push = {
+            let rebuild = rebuild.clone();
+            move |state| {
+                rebuild(Box::new(move |history: &mut History<T>| {
+                    history.push(state);
+                }));
+            }
+        };
+        let undo = {
+            let rebuild = rebuild.clone();
+            move || rebuild(Box::new(History::undo))
+        };
+        let redo = move || rebuild(Box::new(History::redo));
+        (history.present.clone(), push, undo, redo)
+    }
+}
+
+struct History<T> {
+    past: VecDeque<T>,
+    present: T,
+    future: Vec<T>,
+    capacity: usize,
+}
+
+impl<T> History<T> {
+    fn push(&mut self, state: T) {
+        self.future.clear();
+        self.past
+            .push_back(std::mem::replace(&mut self.present, state));
+        if self.past.len() > self.capacity {
+            self.past.pop_front();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(state) = self.past.pop_back() {
+            self.future
+                .push(std::mem::replace(&mut self.present, state));
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(state) = self.future.pop() {
+            self.past
+                .push_back(std::mem::replace(&mut self.present, state));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rearch::{CData, CapsuleHandle, Container};
+
+    use crate::history;
+
+    #[allow(clippy::type_complexity)]
+    fn history_capsule(
+        CapsuleHandle { register, .. }: CapsuleHandle,
+    ) -> (
+        u8,
+        impl CData + Fn(u8),
+        impl CData + Fn(),
+        impl CData + Fn(),
+    ) {
+        register.register(history(0, 2))
+    }
+
+    #[test]
+    fn history_evicts_beyond_capacity() {
+        let container = Container::new();
+        let (_, push, undo, _) = container.read(history_capsule);
+        push(1);
+        push(2);
+        push(3);
+        assert_eq!(container.read(history_capsule).0, 3);
+
+        undo();
+        undo();
+        assert_eq!(container.read(history_capsule).0, 1);
+
+        // 0 was evicted, so there is nothing left to undo
+        undo();
+        assert_eq!(container.read(history_capsule).0, 1);
+    }
+
+    #[test]
+    fn history_push_invalidates_redo() {
+        let container = Container::new();
+        let (_, push, undo, redo) = container.read(history_capsule);
+        push(1);
+        push(2);
+        undo();
+        assert_eq!(container.read(history_capsule).0, 1);
+        redo();
+        assert_eq!(container.read(history_capsule).0, 2);
+
+        undo();
+        push(3);
+        redo();
+        assert_eq!(container.read(history_capsule).0, 3);
+        undo();
+        assert_eq!(container.read(history_capsule).0, 1);
+    }
+}
@@ -1,5 +1,11 @@
 use rearch::{CData, SideEffect, SideEffectRegistrar};
-use std::sync::Arc;
+use std::{
+    any::{Any, TypeId},
+    cell::Cell,
+    collections::HashMap,
+    sync::Arc,
+    time::Instant,
+};
 
 mod state_transformers;
 pub use state_transformers::*;
@@ -8,7 +14,24 @@ mod multi;
 pub use multi::*;
 
 mod overridable_capsule;
-pub use overridable_capsule::{overridable_capsule, OverridableCapsule};
+pub use overridable_capsule::{
+    overridable_capsule, overridable_capsule_with_eq, DataEq, NeverEq, OverridableCapsule,
+    PartialEqData,
+};
+
+mod ref_count;
+pub use ref_count::{ref_count, RefCounted};
+
+mod history;
+pub use history::history;
+
+mod cached;
+pub use cached::{cached, KvBackend};
+
+#[cfg(feature = "rand")]
+mod rng;
+#[cfg(feature = "rand")]
+pub use rng::rng;
 
 mod effect_lifetime_fixers;
 use effect_lifetime_fixers::{EffectLifetimeFixer0, EffectLifetimeFixer1, EffectLifetimeFixer2};
@@ -78,6 +101,232 @@ pub fn state<ST: StateTransformer>(
     })
 }
 
+/// Similar to [`state`], but the provided setter compares the new state to the current state,
+/// returning whether or not the state changed.
+///
+/// The comparison is done against the side effect's existing state (so no copy is kept),
+/// and the state is only overwritten when it actually changed.
+/// However, like any other side effect state mutation, calling the setter still rebuilds
+/// the capsule; use the returned `bool` to avoid your own downstream work when nothing changed.
+pub fn state_tracked<ST: StateTransformer>(
+    initial: ST::Input,
+) -> impl for<'a> SideEffect<Api<'a> = (ST::Output<'a>, impl CData + Fn(ST::Inner) -> bool)>
+where
+    ST::Inner: PartialEq,
+{
+    EffectLifetimeFixer1::<_, ST>::new(move |register: SideEffectRegistrar| {
+        let (state, rebuild, _) = register.register(raw::<ST>(initial));
+        let set_state = move |new_state: ST::Inner| {
+            let mut did_change = false;
+            rebuild(Box::new(|state| {
+                did_change = *state != new_state;
+                if did_change {
+                    *state = new_state;
+                }
+            }));
+            did_change
+        };
+        (state, set_state)
+    })
+}
+
+/// Similar to [`state`], but the setter ignores any new state that `validate` rejects.
+///
+/// The setter returns whether or not the new state was accepted.
+/// This bakes invariants (say, a count that must stay non-negative) into the state layer,
+/// so that every call site doesn't need to re-check them.
+/// Note that the `initial` state is not validated.
+pub fn guarded_state<ST: StateTransformer, V>(
+    initial: ST::Input,
+    validate: V,
+) -> impl for<'a> SideEffect<Api<'a> = (ST::Output<'a>, impl CData + Fn(ST::Inner) -> bool)>
+where
+    V: Fn(&ST::Inner) -> bool + Send + Sync + 'static,
+{
+    EffectLifetimeFixer1::<_, ST>::new(move |register: SideEffectRegistrar| {
+        let (state, rebuild, _) = register.register(raw::<ST>(initial));
+        let validate = Arc::new(validate);
+        let set_state = move |new_state| {
+            let is_valid = validate(&new_state);
+            if is_valid {
+                rebuild(Box::new(|state| *state = new_state));
+            }
+            is_valid
+        };
+        (state, set_state)
+    })
+}
+
+/// Provides a boolean state, alongside a callback to toggle it and a callback to set it.
+///
+/// The toggle callback always flips the *current* state (even when it is called several times
+/// within one side effect transaction), as opposed to the state seen during the last build.
+#[must_use]
+pub fn toggle(
+    initial: bool,
+) -> impl for<'a> SideEffect<Api<'a> = (bool, impl CData + Fn(), impl CData + Fn(bool))> {
+    move |register: SideEffectRegistrar| {
+        let (state, rebuild, _) = register.register(raw::<Cloned<_>>(initial));
+        let toggle = {
+            let rebuild = rebuild.clone();
+            move || rebuild(Box::new(|state| *state = !*state))
+        };
+        let set_state = move |new_state| rebuild(Box::new(move |state| *state = new_state));
+        (state, toggle, set_state)
+    }
+}
+
+/// Provides a latched boolean (starting as `false`), alongside a callback to set it to `true`.
+///
+/// Once set, the latch stays `true` for the lifetime of the side effect.
+/// Callbacks handed out after the latch was set are no-ops, so only a callback from a build
+/// that saw `false` triggers a rebuild (and several such calls within one side effect
+/// transaction result in just one rebuild).
+/// Useful for "has this ever happened" flags, like whether onboarding has been dismissed.
+#[must_use]
+pub fn latch() -> impl for<'a> SideEffect<Api<'a> = (bool, impl CData + Fn())> {
+    |register: SideEffectRegistrar| {
+        let (is_latched, rebuild, _) = register.register(raw::<Cloned<_>>(false));
+        let set = move || {
+            if !is_latched {
+                rebuild(Box::new(|latched| *latched = true));
+            }
+        };
+        (is_latched, set)
+    }
+}
+
+/// Provides a version number (starting at 0), alongside a callback to bump (increment) it.
+///
+/// Useful for cache-busting and `ETag`s. Like [`toggle`], bumps are applied to the *current*
+/// version, so several bumps within one side effect transaction result in just one rebuild
+/// that reflects all of them.
+#[must_use]
+pub fn version() -> impl for<'a> SideEffect<Api<'a> = (u64, impl CData + Fn())> {
+    move |register: SideEffectRegistrar| {
+        let (version, rebuild, _) = register.register(raw::<Cloned<u64>>(0));
+        let bump = move || rebuild(Box::new(|version| *version += 1));
+        (version, bump)
+    }
+}
+
+/// Provides a parameter of the capsule (starting as `initial`),
+/// alongside a callback to change it in place, which rebuilds the capsule with the new parameter.
+///
+/// This is an alternative to dynamic capsules (like `FibCapsule(n)`) for parameterized capsules.
+/// With a dynamic capsule, the parameter is part of the capsule's key,
+/// so each parameter value is a *separate* capsule (with its own data, side effect state,
+/// and dependents) in a family that lives side by side in the container.
+/// With `parameter`, there is exactly *one* capsule whose parameter changes over time;
+/// the capsule keeps its identity (and its dependents and other side effect state),
+/// but only ever reflects the current parameter (like the current page of a paginated list).
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, CData, Container};
+/// # use rearch_effects::parameter;
+/// fn page_capsule(
+///     CapsuleHandle { register, .. }: CapsuleHandle,
+/// ) -> (Vec<u32>, impl CData + Fn(u32)) {
+///     let (page, set_page) = register.register(parameter(0));
+///     let items = (page * 10..(page + 1) * 10).collect();
+///     (items, set_page)
+/// }
+///
+/// let container = Container::new();
+/// let (_, set_page) = container.read(page_capsule);
+/// set_page(2);
+/// assert_eq!(container.read(page_capsule).0[0], 20);
+/// ```
+#[must_use]
+pub fn parameter<P: Clone + Send + 'static>(
+    initial: P,
+) -> impl for<'a> SideEffect<Api<'a> = (P, impl CData + Fn(P))> {
+    move |register: SideEffectRegistrar| {
+        let (parameter, rebuild, _) = register.register(raw::<Cloned<_>>(initial));
+        let set_parameter =
+            move |new_parameter| rebuild(Box::new(move |parameter| *parameter = new_parameter));
+        (parameter, set_parameter)
+    }
+}
+
+/// Provides a draft of some `committed` state, alongside whether the draft is dirty.
+///
+/// The draft is dirty when it differs from the committed state.
+/// Also provides callbacks to set the draft, commit the draft (making it the committed state),
+/// and revert the draft (discarding it in favor of the committed state).
+/// This encapsulates the common "unsaved changes" pattern of form editing.
+/// `committed` is only used as the initial committed state; those passed in later builds
+/// are ignored. Like [`toggle`], every callback applies to the *current* draft and committed
+/// state, so they compose as expected within one side effect transaction.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, CData, Container};
+/// # use rearch_effects::dirty_tracker;
+/// fn name_form_capsule(
+///     CapsuleHandle { register, .. }: CapsuleHandle,
+/// ) -> (String, bool, impl CData + Fn(String), impl CData + Fn(), impl CData + Fn()) {
+///     register.register(dirty_tracker("Alice".to_owned()))
+/// }
+///
+/// let container = Container::new();
+/// let (_, _, set_draft, commit, _) = container.read(name_form_capsule);
+/// set_draft("Bob".to_owned());
+/// assert!(container.read(name_form_capsule).1);
+/// commit();
+/// assert!(!container.read(name_form_capsule).1);
+/// ```
+#[must_use]
+#[allow(clippy::type_complexity)]
+pub fn dirty_tracker<T: Clone + PartialEq + Send + 'static>(
+    committed: T,
+) -> impl for<'a> SideEffect<
+    Api<'a> = (
+        T,
+        bool,
+        impl CData + Fn(T),
+        impl CData + Fn(),
+        impl CData + Fn(),
+    ),
+> {
+    move |register: SideEffectRegistrar| {
+        let draft = committed.clone();
+        let ((committed, draft), rebuild, _) =
+            register.register(raw::<MutRef<_>>((committed, draft)));
+        let is_dirty = draft != committed;
+        let set_draft = {
+            let rebuild = rebuild.clone();
+            move |new_draft| rebuild(Box::new(move |(_, draft)| *draft = new_draft))
+        };
+        let commit = {
+            let rebuild = rebuild.clone();
+            move || rebuild(Box::new(|(committed, draft)| *committed = draft.clone()))
+        };
+        let revert = move || rebuild(Box::new(|(committed, draft)| *draft = committed.clone()));
+        (draft.clone(), is_dirty, set_draft, commit, revert)
+    }
+}
+
+/// Provides an event bus for imperative, discrete events (as opposed to state).
+///
+/// Emitting an event triggers a rebuild, and the events emitted since the last build can then
+/// be drained (in order) during that rebuild via the provided drain callback.
+/// Events are drained once provided, so they will never be seen more than once;
+/// events that are not drained during a build are dropped.
+/// Events emitted within one side effect transaction are batched into a single rebuild.
+#[must_use]
+pub fn event_bus<E: Send + 'static>(
+) -> impl for<'a> SideEffect<Api<'a> = (impl Fn() -> Vec<E>, impl CData + Fn(E))> {
+    |register: SideEffectRegistrar| {
+        let (events, rebuild, _) = register.register(raw::<MutRef<Vec<E>>>(Vec::new()));
+        let pending_events = Cell::new(std::mem::take(events));
+        let drain = move || pending_events.take();
+        let emit = move |event| rebuild(Box::new(move |events| events.push(event)));
+        (drain, emit)
+    }
+}
+
 /// Provides the same given value across builds.
 pub fn value<ST: StateTransformer>(
     value: ST::Input,
@@ -87,6 +336,27 @@ pub fn value<ST: StateTransformer>(
     })
 }
 
+/// A map from a [`TypeId`] to a value of that type, as provided by [`local_store`].
+pub type TypeMap = HashMap<TypeId, Box<dyn Any + Send>>;
+
+/// Provides capsule-local storage, keyed by [`TypeId`], that persists across builds.
+///
+/// This is intended for plugin authors looking to attach arbitrary per-capsule metadata
+/// that isn't part of the capsule's data;
+/// multiple independent plugins can coexist by keying their data with their own types.
+///
+/// Like any other side effect, only one `local_store` can be registered per capsule,
+/// so a capsule should register it once and then share the provided [`TypeMap`]
+/// with all of its plugins.
+/// The [`TypeMap`] is only ever accessed from within its capsule's builds
+/// (which are never run concurrently), so its values need only be [`Send`].
+#[must_use]
+pub fn local_store() -> impl for<'a> SideEffect<Api<'a> = &'a mut TypeMap> {
+    EffectLifetimeFixer0::<_, MutRef<TypeMap>>::new(|register: SideEffectRegistrar| {
+        register.register(value::<MutRef<_>>(TypeMap::new()))
+    })
+}
+
 /// Provides whether or not this is the first build being called.
 #[must_use]
 pub fn is_first_build() -> impl for<'a> SideEffect<Api<'a> = bool> {
@@ -98,6 +368,117 @@ pub fn is_first_build() -> impl for<'a> SideEffect<Api<'a> = bool> {
     }
 }
 
+/// Runs `finalizer` when the [`Container`](rearch::Container) itself is dropped,
+/// which is useful for final flushes (say, of persisted data) at shutdown.
+///
+/// Unlike [`run_on_change`], which runs whenever the capsule is rebuilt or disposed,
+/// `finalizer` is *not* run when the capsule is disposed; it is only ever run on container drop.
+/// Finalizers run in the reverse order that they were registered in
+/// (so dependents are finalized before their dependencies), before any capsule data is dropped.
+///
+/// Only the `finalizer` passed in on the capsule's first build is registered.
+/// A capsule that is disposed and then reinitialized replaces its previously registered
+/// finalizer, so each capsule's finalizer is only ever run once.
+#[must_use]
+pub fn on_container_drop<F>(finalizer: F) -> impl for<'a> SideEffect<Api<'a> = ()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    move |register: SideEffectRegistrar| {
+        let register_finalizer = register.container_finalizer_registrar();
+        if register.register(is_first_build()) {
+            register_finalizer(Box::new(finalizer));
+        }
+    }
+}
+
+/// The [`Instant`]s of a capsule's current and previous builds,
+/// as provided by [`last_build_instant`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BuildInstants {
+    current: Instant,
+    previous: Option<Instant>,
+}
+
+impl BuildInstants {
+    /// Returns the [`Instant`] at which the current build was started.
+    #[must_use]
+    pub const fn current(&self) -> Instant {
+        self.current
+    }
+
+    /// Returns the [`Instant`] at which the previous build was started,
+    /// or `None` if this is the first build.
+    #[must_use]
+    pub const fn previous(&self) -> Option<Instant> {
+        self.previous
+    }
+}
+
+/// Provides the [`Instant`] of the current build, alongside that of the previous build.
+///
+/// Useful for staleness checks and rate limiting
+/// (such as "refresh if the data is older than 5 seconds")
+/// when combined with a way to manually trigger rebuilds.
+#[must_use]
+pub fn last_build_instant() -> impl for<'a> SideEffect<Api<'a> = BuildInstants> {
+    |register: SideEffectRegistrar| {
+        let last_build = register.register(value::<MutRef<Option<Instant>>>(None));
+        let current = Instant::now();
+        let previous = last_build.replace(current);
+        BuildInstants { current, previous }
+    }
+}
+
+/// Invokes `on_change(old, new)` whenever `value` differs from that of the previous build.
+///
+/// `on_change` is *not* invoked on the first build.
+///
+/// Useful for imperatively syncing some derived state to an external system.
+pub fn on_change<T, OnChange>(
+    value: T,
+    on_change: OnChange,
+) -> impl for<'a> SideEffect<Api<'a> = ()>
+where
+    T: PartialEq + Send + 'static,
+    OnChange: FnOnce(&T, &T),
+{
+    move |register: SideEffectRegistrar| {
+        let previous = register.register(self::value::<MutRef<Option<T>>>(None));
+        let old = previous.take();
+        let new = &*previous.insert(value);
+        if let Some(old) = old.filter(|old| old != new) {
+            on_change(&old, new);
+        }
+    }
+}
+
+/// Provides the output of `compute` for the given `inputs` (similar to `useMemo` from React hooks).
+///
+/// The output is cached across builds,
+/// and is only recomputed when `inputs` differs from that of the previous build.
+/// Unlike a separate capsule, whose data is derived from other capsules,
+/// `inputs` may be any values computed during the build.
+pub fn computed<I, O, Compute>(inputs: I, compute: Compute) -> impl for<'a> SideEffect<Api<'a> = O>
+where
+    I: PartialEq + Send + 'static,
+    O: Clone + Send + 'static,
+    Compute: FnOnce(&I) -> O,
+{
+    move |register: SideEffectRegistrar| {
+        let cache = register.register(value::<MutRef<Option<(I, O)>>>(None));
+        if let Some((_, output)) = cache
+            .as_ref()
+            .filter(|(old_inputs, _)| *old_inputs == inputs)
+        {
+            return output.clone();
+        }
+        let output = compute(&inputs);
+        *cache = Some((inputs, output.clone()));
+        output
+    }
+}
+
 /// Models the state reducer pattern via side effects (similar to `useReducer` from React hooks).
 ///
 /// This should normally *not* be used with [`MutRef`].
@@ -182,7 +563,7 @@ pub struct RunOnChange<F>(std::marker::PhantomData<F>);
 impl<F: Send + FnOnce() + 'static> SideEffect for RunOnChange<F> {
     type Api<'registrar> = impl FnMut(F) + 'registrar;
 
-    fn build(self, registrar: SideEffectRegistrar) -> Self::Api<'_> {
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
         let state = registrar.register(value(FunctionalDrop(None)));
         // The old callback, if there is one, will be called when it is dropped,
         // via the `*state = ...` assignment below
@@ -202,6 +583,58 @@ mod tests {
     #[allow(clippy::needless_pass_by_value)]
     fn assert_type<Expected>(_actual: Expected) {}
 
+    type SideEffectTxnRunner = Arc<dyn Send + Sync + for<'f> Fn(Box<dyn 'f + FnOnce()>)>;
+
+    /// Provides a txn runner so that tests can batch several side effect mutations together.
+    fn txn_runner_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> SideEffectTxnRunner {
+        register.register(raw::<Cloned<_>>(())).2
+    }
+
+    #[test]
+    fn on_container_drop_runs_only_on_container_drop() {
+        static FINALIZED: std::sync::Mutex<Vec<&str>> = std::sync::Mutex::new(Vec::new());
+        fn finalized() -> std::sync::MutexGuard<'static, Vec<&'static str>> {
+            FINALIZED.lock().expect("Lock should not be poisoned")
+        }
+
+        fn db_capsule(CapsuleHandle { register, .. }: CapsuleHandle) {
+            register.register(on_container_drop(|| finalized().push("db")));
+        }
+        fn cache_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            get.as_ref(db_capsule);
+            register.register(on_container_drop(|| finalized().push("cache")));
+        }
+
+        let container = Container::new();
+        container.read(cache_capsule);
+        container.dispose(&cache_capsule);
+        assert!(finalized().is_empty());
+
+        drop(container);
+        assert_eq!(*finalized(), ["cache", "db"]);
+    }
+
+    #[test]
+    fn on_container_drop_replaces_finalizer_on_reinitialization() {
+        static FINALIZED: AtomicU8 = AtomicU8::new(0);
+
+        fn db_capsule(CapsuleHandle { register, .. }: CapsuleHandle) {
+            register.register(on_container_drop(|| {
+                FINALIZED.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+
+        let container = Container::new();
+        for _ in 0..3 {
+            container.read(db_capsule);
+            container.dispose(&db_capsule);
+        }
+        container.read(db_capsule);
+
+        drop(container);
+        assert_eq!(FINALIZED.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn transformer_output_types() {
         fn dummy_capsule(CapsuleHandle { register, .. }: CapsuleHandle) {
@@ -240,6 +673,38 @@ mod tests {
         assert_eq!(Container::new().read(lazy_transformer_capsule), 123);
     }
 
+    #[test]
+    fn arc_cloned_transformer_shares_data() {
+        fn big_data_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> Arc<Vec<u8>> {
+            register.register(value::<ArcCloned<_>>(vec![0; 1024]))
+        }
+
+        let container = Container::new();
+        let data = container.read(big_data_capsule);
+        assert_eq!(data.len(), 1024);
+        assert!(Arc::ptr_eq(&data, &container.read(big_data_capsule)));
+    }
+
+    #[test]
+    fn arc_cloned_state_is_copy_on_write() {
+        fn list_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (Arc<Vec<u8>>, impl CData + Fn(u8)) {
+            let (list, mutate, _) = register.register(raw::<ArcCloned<_>>(Vec::new()));
+            let push = move |item| mutate(Box::new(move |list| list.push(item)));
+            (list, push)
+        }
+
+        let container = Container::new();
+        let (old_list, push) = container.read(list_capsule);
+        push(1);
+        push(2);
+
+        let new_list = container.read(list_capsule).0;
+        assert_eq!(*new_list, vec![1, 2]);
+        assert!(old_list.is_empty());
+    }
+
     #[test]
     fn as_listener_gets_changes() {
         static BUILD_COUNT: AtomicU8 = AtomicU8::new(0);
@@ -261,6 +726,42 @@ mod tests {
         assert_eq!(BUILD_COUNT.fetch_add(1, Ordering::SeqCst), 2);
     }
 
+    #[test]
+    fn on_change_invoked_only_on_change() {
+        static CHANGES: std::sync::Mutex<Vec<(u8, u8)>> = std::sync::Mutex::new(Vec::new());
+
+        fn stateful_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, impl CData + Fn(u8)) {
+            register.register(state::<Cloned<_>>(0))
+        }
+
+        fn syncing_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            let curr_state = get.as_ref(stateful_capsule).0;
+            register.register(on_change(curr_state % 2, |old, new| {
+                CHANGES
+                    .lock()
+                    .expect("Lock should not be poisoned")
+                    .push((*old, *new));
+            }));
+        }
+
+        let container = Container::new();
+        container.read(syncing_capsule);
+        assert!(CHANGES
+            .lock()
+            .expect("Lock should not be poisoned")
+            .is_empty());
+
+        container.read(stateful_capsule).1(1);
+        container.read(stateful_capsule).1(3);
+        container.read(stateful_capsule).1(4);
+        assert_eq!(
+            *CHANGES.lock().expect("Lock should not be poisoned"),
+            vec![(0, 1), (1, 0)]
+        );
+    }
+
     #[test]
     fn state_can_change() {
         fn stateful_capsule(
@@ -275,6 +776,313 @@ mod tests {
         assert_eq!(container.read(stateful_capsule).0, 1);
     }
 
+    #[test]
+    fn state_tracked_reports_changes() {
+        fn stateful_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, impl CData + Fn(u8) -> bool) {
+            register.register(state_tracked::<Cloned<_>>(0))
+        }
+
+        let container = Container::new();
+        let set_state = container.read(stateful_capsule).1;
+        assert!(!set_state(0));
+        assert_eq!(container.read(stateful_capsule).0, 0);
+
+        assert!(set_state(1));
+        assert_eq!(container.read(stateful_capsule).0, 1);
+
+        assert!(!set_state(1));
+        assert_eq!(container.read(stateful_capsule).0, 1);
+    }
+
+    #[test]
+    fn guarded_state_rejects_invalid_updates() {
+        fn stateful_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (i8, impl CData + Fn(i8) -> bool) {
+            register.register(guarded_state::<Cloned<_>, _>(0, |count: &i8| *count >= 0))
+        }
+
+        let container = Container::new();
+        let set_count = container.read(stateful_capsule).1;
+        assert!(set_count(1));
+        assert_eq!(container.read(stateful_capsule).0, 1);
+
+        container.assert_no_builds(|| assert!(!set_count(-1)));
+        assert_eq!(container.read(stateful_capsule).0, 1);
+    }
+
+    #[test]
+    fn local_store_persists_across_builds() {
+        struct PluginA(u8);
+        struct PluginB(&'static str);
+
+        fn rebuildable_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
+            let ((), rebuild, _) = register.raw(());
+            move || rebuild(Box::new(|()| {}))
+        }
+
+        fn plugin_capsule(
+            CapsuleHandle { mut get, register }: CapsuleHandle,
+        ) -> (u8, &'static str) {
+            get.as_ref(rebuildable_capsule);
+            let store = register.register(local_store());
+
+            let plugin_a = store
+                .entry(TypeId::of::<PluginA>())
+                .or_insert_with(|| Box::new(PluginA(0)))
+                .downcast_mut::<PluginA>()
+                .expect("PluginA is stored under its own TypeId");
+            plugin_a.0 += 1;
+            let build_count = plugin_a.0;
+
+            let plugin_b = store
+                .entry(TypeId::of::<PluginB>())
+                .or_insert_with(|| Box::new(PluginB("plugin b")))
+                .downcast_ref::<PluginB>()
+                .expect("PluginB is stored under its own TypeId");
+
+            (build_count, plugin_b.0)
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(plugin_capsule), (1, "plugin b"));
+        container.read(rebuildable_capsule)();
+        assert_eq!(container.read(plugin_capsule), (2, "plugin b"));
+    }
+
+    #[test]
+    fn toggle_flips_current_state() {
+        fn toggle_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (bool, impl CData + Fn(), impl CData + Fn(bool)) {
+            register.register(toggle(false))
+        }
+
+        let container = Container::new();
+        let (state, toggle, set_state) = container.read(toggle_capsule);
+        let run_txn = container.read(txn_runner_capsule);
+        assert!(!state);
+
+        toggle();
+        assert!(container.read(toggle_capsule).0);
+
+        run_txn(Box::new(|| {
+            toggle();
+            toggle();
+            toggle();
+        }));
+        assert!(!container.read(toggle_capsule).0);
+
+        set_state(true);
+        assert!(container.read(toggle_capsule).0);
+    }
+
+    #[test]
+    fn version_bumps_are_batched() {
+        static BUILD_COUNT: AtomicU8 = AtomicU8::new(0);
+
+        fn version_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u64, impl CData + Fn()) {
+            BUILD_COUNT.fetch_add(1, Ordering::SeqCst);
+            register.register(version())
+        }
+
+        let container = Container::new();
+        let (version, bump) = container.read(version_capsule);
+        let run_txn = container.read(txn_runner_capsule);
+        assert_eq!(version, 0);
+
+        bump();
+        assert_eq!(container.read(version_capsule).0, 1);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 2);
+
+        run_txn(Box::new(|| {
+            bump();
+            bump();
+            bump();
+        }));
+        assert_eq!(container.read(version_capsule).0, 4);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn parameter_rebuilds_same_capsule() {
+        fn square_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn(u32)) {
+            let (n, set_n) = register.register(parameter(2));
+            (n * n, set_n)
+        }
+        fn square_plus_one_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+            get.as_ref(square_capsule).0 + 1
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(square_plus_one_capsule), 5);
+
+        container.read(square_capsule).1(3);
+        assert_eq!(container.read(square_plus_one_capsule), 10);
+        assert_eq!(container.stats().node_count, 2);
+    }
+
+    #[test]
+    fn dirty_tracker_commits_and_reverts() {
+        fn form_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            String,
+            bool,
+            impl CData + Fn(String),
+            impl CData + Fn(),
+            impl CData + Fn(),
+        ) {
+            register.register(dirty_tracker("committed".to_owned()))
+        }
+        fn draft_and_dirty(container: &Container) -> (String, bool) {
+            let (draft, is_dirty, _, _, _) = container.read(form_capsule);
+            (draft, is_dirty)
+        }
+
+        let container = Container::new();
+        let (_, _, set_draft, commit, revert) = container.read(form_capsule);
+        let run_txn = container.read(txn_runner_capsule);
+        assert_eq!(draft_and_dirty(&container), ("committed".to_owned(), false));
+
+        run_txn(Box::new(|| {
+            set_draft("a".to_owned());
+            set_draft("ab".to_owned());
+            set_draft("abc".to_owned());
+        }));
+        assert_eq!(draft_and_dirty(&container), ("abc".to_owned(), true));
+
+        revert();
+        assert_eq!(draft_and_dirty(&container), ("committed".to_owned(), false));
+
+        set_draft("new".to_owned());
+        commit();
+        assert_eq!(draft_and_dirty(&container), ("new".to_owned(), false));
+
+        set_draft("newer".to_owned());
+        set_draft("new".to_owned());
+        assert_eq!(draft_and_dirty(&container), ("new".to_owned(), false));
+
+        run_txn(Box::new(|| {
+            set_draft("discarded".to_owned());
+            revert();
+        }));
+        assert_eq!(draft_and_dirty(&container), ("new".to_owned(), false));
+    }
+
+    #[test]
+    fn latch_only_rebuilds_from_unlatched_builds() {
+        static BUILD_COUNT: AtomicU8 = AtomicU8::new(0);
+
+        fn latch_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (bool, impl CData + Fn()) {
+            BUILD_COUNT.fetch_add(1, Ordering::SeqCst);
+            register.register(latch())
+        }
+
+        let container = Container::new();
+        let (is_latched, set) = container.read(latch_capsule);
+        let run_txn = container.read(txn_runner_capsule);
+        assert!(!is_latched);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 1);
+
+        run_txn(Box::new(|| {
+            set();
+            set();
+        }));
+        assert!(container.read(latch_capsule).0);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 2);
+
+        container.read(latch_capsule).1();
+        assert!(container.read(latch_capsule).0);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 2);
+
+        // A callback from a build that saw `false` still requests a (redundant) rebuild
+        set();
+        assert!(container.read(latch_capsule).0);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn computed_only_recomputes_on_input_change() {
+        static COMPUTE_COUNT: AtomicU8 = AtomicU8::new(0);
+
+        fn input_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn(u32)) {
+            register.register(state::<Cloned<_>>(1))
+        }
+
+        fn squared_capsule(
+            CapsuleHandle { mut get, register }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn()) {
+            let input = get.as_ref(input_capsule).0;
+            let (squared, (_, rebuild)) = register.register((
+                computed(input, |input| {
+                    COMPUTE_COUNT.fetch_add(1, Ordering::SeqCst);
+                    input * input
+                }),
+                version(),
+            ));
+            (squared, rebuild)
+        }
+
+        let container = Container::new();
+        let (squared, rebuild) = container.read(squared_capsule);
+        let set_input = container.read(input_capsule).1;
+        assert_eq!(squared, 1);
+        assert_eq!(COMPUTE_COUNT.load(Ordering::SeqCst), 1);
+
+        rebuild();
+        assert_eq!(container.read(squared_capsule).0, 1);
+        assert_eq!(COMPUTE_COUNT.load(Ordering::SeqCst), 1);
+
+        set_input(3);
+        assert_eq!(container.read(squared_capsule).0, 9);
+        assert_eq!(COMPUTE_COUNT.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn event_bus_drains_batched_events() {
+        static BUILD_COUNT: AtomicU8 = AtomicU8::new(0);
+
+        fn event_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (Vec<u8>, impl CData + Fn(u8)) {
+            BUILD_COUNT.fetch_add(1, Ordering::SeqCst);
+            let (drain_events, emit) = register.register(event_bus());
+            let events = drain_events();
+            assert!(
+                drain_events().is_empty(),
+                "Events should only be drained once"
+            );
+            (events, emit)
+        }
+
+        let container = Container::new();
+        let (events, emit) = container.read(event_capsule);
+        let run_txn = container.read(txn_runner_capsule);
+        assert!(events.is_empty());
+
+        emit(1);
+        assert_eq!(container.read(event_capsule).0, vec![1]);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 2);
+
+        run_txn(Box::new(|| {
+            emit(2);
+            emit(3);
+        }));
+        assert_eq!(container.read(event_capsule).0, vec![2, 3]);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 3);
+    }
+
     #[test]
     fn value_can_change() {
         fn rebuildable_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
@@ -315,6 +1123,26 @@ mod tests {
         assert!(!container.read(is_first_build_capsule).0);
     }
 
+    #[test]
+    fn last_build_instant_tracks_previous_build() {
+        fn build_instants_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (BuildInstants, impl CData + Fn()) {
+            let (instants, ((), rebuild, _)) =
+                register.register((last_build_instant(), raw::<MutRef<_>>(())));
+            (instants, move || rebuild(Box::new(|()| {})))
+        }
+
+        let container = Container::new();
+        let (first, rebuild) = container.read(build_instants_capsule);
+        assert_eq!(first.previous(), None);
+
+        rebuild();
+        let (second, _) = container.read(build_instants_capsule);
+        assert_eq!(second.previous(), Some(first.current()));
+        assert!(second.current() >= first.current());
+    }
+
     #[test]
     fn reducer_can_change() {
         enum CountAction {
@@ -8,6 +8,7 @@ use std::{
 type SideEffectTxn<'f> = Box<dyn 'f + FnOnce()>;
 type SideEffectTxnRunner = Arc<dyn Send + Sync + Fn(SideEffectTxn)>;
 type SideEffectStateMutation<'f> = Box<dyn 'f + FnOnce(&mut dyn Any)>;
+type ContainerFinalizerRegistrar = Arc<dyn Send + Sync + Fn(Box<dyn Send + FnOnce()>)>;
 
 type MultiSideEffectStateMutation<'f> = Box<dyn 'f + FnOnce(&mut [OnceCell<Box<dyn Any + Send>>])>;
 type MultiSideEffectStateMutationRunner = Arc<dyn Send + Sync + Fn(MultiSideEffectStateMutation)>;
@@ -29,6 +30,8 @@ pub fn multi<const LENGTH: usize>(
 fn multi_impl<const LENGTH: usize>(register: SideEffectRegistrar) -> MultiSideEffectRegistrar {
     let default_array: [OnceCell<Box<dyn Any + Send>>; LENGTH] =
         std::array::from_fn(|_| OnceCell::new());
+    let capsule_type_name = register.capsule_type_name();
+    let finalizer_registrar = register.container_finalizer_registrar();
     let (curr_slice, mutation_runner, run_txn) = register.raw(default_array);
     let multi_mutation_runner = Arc::new(move |mutation: MultiSideEffectStateMutation| {
         mutation_runner(Box::new(move |data| mutation(data)));
@@ -38,6 +41,8 @@ fn multi_impl<const LENGTH: usize>(register: SideEffectRegistrar) -> MultiSideEf
         curr_slice: Cell::new(curr_slice),
         multi_mutation_runner,
         run_txn,
+        capsule_type_name,
+        finalizer_registrar,
     }
 }
 
@@ -50,6 +55,8 @@ pub struct MultiSideEffectRegistrar<'a> {
     curr_slice: Cell<&'a mut [OnceCell<Box<dyn Any + Send>>]>,
     multi_mutation_runner: MultiSideEffectStateMutationRunner,
     run_txn: SideEffectTxnRunner,
+    capsule_type_name: &'static str,
+    finalizer_registrar: ContainerFinalizerRegistrar,
 }
 
 impl<'a> MultiSideEffectRegistrar<'a> {
@@ -84,6 +91,8 @@ impl<'a> MultiSideEffectRegistrar<'a> {
         self.curr_slice.replace(rest_slice);
 
         SideEffectRegistrar::new(curr_data, mutation_runner, Arc::clone(&self.run_txn))
+            .with_capsule_type_name(self.capsule_type_name)
+            .with_container_finalizer_registrar(Arc::clone(&self.finalizer_registrar))
             .register(effect)
     }
 }
@@ -95,7 +104,7 @@ where
     F: FnOnce(SideEffectRegistrar) -> MultiSideEffectRegistrar,
 {
     type Api<'a> = MultiSideEffectRegistrar<'a>;
-    fn build(self, registrar: SideEffectRegistrar) -> Self::Api<'_> {
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
         self.0(registrar)
     }
 }
@@ -1,4 +1,4 @@
-use std::{any::TypeId, sync::Arc};
+use std::{any::TypeId, marker::PhantomData, sync::Arc};
 
 use rearch::{Capsule, CapsuleHandle, CapsuleKey, SideEffect, SideEffectRegistrar};
 
@@ -9,11 +9,12 @@ use rearch::{Capsule, CapsuleHandle, CapsuleKey, SideEffect, SideEffectRegistrar
 /// (backing capsules must share _the same_ [`Capsule::Data`]).
 ///
 /// Note that there is no free lunch. This side effect has some known limitations:
-/// - No [`Capsule::eq`] support (so no runtime-optimizations when capsule data doesn't change)
+/// - No [`Capsule::eq`] support by default (so no runtime-optimizations when capsule data
+///   doesn't change); see [`overridable_capsule_with_eq`] to opt into it
 /// - Overriding capsules must be [`Sync`], since [`Capsule::Data`] itself is [`Sync`]
 ///   (and the current overriding capsule is stored as [`Capsule::Data`])
 /// - Capsules that have `impl Trait` in their [`Capsule::Data`] are not compatible with each other;
-///   you must use `dyn Trait` (or an enum) instead
+///   you must use `dyn Trait` (or an enum) instead, which [`rearch::dyn_capsule`] can help with
 /// - There's some slight overhead due to dynamic dispatch everywhere in the implementation
 ///   (but is largely minimal in the context of `ReArch` as a whole)
 ///
@@ -54,6 +55,47 @@ pub fn overridable_capsule<Data, C>(
 where
     Data: Send + Sync + 'static,
     C: Capsule<Data = Data> + Sync,
+{
+    overridable_capsule_with_eq::<NeverEq, _, _>(default_capsule)
+}
+
+/// Like [`overridable_capsule`], but with `Cmp` (such as [`PartialEqData`]) as [`Capsule::eq`].
+///
+/// This way, dependents of the resulting [`OverridableCapsule`] are not rebuilt
+/// when its data does not change.
+/// Since [`Capsule::eq`] is an associated function (without access to the capsule instance),
+/// the comparison is specified at the type level rather than as a closure.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, Container};
+/// # use rearch_effects::{overridable_capsule_with_eq, OverridableCapsule, PartialEqData};
+/// fn default_string_capsule(_: CapsuleHandle) -> String {
+///     String::new()
+/// }
+///
+/// fn string_overridable_capsule(
+///     CapsuleHandle { register, .. }: CapsuleHandle,
+/// ) -> OverridableCapsule<String, PartialEqData> {
+///     register.register(overridable_capsule_with_eq::<PartialEqData, _, _>(
+///         default_string_capsule,
+///     ))
+/// }
+///
+/// fn string_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> String {
+///     let curr_capsule = get.as_ref(string_overridable_capsule).clone();
+///     get.as_ref(curr_capsule).clone()
+/// }
+///
+/// assert_eq!(Container::new().read(string_capsule), "");
+/// ```
+pub fn overridable_capsule_with_eq<Cmp, Data, C>(
+    default_capsule: C,
+) -> impl for<'a> SideEffect<Api<'a> = OverridableCapsule<Data, Cmp>>
+where
+    Cmp: DataEq<Data>,
+    Data: Send + Sync + 'static,
+    C: Capsule<Data = Data> + Sync,
 {
     move |register: SideEffectRegistrar<'_>| {
         let (curr_capsule, mutate, _) = register.raw(DynCapsuleHolder::new(default_capsule));
@@ -62,18 +104,42 @@ where
             capsule_setter: Arc::new(move |new_holder| {
                 mutate(Box::new(|curr_holder| *curr_holder = new_holder));
             }),
+            eq: PhantomData,
         }
     }
 }
 
+/// Compares the old and new data of an [`OverridableCapsule`]; see [`Capsule::eq`].
+pub trait DataEq<Data>: 'static {
+    /// Returns whether `old` and `new` are equal (so dependents do not need to be rebuilt).
+    fn eq(old: &Data, new: &Data) -> bool;
+}
+
+/// A [`DataEq`] that never considers data equal, so dependents are always rebuilt.
+pub struct NeverEq;
+impl<Data> DataEq<Data> for NeverEq {
+    fn eq(_old: &Data, _new: &Data) -> bool {
+        false
+    }
+}
+
+/// A [`DataEq`] that compares data via [`PartialEq`].
+pub struct PartialEqData;
+impl<Data: PartialEq> DataEq<Data> for PartialEqData {
+    fn eq(old: &Data, new: &Data) -> bool {
+        old == new
+    }
+}
+
 /// A [`Capsule`] that enables overriding its implementation via [`OverridableCapsule::set`].
 /// See [`overridable_capsule`] for more.
-pub struct OverridableCapsule<Data> {
+pub struct OverridableCapsule<Data, Cmp = NeverEq> {
     capsule_holder: DynCapsuleHolder<Data>,
     capsule_setter: Arc<dyn Fn(DynCapsuleHolder<Data>) + Send + Sync>,
+    eq: PhantomData<fn() -> Cmp>,
 }
 
-impl<Data> OverridableCapsule<Data> {
+impl<Data, Cmp> OverridableCapsule<Data, Cmp> {
     /// Overrides the [`OverridableCapsule`] to point to the supplied [`Capsule`].
     ///
     /// Note that this function mutates the underlying [`rearch::Container`] (and not `self`),
@@ -82,8 +148,21 @@ impl<Data> OverridableCapsule<Data> {
     /// As you shouldn't be using an [`OverridableCapsule`] after calling this method
     /// (to prevent using stale data), this method consumes `self` to prevent possible API misuse.
     /// If, for some reason, you _do_ want to use the outdated capsule after calling this method,
-    /// call [`OverridableCapsule::clone`] first.
+    /// call [`OverridableCapsule::clone`] first (or use [`OverridableCapsule::set_by_ref`]).
     pub fn set<C>(self, capsule: C)
+    where
+        C: Capsule<Data = Data> + Sync,
+    {
+        self.set_by_ref(capsule);
+    }
+
+    /// Like [`OverridableCapsule::set`], but does not consume `self`.
+    ///
+    /// Prefer [`OverridableCapsule::set`] by default.
+    /// This is only intended for when you knowingly want to keep using this (now outdated) handle,
+    /// such as to repeatedly switch implementations in a loop,
+    /// since reading it as a [`Capsule`] would still provide the *old* implementation's data.
+    pub fn set_by_ref<C>(&self, capsule: C)
     where
         C: Capsule<Data = Data> + Sync,
     {
@@ -91,18 +170,20 @@ impl<Data> OverridableCapsule<Data> {
     }
 }
 
-impl<Data> Clone for OverridableCapsule<Data> {
+impl<Data, Cmp> Clone for OverridableCapsule<Data, Cmp> {
     fn clone(&self) -> Self {
         Self {
             capsule_holder: self.capsule_holder.clone(),
             capsule_setter: Arc::clone(&self.capsule_setter),
+            eq: PhantomData,
         }
     }
 }
 
-impl<Data> Capsule for OverridableCapsule<Data>
+impl<Data, Cmp> Capsule for OverridableCapsule<Data, Cmp>
 where
     Data: Send + Sync + 'static,
+    Cmp: DataEq<Data>,
 {
     type Data = Data;
 
@@ -111,7 +192,7 @@ where
     }
 
     fn eq(old: &Self::Data, new: &Self::Data) -> bool {
-        DynCapsuleHolder::eq(old, new)
+        Cmp::eq(old, new)
     }
 
     fn key(&self) -> impl CapsuleKey {
@@ -290,6 +371,64 @@ mod tests {
         assert_eq!(container.read(string_capsule), "");
     }
 
+    #[test]
+    fn overridable_capsule_set_by_ref_does_not_consume() {
+        let container = Container::new();
+        let overridable = container.read(string_overridable_capsule);
+        for (n, expected) in [(1, "1"), (23, "23")] {
+            overridable.set_by_ref(DynamicStringCapsule(n));
+            assert_eq!(container.read(string_capsule), expected);
+        }
+
+        overridable.set_by_ref(foobar_string_capsule);
+        assert_eq!(container.read(string_capsule), "foobar");
+    }
+
+    #[test]
+    fn overridable_capsule_with_eq_skips_unchanged_rebuilds() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        use rearch::{CData, CapsuleHandle};
+
+        use crate::{overridable_capsule_with_eq, OverridableCapsule, PartialEqData};
+
+        static BUILD_COUNT: AtomicU8 = AtomicU8::new(0);
+
+        fn rebuildable_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
+            let ((), rebuild, _) = register.raw(());
+            move || rebuild(Box::new(|()| {}))
+        }
+
+        fn constant_string_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> String {
+            get.as_ref(rebuildable_capsule);
+            "constant".to_owned()
+        }
+
+        fn eq_overridable_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> OverridableCapsule<String, PartialEqData> {
+            register.register(overridable_capsule_with_eq::<PartialEqData, _, _>(
+                constant_string_capsule,
+            ))
+        }
+
+        fn length_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) -> usize {
+            register.register(crate::as_listener());
+            BUILD_COUNT.fetch_add(1, Ordering::SeqCst);
+            let curr_capsule = get.as_ref(eq_overridable_capsule).clone();
+            get.as_ref(curr_capsule).len()
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(length_capsule), 8);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 1);
+
+        // The overridden capsule rebuilds, but with the same data, so length_capsule is skipped
+        container.read(rebuildable_capsule)();
+        assert_eq!(container.read(length_capsule), 8);
+        assert_eq!(BUILD_COUNT.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn overridable_dynamic_capsules_correctly_updates() {
         let container = Container::new();
@@ -0,0 +1,210 @@
+use std::{ops::Deref, sync::Arc};
+
+use rearch::{Capsule, CapsuleHandle, CapsuleKey};
+
+/// Creates a [`Capsule`] that shares the (expensive) resource built by `capsule`
+/// across all of its dependents, invoking `cleanup` on the resource once it is no longer in use.
+///
+/// The resource is kept alive by the resulting capsule's [`RefCounted`] data
+/// (and any clones of it), so `cleanup` is invoked once the capsule is disposed
+/// from the [`rearch::Container`] and every clone of its data has been dropped.
+/// It is also disposed (and then rebuilt, with a new resource) whenever `capsule` must rebuild.
+///
+/// Note: the resulting capsule is *not* disposed automatically when its last dependent is gone.
+/// As it is idempotent (so long as `capsule` is), it is only disposed
+/// once you call [`rearch::Container::collect_garbage`] after its last dependent is gone;
+/// until then, `cleanup` is not invoked, even if every clone of its data was dropped.
+///
+/// Note: `capsule` is built directly within the resulting capsule (and is not read from the
+/// container), so be sure to only read the resulting capsule (and not `capsule` itself)
+/// in your dependents.
+/// The resulting capsule's key includes `cleanup`, so the same `capsule` with different
+/// `cleanup`s creates different resources.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, Container};
+/// # use rearch_effects::{ref_count, RefCounted};
+/// # use std::sync::atomic::{AtomicBool, Ordering};
+/// static IS_POOL_OPEN: AtomicBool = AtomicBool::new(false);
+///
+/// struct DbPool;
+///
+/// fn db_pool_capsule(_: CapsuleHandle) -> DbPool {
+///     IS_POOL_OPEN.store(true, Ordering::SeqCst);
+///     DbPool
+/// }
+///
+/// fn close_db_pool(_: DbPool) {
+///     IS_POOL_OPEN.store(false, Ordering::SeqCst);
+/// }
+///
+/// fn user_count_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+///     let _pool: &RefCounted<DbPool> = get.as_ref(ref_count(db_pool_capsule, close_db_pool));
+///     0 // a real capsule would query the database with the pool here
+/// }
+///
+/// let container = Container::new();
+/// assert_eq!(container.read(user_count_capsule), 0);
+/// assert!(IS_POOL_OPEN.load(Ordering::SeqCst));
+///
+/// container.collect_garbage();
+/// assert!(!IS_POOL_OPEN.load(Ordering::SeqCst));
+/// ```
+pub fn ref_count<C>(
+    capsule: C,
+    cleanup: fn(C::Data),
+) -> impl Capsule<Data = RefCounted<C::Data>> + Clone
+where
+    C: Capsule + Clone,
+{
+    RefCountCapsule { capsule, cleanup }
+}
+
+/// A shared, reference counted resource created by a [`ref_count`] capsule.
+/// See [`ref_count`] for more.
+pub struct RefCounted<T>(Arc<RefCountedInner<T>>);
+
+struct RefCountedInner<T> {
+    // NOTE: this is only None while being dropped, so that it can be passed into cleanup
+    resource: Option<T>,
+    cleanup: fn(T),
+}
+
+impl<T> Drop for RefCountedInner<T> {
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            (self.cleanup)(resource);
+        }
+    }
+}
+
+impl<T> Deref for RefCounted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+            .resource
+            .as_ref()
+            .expect("The resource is only taken when dropped")
+    }
+}
+
+impl<T> Clone for RefCounted<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+struct RefCountCapsule<C: Capsule> {
+    capsule: C,
+    cleanup: fn(C::Data),
+}
+
+impl<C: Capsule + Clone> Clone for RefCountCapsule<C> {
+    fn clone(&self) -> Self {
+        Self {
+            capsule: self.capsule.clone(),
+            cleanup: self.cleanup,
+        }
+    }
+}
+
+impl<C: Capsule> Capsule for RefCountCapsule<C> {
+    type Data = RefCounted<C::Data>;
+
+    fn build(&self, handle: CapsuleHandle) -> Self::Data {
+        RefCounted(Arc::new(RefCountedInner {
+            resource: Some(self.capsule.build(handle)),
+            cleanup: self.cleanup,
+        }))
+    }
+
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+        Arc::ptr_eq(&old.0, &new.0)
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        (self.capsule.key(), self.cleanup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    use rearch::{CapsuleHandle, Container};
+
+    use crate::{ref_count, RefCounted};
+
+    static OPEN_CONNECTIONS: AtomicU8 = AtomicU8::new(0);
+
+    struct Connection;
+
+    fn connection_capsule(_: CapsuleHandle) -> Connection {
+        OPEN_CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+        Connection
+    }
+
+    fn close_connection(_: Connection) {
+        OPEN_CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn shared_connection_capsule(
+        CapsuleHandle { mut get, .. }: CapsuleHandle,
+    ) -> RefCounted<Connection> {
+        get.as_ref(ref_count(connection_capsule, close_connection))
+            .clone()
+    }
+
+    fn first_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+        get.as_ref(shared_connection_capsule);
+        1
+    }
+
+    fn second_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+        get.as_ref(shared_connection_capsule);
+        2
+    }
+
+    #[test]
+    fn ref_count_cleans_up_after_last_use() {
+        let container = Container::new();
+        assert_eq!(container.read((first_capsule, second_capsule)), (1, 2));
+        assert_eq!(OPEN_CONNECTIONS.load(Ordering::SeqCst), 1);
+
+        let connection = container.read(shared_connection_capsule);
+        container.collect_garbage();
+        assert_eq!(OPEN_CONNECTIONS.load(Ordering::SeqCst), 1);
+
+        drop(connection);
+        assert_eq!(OPEN_CONNECTIONS.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn ref_count_with_different_cleanups_are_different_capsules() {
+        static FIRST_CLEANUPS: AtomicU8 = AtomicU8::new(0);
+        static SECOND_CLEANUPS: AtomicU8 = AtomicU8::new(0);
+
+        fn resource_capsule(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn first_cleanup(_: u8) {
+            FIRST_CLEANUPS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn second_cleanup(_: u8) {
+            SECOND_CLEANUPS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let container = Container::new();
+        drop(container.read((
+            ref_count(resource_capsule, first_cleanup),
+            ref_count(resource_capsule, second_cleanup),
+        )));
+        container.collect_garbage();
+        assert_eq!(FIRST_CLEANUPS.load(Ordering::SeqCst), 1);
+        assert_eq!(SECOND_CLEANUPS.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,57 @@
+use rand::{rngs::SmallRng, SeedableRng};
+use rearch::{CData, SideEffect, SideEffectRegistrar};
+
+use crate::{raw, EffectLifetimeFixer1, MutRef};
+
+/// Provides a deterministic [`SmallRng`] that is seeded with `seed` on the first build,
+/// alongside a callback that resets the [`SmallRng`] back to its initial `seed` (and rebuilds).
+///
+/// The [`SmallRng`]'s state persists across rebuilds,
+/// so the generated sequence remains stable until it is explicitly reset.
+/// This is useful for reproducible simulations.
+#[must_use]
+pub fn rng(seed: u64) -> impl for<'a> SideEffect<Api<'a> = (&'a mut SmallRng, impl CData + Fn())> {
+    EffectLifetimeFixer1::<_, MutRef<SmallRng>>::new(move |register: SideEffectRegistrar| {
+        let (rng, rebuild, _) = register.register(raw::<MutRef<_>>(SmallRng::seed_from_u64(seed)));
+        let reset = move || {
+            rebuild(Box::new(move |rng| *rng = SmallRng::seed_from_u64(seed)));
+        };
+        (rng, reset)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    use rearch::{CapsuleHandle, Container};
+
+    #[test]
+    fn rng_persists_across_rebuilds_until_reset() {
+        fn rebuildable_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
+            let ((), rebuild, _) = register.raw(());
+            move || rebuild(Box::new(|()| {}))
+        }
+
+        fn rng_capsule(
+            CapsuleHandle { mut get, register }: CapsuleHandle,
+        ) -> (u64, impl CData + Fn()) {
+            get.as_ref(rebuildable_capsule);
+            let (rng, reset) = register.register(rng(123));
+            (rng.random(), reset)
+        }
+
+        let mut expected_rng = SmallRng::seed_from_u64(123);
+        let first = expected_rng.random::<u64>();
+        let second = expected_rng.random::<u64>();
+
+        let container = Container::new();
+        assert_eq!(container.read(rng_capsule).0, first);
+
+        container.read(rebuildable_capsule)();
+        assert_eq!(container.read(rng_capsule).0, second);
+
+        container.read(rng_capsule).1();
+        assert_eq!(container.read(rng_capsule).0, first);
+    }
+}
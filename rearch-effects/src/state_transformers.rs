@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
 use crate::StateTransformer;
 
@@ -120,3 +120,30 @@ impl<T: Clone + Send + 'static, F: 'static + Send + Fn() -> T> StateTransformer
         self.0.clone()
     }
 }
+
+/// A [`StateTransformer`] that provides an `Arc<T>` as a part of the side effect's api,
+/// so that large data can be handed out without needing to deep clone it.
+///
+/// This follows the "cheap Clone" guidance of [`rearch::Capsule::Data`];
+/// returning the `Arc<T>` from a capsule lets [`rearch::Container::read`] clone just the `Arc`.
+///
+/// Mutations are copy-on-write (via [`Arc::make_mut`]): when a previously provided `Arc<T>`
+/// is still alive, the state is first cloned so that the old `Arc<T>` is left untouched.
+/// Otherwise, the state is mutated in place.
+pub struct ArcCloned<T>(Arc<T>);
+impl<T: Clone + Send + Sync + 'static> StateTransformer for ArcCloned<T> {
+    type Input = T;
+    fn from_input(input: Self::Input) -> Self {
+        Self(Arc::new(input))
+    }
+
+    type Inner = T;
+    fn as_inner(&mut self) -> &mut Self::Inner {
+        Arc::make_mut(&mut self.0)
+    }
+
+    type Output<'a> = Arc<T>;
+    fn as_output(&mut self) -> Self::Output<'_> {
+        Arc::clone(&self.0)
+    }
+}
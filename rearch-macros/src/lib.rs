@@ -21,10 +21,15 @@ pub fn generate_tuple_side_effect_impl(input: TokenStream) -> TokenStream {
                 let rebuild_all = rebuild_all.clone();
                 Arc::new(move |mutation: Box<dyn FnOnce(&mut dyn Any)>| {
                     rebuild_all(Box::new(move |all_states| {
-                        mutation(all_states.#i.get_mut().expect(EFFECT_FAILED_CAST_MSG).as_mut());
+                        // NOTE: the state is initialized whenever there is a mutation runner for it
+                        if let Some(state) = all_states.#i.get_mut() {
+                            mutation(state.as_mut());
+                        }
                     }));
                 })
-            }, Arc::clone(&run_txn)))
+            }, Arc::clone(&run_txn))
+            .with_capsule_type_name(capsule_type_name)
+            .with_container_finalizer_registrar(Arc::clone(&finalizer_registrar)))
         }
     });
     let effect_impl = quote! {
@@ -33,6 +38,8 @@ pub fn generate_tuple_side_effect_impl(input: TokenStream) -> TokenStream {
 
             #[allow(clippy::unused_unit)]
             fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+                let capsule_type_name = registrar.capsule_type_name();
+                let finalizer_registrar = registrar.container_finalizer_registrar();
                 let (all_states, rebuild_all, run_txn) = registrar.raw((
                     #(#once_cell_inits),*
                 ));
@@ -42,3 +49,300 @@ pub fn generate_tuple_side_effect_impl(input: TokenStream) -> TokenStream {
     };
     effect_impl.into()
 }
+
+/// Derives `Capsule` for a struct that has an inherent
+/// `fn build(&self, handle: CapsuleHandle) -> Data` method.
+/// Forgetting that inherent method is a compile error.
+///
+/// The struct must be annotated with `#[capsule(data = Data)]`,
+/// optionally with `eq` (as in `#[capsule(data = Data, eq)]`) to compare data via `PartialEq`.
+/// Fields annotated with `#[capsule_key]` are cloned to form the capsule's key;
+/// when no fields are annotated, the capsule is static.
+#[proc_macro_derive(Capsule, attributes(capsule, capsule_key))]
+pub fn derive_capsule(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive_capsule_impl(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn derive_capsule_impl(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut data = None;
+    let mut use_eq = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("capsule")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("data") {
+                data = Some(meta.value()?.parse::<syn::Type>()?);
+                Ok(())
+            } else if meta.path.is_ident("eq") {
+                use_eq = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `data = Type` or `eq`"))
+            }
+        })?;
+    }
+    let data = data.ok_or_else(|| {
+        syn::Error::new_spanned(name, "expected a `#[capsule(data = Type)]` attribute")
+    })?;
+
+    let syn::Data::Struct(data_struct) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "Capsule can only be derived for structs",
+        ));
+    };
+    let key_fields = data_struct
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.attrs.iter().any(|a| a.path().is_ident("capsule_key")))
+        .map(|(i, field)| {
+            field.ident.as_ref().map_or_else(
+                || {
+                    let index = syn::Index::from(i);
+                    quote! { ::core::clone::Clone::clone(&self.#index) }
+                },
+                |ident| quote! { ::core::clone::Clone::clone(&self.#ident) },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let eq_fn = eq_fn(use_eq);
+    let key_fn = match key_fields.as_slice() {
+        [] => quote! {},
+        [key] => quote! {
+            fn key(&self) -> impl ::rearch::CapsuleKey {
+                #key
+            }
+        },
+        keys => quote! {
+            fn key(&self) -> impl ::rearch::CapsuleKey {
+                (#(#keys),*)
+            }
+        },
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::rearch::Capsule for #name #ty_generics #where_clause {
+            type Data = #data;
+
+            fn build(&self, handle: ::rearch::CapsuleHandle) -> Self::Data {
+                // NOTE: inherent methods take precedence over trait methods here.
+                // Without an inherent build, this call would otherwise resolve to
+                // `Capsule::build` itself (and recurse forever), so we bring another
+                // `build` into scope to turn that mistake into a compile error instead.
+                #[allow(dead_code)]
+                trait CapsuleDeriveRequiresInherentBuild {
+                    fn build(&self, _: CapsuleDeriveMissingInherentBuild);
+                }
+                impl<T: ?Sized> CapsuleDeriveRequiresInherentBuild for T {
+                    fn build(&self, _: CapsuleDeriveMissingInherentBuild) {}
+                }
+                struct CapsuleDeriveMissingInherentBuild;
+
+                Self::build(self, handle)
+            }
+
+            #eq_fn
+
+            #key_fn
+        }
+    })
+}
+
+fn eq_fn(use_eq: bool) -> proc_macro2::TokenStream {
+    if use_eq {
+        quote! {
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+        }
+    } else {
+        quote! {
+            fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+                false
+            }
+        }
+    }
+}
+
+/// Turns a function into a static capsule (a unit struct of the same name).
+///
+/// Each parameter is bound according to its type:
+/// - `CapsuleHandle` binds the capsule's handle
+/// - `CapsuleReader` (or `&mut CapsuleReader`) binds the handle's `get`
+/// - `SideEffectRegistrar` binds the handle's `register`
+/// - any other type must be a capsule that implements `Default` and has `Clone` data,
+///   and the parameter's pattern binds a clone of that capsule's data (not the capsule itself)
+///
+/// Pass `eq` (as in `#[capsule(eq)]`) to compare data via `PartialEq`.
+#[proc_macro_attribute]
+pub fn capsule(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut use_eq = false;
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("eq") {
+            use_eq = true;
+            Ok(())
+        } else {
+            Err(meta.error("expected `eq`"))
+        }
+    });
+    syn::parse_macro_input!(args with args_parser);
+    let input = syn::parse_macro_input!(input as syn::ItemFn);
+    capsule_impl(&input, use_eq)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a `#[capsule]` function parameter is bound, based on its type.
+enum CapsuleParam {
+    Handle,
+    Reader { by_ref: bool },
+    Registrar,
+    Capsule,
+}
+
+impl CapsuleParam {
+    fn of(ty: &syn::Type) -> Self {
+        let (ty, by_ref) = match ty {
+            syn::Type::Reference(reference) if reference.mutability.is_some() => {
+                (&*reference.elem, true)
+            }
+            ty => (ty, false),
+        };
+        let last_segment = match ty {
+            syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+            _ => None,
+        };
+        match last_segment
+            .map(|segment| segment.ident.to_string())
+            .as_deref()
+        {
+            Some("CapsuleHandle") => Self::Handle,
+            Some("CapsuleReader") => Self::Reader { by_ref },
+            Some("SideEffectRegistrar") => Self::Registrar,
+            _ => Self::Capsule,
+        }
+    }
+}
+
+fn check_capsule_signature(sig: &syn::Signature) -> syn::Result<()> {
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "#[capsule] functions cannot be generic",
+        ));
+    }
+    if let Some(modifier) = sig
+        .constness
+        .as_ref()
+        .map(quote::ToTokens::to_token_stream)
+        .or_else(|| sig.asyncness.as_ref().map(quote::ToTokens::to_token_stream))
+        .or_else(|| sig.unsafety.as_ref().map(quote::ToTokens::to_token_stream))
+    {
+        return Err(syn::Error::new_spanned(
+            modifier,
+            "#[capsule] functions cannot be const, async, or unsafe",
+        ));
+    }
+    Ok(())
+}
+
+fn capsule_impl(input: &syn::ItemFn, use_eq: bool) -> syn::Result<proc_macro2::TokenStream> {
+    let syn::ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input;
+    let name = &sig.ident;
+
+    check_capsule_signature(sig)?;
+    let data = match &sig.output {
+        syn::ReturnType::Default => quote! { () },
+        syn::ReturnType::Type(_, ty) => quote! { #ty },
+    };
+
+    // NOTE: mixed_site hygiene keeps these from clashing with the function's own bindings
+    let handle = syn::Ident::new("handle", proc_macro2::Span::mixed_site());
+    let get = syn::Ident::new("get", proc_macro2::Span::mixed_site());
+    let register = syn::Ident::new("register", proc_macro2::Span::mixed_site());
+
+    let mut capsule_reads = Vec::new();
+    let mut handle_bindings = Vec::new();
+    let mut binds_handle = false;
+    let mut binds_handle_parts = false;
+    for input in &sig.inputs {
+        let syn::FnArg::Typed(syn::PatType { pat, ty, .. }) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "#[capsule] functions cannot take self",
+            ));
+        };
+        match CapsuleParam::of(ty) {
+            CapsuleParam::Handle => {
+                binds_handle = true;
+                handle_bindings.push(quote! {
+                    let #pat: #ty = ::rearch::CapsuleHandle {
+                        get: #get,
+                        register: #register,
+                    };
+                });
+            }
+            CapsuleParam::Reader { by_ref } => {
+                binds_handle_parts = true;
+                let reader = if by_ref {
+                    quote! { &mut #get }
+                } else {
+                    quote! { #get }
+                };
+                handle_bindings.push(quote! { let #pat: #ty = #reader; });
+            }
+            CapsuleParam::Registrar => {
+                binds_handle_parts = true;
+                handle_bindings.push(quote! { let #pat: #ty = #register; });
+            }
+            CapsuleParam::Capsule => capsule_reads.push(quote! {
+                let #pat = ::core::clone::Clone::clone(
+                    #get.as_ref(<#ty as ::core::default::Default>::default()),
+                );
+            }),
+        }
+    }
+    if binds_handle && (binds_handle_parts || handle_bindings.len() > 1) {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            "a #[capsule] function that takes a CapsuleHandle cannot take another \
+            CapsuleHandle, CapsuleReader, or SideEffectRegistrar",
+        ));
+    }
+
+    let eq_fn = eq_fn(use_eq);
+    Ok(quote! {
+        #(#attrs)*
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Default)]
+        #vis struct #name;
+
+        impl ::rearch::Capsule for #name {
+            type Data = #data;
+
+            #[allow(unused_mut, unused_variables)]
+            fn build(&self, #handle: ::rearch::CapsuleHandle) -> Self::Data {
+                let ::rearch::CapsuleHandle {
+                    get: mut #get,
+                    register: #register,
+                } = #handle;
+                #(#capsule_reads)*
+                #(#handle_bindings)*
+                #block
+            }
+
+            #eq_fn
+        }
+    })
+}
@@ -0,0 +1,8 @@
+use rearch::capsule;
+
+#[capsule]
+fn identity_capsule<T: Default + Send + Sync + 'static>() -> T {
+    T::default()
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use rearch::capsule;
+
+#[capsule]
+fn count_capsule(_: rearch::CapsuleHandle, _: &mut rearch::CapsuleReader) -> u8 {
+    0
+}
+
+fn main() {}
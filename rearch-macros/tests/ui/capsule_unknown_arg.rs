@@ -0,0 +1,8 @@
+use rearch::capsule;
+
+#[capsule(data = u8)]
+fn count_capsule() -> u8 {
+    0
+}
+
+fn main() {}
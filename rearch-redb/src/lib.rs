@@ -0,0 +1,272 @@
+#![allow(
+    clippy::result_large_err,
+    reason = "redb::Error is surfaced as-is for easier interop with the rest of redb"
+)]
+
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use rearch::{Capsule, CapsuleHandle, CapsuleKey};
+use redb::{Database, Key, TableDefinition, TableError, TableHandle, Value};
+
+/// The persisted value of a [`persisted_capsule`]:
+/// `Ok(None)` when nothing has been persisted under the key yet,
+/// and `Err` when the value could not be read from the [`Database`].
+pub type PersistedValue<V> = Result<Option<V>, Arc<redb::Error>>;
+
+/// Creates a [`Capsule`] whose data is the value stored under `key` in the `table` of `db`,
+/// alongside a [`PersistedSetter`] to write a new value back (and then rebuild).
+///
+/// The value is read from `db` once, when the capsule is first built,
+/// and is then kept in the capsule's state (a failed read is retried on the next build instead);
+/// the [`PersistedSetter`] writes new values through to `db` before updating that state.
+/// As such, all writes to the value should go through the [`PersistedSetter`]
+/// so that the capsule (and its dependents) stay up to date.
+/// A missing table is treated like a missing key (the table is created on the first write).
+///
+/// Capsules are identified by their table name and key, so within one [`rearch::Container`],
+/// each table name and key should only ever be paired with a single `db`.
+///
+/// Keys and values must be owned types (like `u64` or `String`, but not `&str`),
+/// since they are kept in (and read out of) the [`rearch::Container`].
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use rearch::Container;
+/// # use rearch_redb::persisted_capsule;
+/// # use redb::{backends::InMemoryBackend, Database, TableDefinition};
+/// const COUNTS: TableDefinition<String, u64> = TableDefinition::new("counts");
+///
+/// let db = Database::builder().create_with_backend(InMemoryBackend::new())?;
+/// let count_capsule = persisted_capsule(Arc::new(db), COUNTS, "visits".to_owned());
+///
+/// let container = Container::new();
+/// let (count, set_count) = container.read(count_capsule.clone());
+/// assert_eq!(count?, None);
+///
+/// set_count.set(1)?;
+/// assert_eq!(container.read(count_capsule).0?, Some(1));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[must_use]
+pub const fn persisted_capsule<K, V>(
+    db: Arc<Database>,
+    table: TableDefinition<'static, K, V>,
+    key: K,
+) -> PersistedCapsule<K, V>
+where
+    K: for<'a> Key<SelfType<'a> = K> + Clone + Hash + Eq + Debug + Send + Sync,
+    V: for<'a> Value<SelfType<'a> = V> + Clone + Send + Sync,
+{
+    PersistedCapsule { db, table, key }
+}
+
+/// A [`Capsule`] backed by a value in a redb [`Database`]. See [`persisted_capsule`] for more.
+pub struct PersistedCapsule<K: Key + 'static, V: Value + 'static> {
+    db: Arc<Database>,
+    table: TableDefinition<'static, K, V>,
+    key: K,
+}
+
+impl<K, V> PersistedCapsule<K, V>
+where
+    K: for<'a> Key<SelfType<'a> = K>,
+    V: for<'a> Value<SelfType<'a> = V>,
+{
+    fn read(&self) -> Result<Option<V>, redb::Error> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(self.table) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(table.get(&self.key)?.map(|value| value.value()))
+    }
+
+    fn write(&self, value: &V) -> Result<(), redb::Error> {
+        let txn = self.db.begin_write()?;
+        txn.open_table(self.table)?.insert(&self.key, value)?;
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+impl<K: Key + Clone + 'static, V: Value + 'static> Clone for PersistedCapsule<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            db: Arc::clone(&self.db),
+            table: self.table,
+            key: self.key.clone(),
+        }
+    }
+}
+
+impl<K, V> Capsule for PersistedCapsule<K, V>
+where
+    K: for<'a> Key<SelfType<'a> = K> + Clone + Hash + Eq + Debug + Send + Sync,
+    V: for<'a> Value<SelfType<'a> = V> + Clone + Send + Sync,
+{
+    type Data = (PersistedValue<V>, PersistedSetter<K, V>);
+
+    fn build(&self, CapsuleHandle { register, .. }: CapsuleHandle) -> Self::Data {
+        // NOTE: only successfully read values are kept, so that failed reads are retried
+        let (loaded_value, set_loaded_value, _) = register.raw(None::<Option<V>>);
+        let value = match loaded_value {
+            Some(value) => Ok(value.clone()),
+            None => self
+                .read()
+                .map(|value| loaded_value.insert(value).clone())
+                .map_err(Arc::new),
+        };
+        let setter = PersistedSetter {
+            capsule: self.clone(),
+            set_value: Arc::new(move |new_value| {
+                set_loaded_value(Box::new(|value| *value = Some(Some(new_value))));
+            }),
+        };
+        (value, setter)
+    }
+
+    fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+        false
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        (self.table.name().to_owned(), self.key.clone())
+    }
+}
+
+/// Writes a new value for a [`persisted_capsule`]. See [`PersistedSetter::set`].
+pub struct PersistedSetter<K: Key + 'static, V: Value + 'static> {
+    capsule: PersistedCapsule<K, V>,
+    set_value: Arc<dyn Fn(V) + Send + Sync>,
+}
+
+impl<K, V> PersistedSetter<K, V>
+where
+    K: for<'a> Key<SelfType<'a> = K>,
+    V: for<'a> Value<SelfType<'a> = V>,
+{
+    /// Writes `value` to the [`Database`] in its own write transaction,
+    /// and then rebuilds the [`persisted_capsule`] with the new value
+    /// (without reading it back from the [`Database`]).
+    ///
+    /// # Errors
+    /// Returns an error (without rebuilding) when the value could not be written.
+    pub fn set(&self, value: V) -> Result<(), redb::Error> {
+        self.capsule.write(&value)?;
+        (self.set_value)(value);
+        Ok(())
+    }
+}
+
+impl<K: Key + Clone + 'static, V: Value + 'static> Clone for PersistedSetter<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            capsule: self.capsule.clone(),
+            set_value: Arc::clone(&self.set_value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rearch::Container;
+    use redb::{backends::InMemoryBackend, Database, TableDefinition};
+
+    use crate::{persisted_capsule, PersistedCapsule};
+
+    const NAMES: TableDefinition<u64, String> = TableDefinition::new("names");
+
+    #[test]
+    fn persisted_capsule_reads_and_writes_db() {
+        let db = Arc::new(
+            Database::builder()
+                .create_with_backend(InMemoryBackend::new())
+                .expect("In-memory database should be created"),
+        );
+        let first_name = persisted_capsule(Arc::clone(&db), NAMES, 1);
+        let second_name = persisted_capsule(Arc::clone(&db), NAMES, 2);
+
+        let container = Container::new();
+        let (name, set_name) = container.read(first_name.clone());
+        assert_eq!(name.ok(), Some(None));
+
+        set_name
+            .set("Alice".to_owned())
+            .expect("Write should succeed");
+        assert_eq!(
+            container.read(first_name.clone()).0.ok(),
+            Some(Some("Alice".to_owned()))
+        );
+        assert_eq!(container.read(second_name).0.ok(), Some(None));
+
+        // The value is persisted in the db itself, not just the container
+        assert_eq!(
+            Container::new().read(first_name).0.ok(),
+            Some(Some("Alice".to_owned()))
+        );
+    }
+
+    #[test]
+    fn persisted_capsule_keeps_loaded_value_in_state() {
+        let db = Arc::new(
+            Database::builder()
+                .create_with_backend(InMemoryBackend::new())
+                .expect("In-memory database should be created"),
+        );
+        let name = persisted_capsule(Arc::clone(&db), NAMES, 1);
+
+        let container = Container::new();
+        assert_eq!(container.read(name.clone()).0.ok(), Some(None));
+
+        // Writes that bypass the setter are not observed by already-loaded capsules
+        let txn = db.begin_write().expect("Write txn should begin");
+        txn.open_table(NAMES)
+            .expect("Table should open")
+            .insert(1, "Bob".to_owned())
+            .expect("Insert should succeed");
+        txn.commit().expect("Commit should succeed");
+        assert_eq!(container.read(name.clone()).0.ok(), Some(None));
+        assert_eq!(
+            Container::new().read(name).0.ok(),
+            Some(Some("Bob".to_owned()))
+        );
+    }
+
+    #[test]
+    fn persisted_capsule_retries_failed_reads() {
+        const MISTYPED_NAMES: TableDefinition<u64, u64> = TableDefinition::new("names");
+
+        let db = Arc::new(
+            Database::builder()
+                .create_with_backend(InMemoryBackend::new())
+                .expect("In-memory database should be created"),
+        );
+        let name = persisted_capsule(Arc::clone(&db), NAMES, 1);
+        let txn = db.begin_write().expect("Write txn should begin");
+        txn.open_table(MISTYPED_NAMES)
+            .expect("Table should open")
+            .insert(1, 0)
+            .expect("Insert should succeed");
+        txn.commit().expect("Commit should succeed");
+
+        let container = Container::new();
+        assert!(container.read(name.clone()).0.is_err());
+
+        let txn = db.begin_write().expect("Write txn should begin");
+        txn.delete_table(MISTYPED_NAMES)
+            .expect("Table should be deleted");
+        txn.open_table(NAMES)
+            .expect("Table should open")
+            .insert(1, "Alice".to_owned())
+            .expect("Insert should succeed");
+        txn.commit().expect("Commit should succeed");
+
+        // The failed read was not kept, so the next build reads from the db again
+        container.invalidate_type::<PersistedCapsule<u64, String>>();
+        assert_eq!(container.read(name).0.ok(), Some(Some("Alice".to_owned())));
+    }
+}
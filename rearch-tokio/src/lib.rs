@@ -1,7 +1,23 @@
-use effects::{MutRef, StateTransformer};
-use rearch::{CData, SideEffect, SideEffectRegistrar};
+use effects::{Cloned, MutRef, StateTransformer};
+use futures_core::Stream;
+use rearch::{
+    CData, Capsule, CapsuleHandle, CapsuleKey, CapsuleReader, CapsulesWithRefRead, Container,
+    SideEffect, SideEffectRegistrar,
+};
 use rearch_effects as effects;
-use std::{future::Future, sync::Arc};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
 
 struct FunctionalDrop<F: FnOnce()>(Option<F>);
 impl<F: FnOnce()> Drop for FunctionalDrop<F> {
@@ -12,59 +28,159 @@ impl<F: FnOnce()> Drop for FunctionalDrop<F> {
     }
 }
 
+/// Provides a callback to replace the side effect's in-flight task (if any), aborting the old one.
+/// The in-flight task is also aborted when the side effect is disposed.
+///
+/// This immitates `run_on_change`, but for external use (outside of build).
+fn in_flight_task(
+) -> impl for<'a> SideEffect<Api<'a> = impl CData + Fn(Option<tokio::task::AbortHandle>)> {
+    move |register: SideEffectRegistrar| {
+        let (_, on_change) = register.register(effects::state::<MutRef<_>>(FunctionalDrop(None)));
+        move |task: Option<tokio::task::AbortHandle>| {
+            on_change(FunctionalDrop(task.map(|task| move || task.abort())));
+        }
+    }
+}
+
+/// The state of some asynchronous work, such as a future.
+///
+/// Only fallible side effects (like [`future_with_retry`]) can produce an [`AsyncState::Error`];
+/// the error type defaults to [`Infallible`] for all of the others.
+///
+/// Note: the [`AsyncState::Error`] variant was added in v0.11.0,
+/// which is a breaking change for any exhaustive `match` on an [`AsyncState`].
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum AsyncState<T> {
+pub enum AsyncState<T, E = Infallible> {
     Loading(Option<T>),
     Complete(T),
+    Error(E),
 }
 
-impl<T> AsyncState<T> {
+impl<T, E> AsyncState<T, E> {
     pub fn data(self) -> Option<T> {
         match self {
             Self::Loading(previous_data) => previous_data,
             Self::Complete(data) => Some(data),
+            Self::Error(_) => None,
+        }
+    }
+
+    pub fn map<U, F>(self, f: F) -> AsyncState<U, E>
+    where
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            Self::Loading(prev) => AsyncState::Loading(prev.map(f)),
+            Self::Complete(data) => AsyncState::Complete(f(data)),
+            Self::Error(err) => AsyncState::Error(err),
+        }
+    }
+
+    pub fn map_err<U, F>(self, f: F) -> AsyncState<T, U>
+    where
+        F: FnOnce(E) -> U,
+    {
+        match self {
+            Self::Loading(prev) => AsyncState::Loading(prev),
+            Self::Complete(data) => AsyncState::Complete(data),
+            Self::Error(err) => AsyncState::Error(f(err)),
+        }
+    }
+
+    pub const fn as_mut(&mut self) -> AsyncState<&mut T, &mut E> {
+        match *self {
+            Self::Loading(ref mut prev) => AsyncState::Loading(prev.as_mut()),
+            Self::Complete(ref mut data) => AsyncState::Complete(data),
+            Self::Error(ref mut err) => AsyncState::Error(err),
         }
     }
 }
 
-/*
-TODO I think this should be modified to return `impl 'a + FnMut(F) -> AsyncState<T>`
-to remove the idle state
-Also might want to consider cancelation too--maybe the same function should return a cancel token
+struct FutureLifetimeFixer<F, ST>(F, std::marker::PhantomData<ST>);
+impl<F, ST, R1, R2> SideEffect for FutureLifetimeFixer<F, ST>
+where
+    F: FnOnce(SideEffectRegistrar) -> (AsyncState<ST::Output<'_>>, R1, R2),
+    ST: StateTransformer,
+{
+    type Api<'a> = (AsyncState<ST::Output<'a>>, R1, R2);
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        self.0(registrar)
+    }
+}
+impl<F, ST> FutureLifetimeFixer<F, ST> {
+    const fn new<R1, R2>(f: F) -> Self
+    where
+        F: FnOnce(SideEffectRegistrar) -> (AsyncState<ST::Output<'_>>, R1, R2),
+        ST: StateTransformer,
+    {
+        Self(f, std::marker::PhantomData)
+    }
+}
 
+/// Allows you to run a future, whose data is then provided via an [`AsyncState`],
+/// alongside a callback to run a (new) future and a callback to abort the in-flight future.
+///
+/// The state starts as [`AsyncState::Loading`] (with no previous data).
+/// Running a future immediately transitions the state to [`AsyncState::Loading`]
+/// (with any previous data) and spawns the future via [`tokio::spawn`];
+/// once the future resolves, the state becomes [`AsyncState::Complete`].
+/// Running a new future aborts any in-flight one, so a stale future can never complete
+/// after (and overwrite the data of) a newer one.
+/// Aborting the in-flight future leaves the state as-is.
+/// The in-flight future is also aborted when the side effect is disposed.
+///
+/// Note: futures must be run within a Tokio runtime.
+/// This should normally *not* be used with [`MutRef`].
 #[must_use]
-pub fn future<T, F>(
-) -> impl for<'a> SideEffect<Api<'a> = (impl Fn() -> AsyncState<T> + 'a, impl FnMut(F) + 'a)>
+pub fn future<ST: StateTransformer, F>() -> impl for<'a> SideEffect<
+    Api<'a> = (
+        AsyncState<ST::Output<'a>>,
+        impl CData + Fn(F),
+        impl CData + Fn(),
+    ),
+>
 where
-    T: Clone + Send + 'static,
-    F: Future<Output = T> + Send + 'static,
+    F: Future<Output = ST::Input> + Send + 'static,
 {
-    move |register: SideEffectRegistrar<'a>| {
-        let ((state, set_state), mut on_change) = register.register((
-            effects::state(AsyncState::Idle(None)),
-            effects::run_on_change(),
+    FutureLifetimeFixer::<_, ST>::new(move |register: SideEffectRegistrar| {
+        let ((state, mutate_state, run_txn), set_in_flight_task) = register.register((
+            effects::raw::<MutRef<AsyncState<ST>>>(AsyncState::Loading(None)),
+            in_flight_task(),
         ));
-        let state = Rc::new(RefCell::new(state));
-        let get = {
-            let state = Rc::clone(&state);
-            move || state.borrow().clone()
+
+        let state = match *state {
+            AsyncState::Loading(ref mut prev) => {
+                AsyncState::Loading(prev.as_mut().map(ST::as_output))
+            }
+            AsyncState::Complete(ref mut data) => AsyncState::Complete(ST::as_output(data)),
+            AsyncState::Error(never) => match never {},
         };
-        let set = move |future| {
-            let mut state = state.borrow_mut();
-            let old_state = std::mem::replace(*state, AsyncState::Idle(None));
-            **state = AsyncState::Loading(old_state.data());
+        let run = {
+            let set_in_flight_task = set_in_flight_task.clone();
+            move |future| {
+                let set_in_flight_task = set_in_flight_task.clone();
+                let mutate_state = mutate_state.clone();
+                run_txn(Box::new(|| {
+                    mutate_state(Box::new(|state| {
+                        let old_state = std::mem::replace(state, AsyncState::Loading(None));
+                        *state = AsyncState::Loading(old_state.data());
+                    }));
 
-            let set_state = set_state.clone();
-            let handle = tokio::spawn(async move {
-                let data = future.await;
-                set_state(AsyncState::Complete(data));
-            });
-            on_change(move || handle.abort());
+                    let mutate_state = mutate_state.clone();
+                    let handle = tokio::spawn(async move {
+                        let data = ST::from_input(future.await);
+                        mutate_state(Box::new(move |state| {
+                            *state = AsyncState::Complete(data);
+                        }));
+                    });
+                    set_in_flight_task(Some(handle.abort_handle()));
+                }));
+            }
         };
-        (get, set)
-    }
+        let abort = move || set_in_flight_task(None); // aborts the old future if present
+        (state, run, abort)
+    })
 }
-*/
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MutationState<T> {
@@ -92,7 +208,7 @@ impl<T> MutationState<T> {
         }
     }
 
-    pub fn as_mut(&mut self) -> MutationState<&mut T> {
+    pub const fn as_mut(&mut self) -> MutationState<&mut T> {
         match *self {
             Self::Idle(ref mut prev) => MutationState::Idle(prev.as_mut()),
             Self::Loading(ref mut prev) => MutationState::Loading(prev.as_mut()),
@@ -108,7 +224,7 @@ where
     ST: StateTransformer,
 {
     type Api<'a> = (MutationState<ST::Output<'a>>, R1, R2);
-    fn build(self, registrar: SideEffectRegistrar) -> Self::Api<'_> {
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
         self.0(registrar)
     }
 }
@@ -137,21 +253,51 @@ where
     F: Future<Output = ST::Input> + Send + 'static,
 {
     MutationLifetimeFixer::<_, ST>::new(move |register: SideEffectRegistrar| {
-        let ((state, mutate_state, run_txn), (_, on_change)) = register.register((
+        let (state, mutate, clear) = register.register(mutation_with_handle::<ST, F>());
+        let mutate = move |future| drop(mutate(future));
+        (state, mutate, clear)
+    })
+}
+
+/// Like [`mutation`], but `mutate` returns the spawned task's [`tokio::task::JoinHandle`].
+///
+/// The handle can be awaited to know when the mutation settles
+/// (for orchestrating mutations outside of the reactive graph).
+/// Just like with [`mutation`], the task is aborted when a new mutation is triggered
+/// or the mutation is cleared, in which case awaiting the handle returns a cancelled error.
+///
+/// This should normally *not* be used with [`MutRef`].
+#[must_use]
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "Side effect txns are always run synchronously"
+)]
+pub fn mutation_with_handle<ST: StateTransformer, F>() -> impl for<'a> SideEffect<
+    Api<'a> = (
+        MutationState<ST::Output<'a>>,
+        impl CData + Fn(F) -> tokio::task::JoinHandle<()>,
+        impl CData + Fn(),
+    ),
+>
+where
+    F: Future<Output = ST::Input> + Send + 'static,
+{
+    MutationLifetimeFixer::<_, ST>::new(move |register: SideEffectRegistrar| {
+        let ((state, mutate_state, run_txn), set_in_flight_task) = register.register((
             effects::raw::<MutRef<MutationState<ST>>>(MutationState::Idle(None)),
-            // This immitates run_on_change, but for external use (outside of build)
-            effects::state::<MutRef<_>>(FunctionalDrop(None)),
+            in_flight_task(),
         ));
 
         let state = state.as_mut().map(ST::as_output);
         let mutate = {
-            let on_change = on_change.clone();
+            let set_in_flight_task = set_in_flight_task.clone();
             let mutate_state = mutate_state.clone();
             let run_txn = Arc::clone(&run_txn);
             move |future| {
-                let on_change = on_change.clone();
+                let set_in_flight_task = set_in_flight_task.clone();
                 let mutate_state = mutate_state.clone();
-                run_txn(Box::new(move || {
+                let mut spawned_handle = None;
+                run_txn(Box::new(|| {
                     mutate_state(Box::new(|state| {
                         let old_state = std::mem::replace(state, MutationState::Idle(None));
                         *state = MutationState::Loading(old_state.data());
@@ -164,68 +310,1676 @@ where
                             *state = MutationState::Complete(data);
                         }));
                     });
-                    on_change(FunctionalDrop(Some(move || handle.abort())));
+                    set_in_flight_task(Some(handle.abort_handle()));
+                    spawned_handle = Some(handle);
                 }));
+                spawned_handle.expect("Side effect txns are run synchronously")
             }
         };
         let clear = move || {
-            let on_change = on_change.clone();
+            let set_in_flight_task = set_in_flight_task.clone();
             let mutate_state = mutate_state.clone();
             run_txn(Box::new(move || {
                 mutate_state(Box::new(|state| {
                     let old_state = std::mem::replace(state, MutationState::Idle(None));
                     *state = MutationState::Idle(old_state.data());
                 }));
-                on_change(FunctionalDrop(None)); // abort old future if present
+                set_in_flight_task(None); // abort old future if present
             }));
         };
         (state, mutate, clear)
     })
 }
 
-/*
-TODO this should probably be reworked to be hydrate-like instead of state-like
+/// Specifies how [`future_with_retry`] should retry failed futures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    backoff_multiplier: u32,
+}
 
-pub fn async_persist<T, R, Reader, Writer, ReadFuture, WriteFuture>(
-    read: Reader,
-    write: Writer,
-) -> impl for<'a> SideEffect<Api<'a> = (AsyncPersistState<R>, impl FnMut(T) + Send + Sync + Clone)>
+impl RetryPolicy {
+    /// Creates a new [`RetryPolicy`] that makes at most `max_attempts` attempts
+    /// (including the first), waiting 100 milliseconds before the first retry
+    /// and doubling the wait before each subsequent retry.
+    #[must_use]
+    pub const fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 2,
+        }
+    }
+
+    /// Sets the wait before the first retry to `initial_backoff`,
+    /// with the wait being multiplied by `multiplier` before each subsequent retry.
+    #[must_use]
+    pub const fn with_backoff(self, initial_backoff: Duration, multiplier: u32) -> Self {
+        Self {
+            initial_backoff,
+            backoff_multiplier: multiplier,
+            ..self
+        }
+    }
+
+    /// Returns how long to wait after the given (failed) attempt before attempting again.
+    const fn backoff_after(&self, attempt: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor)
+    }
+}
+
+struct RetryLifetimeFixer<F, ST, E>(F, std::marker::PhantomData<(ST, E)>);
+impl<F, ST, E, R1, R2> SideEffect for RetryLifetimeFixer<F, ST, E>
 where
-    T: Send + 'static,
-    R: Clone + Send + 'static,
-    Reader: FnOnce() -> ReadFuture + Send + 'static,
-    Writer: Fn(T) -> WriteFuture + Send + Sync + 'static,
-    ReadFuture: Future<Output = R> + Send + 'static,
-    WriteFuture: Future<Output = R> + Send + 'static,
+    F: FnOnce(SideEffectRegistrar) -> (AsyncState<ST::Output<'_>, E>, u32, R1, R2),
+    ST: StateTransformer,
 {
-    move |register: SideEffectRegistrar| {
-        let ((get_read, mut set_read), (write_state, set_write, _), is_first_build) =
-            register.register((future(), mutation(), effects::is_first_build()));
+    type Api<'a> = (AsyncState<ST::Output<'a>, E>, u32, R1, R2);
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        self.0(registrar)
+    }
+}
+impl<F, ST, E> RetryLifetimeFixer<F, ST, E> {
+    const fn new<R1, R2>(f: F) -> Self
+    where
+        F: FnOnce(SideEffectRegistrar) -> (AsyncState<ST::Output<'_>, E>, u32, R1, R2),
+        ST: StateTransformer,
+    {
+        Self(f, std::marker::PhantomData)
+    }
+}
 
-        if is_first_build {
-            set_read(read());
-        }
-        let state = match (write_state, get_read()) {
-            (AsyncState::Idle(_), AsyncState::Loading(prev))
-            | (AsyncState::Loading(prev @ Some(_)), _) => AsyncPersistState::Loading(prev),
-            (AsyncState::Idle(_), AsyncState::Complete(data)) | (AsyncState::Complete(data), _) => {
-                AsyncPersistState::Complete(data)
-            }
-            (AsyncState::Loading(None), read_state) => {
-                AsyncPersistState::Loading(read_state.data())
-            }
-            (_, AsyncState::Idle(_)) => {
-                unreachable!("Read should never be idle")
+/// Allows you to trigger (and cancel) fallible futures that are retried per a [`RetryPolicy`].
+///
+/// The api is the current [`AsyncState`], the current attempt number
+/// (starting at 1 for each triggered future, or 0 when no future has been triggered/was canceled),
+/// a callback to trigger a new future (via a function that creates it, since it may be retried),
+/// and a callback to cancel the current future (which leaves the current state as-is).
+/// Once all attempts fail, the state settles into [`AsyncState::Error`] with the latest error.
+///
+/// Triggering a new future, canceling, or disposing the capsule aborts any in-progress attempt
+/// (including one waiting to retry).
+///
+/// This should normally *not* be used with [`MutRef`].
+#[must_use]
+#[allow(
+    clippy::type_complexity,
+    reason = "Api mirrors the other async side effects, with the attempt number added"
+)]
+pub fn future_with_retry<ST: StateTransformer, E, F, Fut>(
+    policy: RetryPolicy,
+) -> impl for<'a> SideEffect<
+    Api<'a> = (
+        AsyncState<ST::Output<'a>, E>,
+        u32,
+        impl CData + Fn(F),
+        impl CData + Fn(),
+    ),
+>
+where
+    E: Clone + Send + 'static,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<ST::Input, E>> + Send + 'static,
+{
+    RetryLifetimeFixer::<_, ST, E>::new(move |register: SideEffectRegistrar| {
+        let ((state, mutate_state, run_txn), set_in_flight_task) = register.register((
+            effects::raw::<MutRef<(AsyncState<ST, E>, u32)>>((AsyncState::Loading(None), 0)),
+            in_flight_task(),
+        ));
+
+        let (state, attempt) = state;
+        let state = state.as_mut().map(ST::as_output).map_err(|err| err.clone());
+        let attempt = *attempt;
+
+        let trigger = {
+            let set_in_flight_task = set_in_flight_task.clone();
+            let mutate_state = mutate_state.clone();
+            let run_txn = Arc::clone(&run_txn);
+            move |create_future: F| {
+                let set_in_flight_task = set_in_flight_task.clone();
+                let mutate_state = mutate_state.clone();
+                run_txn(Box::new(move || {
+                    mutate_state(Box::new(|(state, attempt)| {
+                        let old_state = std::mem::replace(state, AsyncState::Loading(None));
+                        *state = AsyncState::Loading(old_state.data());
+                        *attempt = 1;
+                    }));
+
+                    let mutate_state = mutate_state.clone();
+                    let handle = tokio::spawn(async move {
+                        let mut curr_attempt = 1;
+                        loop {
+                            let err = match create_future().await {
+                                Ok(data) => {
+                                    let data = ST::from_input(data);
+                                    mutate_state(Box::new(move |(state, _)| {
+                                        *state = AsyncState::Complete(data);
+                                    }));
+                                    break;
+                                }
+                                Err(err) => err,
+                            };
+
+                            if curr_attempt >= policy.max_attempts {
+                                mutate_state(Box::new(move |(state, _)| {
+                                    *state = AsyncState::Error(err);
+                                }));
+                                break;
+                            }
+
+                            tokio::time::sleep(policy.backoff_after(curr_attempt)).await;
+                            curr_attempt += 1;
+                            mutate_state(Box::new(move |(_, attempt)| {
+                                *attempt = curr_attempt;
+                            }));
+                        }
+                    });
+                    set_in_flight_task(Some(handle.abort_handle()));
+                }));
             }
         };
+        let cancel = move || {
+            let set_in_flight_task = set_in_flight_task.clone();
+            let mutate_state = mutate_state.clone();
+            run_txn(Box::new(move || {
+                mutate_state(Box::new(|(_, attempt)| *attempt = 0));
+                set_in_flight_task(None); // abort old future if present
+            }));
+        };
+        (state, attempt, trigger, cancel)
+    })
+}
 
-        let write = Arc::new(write);
-        let persist = move |new_data| {
-            let write = Arc::clone(&write);
-            set_write(async move { write(new_data).await });
+/// The side effect state of [`optimistic_mutation`].
+struct OptimisticState<ST, E> {
+    value: ST,
+    rollback: Option<ST>,
+    status: AsyncState<(), E>,
+}
+
+struct OptimisticMutationLifetimeFixer<F, ST, E>(F, std::marker::PhantomData<(ST, E)>);
+impl<F, ST, E, R1> SideEffect for OptimisticMutationLifetimeFixer<F, ST, E>
+where
+    F: FnOnce(SideEffectRegistrar) -> (ST::Output<'_>, AsyncState<(), E>, R1),
+    ST: StateTransformer,
+{
+    type Api<'a> = (ST::Output<'a>, AsyncState<(), E>, R1);
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        self.0(registrar)
+    }
+}
+impl<F, ST, E> OptimisticMutationLifetimeFixer<F, ST, E> {
+    const fn new<R1>(f: F) -> Self
+    where
+        F: FnOnce(SideEffectRegistrar) -> (ST::Output<'_>, AsyncState<(), E>, R1),
+        ST: StateTransformer,
+    {
+        Self(f, std::marker::PhantomData)
+    }
+}
+
+/// Allows you to optimistically update some state while an async write is in progress,
+/// rolling back the state if the write fails.
+///
+/// The api is the current (possibly optimistic) state, the status of the latest mutation,
+/// and a callback that takes the optimistic value and the future performing the write.
+/// The optimistic value is applied immediately; once the future completes,
+/// the optimistic value is kept on `Ok` and the pre-mutation value is restored on `Err`
+/// (with the error exposed via [`AsyncState::Error`] in the status).
+///
+/// Triggering a new mutation while one is in progress aborts the old future,
+/// but keeps the original pre-mutation value as the rollback target.
+/// The status is [`AsyncState::Complete`] when no mutation has been triggered yet.
+///
+/// This should normally *not* be used with [`MutRef`].
+#[must_use]
+#[allow(clippy::type_complexity)]
+pub fn optimistic_mutation<ST: StateTransformer, E, F>(
+    initial: ST::Input,
+) -> impl for<'a> SideEffect<
+    Api<'a> = (
+        ST::Output<'a>,
+        AsyncState<(), E>,
+        impl CData + Fn(ST::Input, F),
+    ),
+>
+where
+    E: Clone + Send + 'static,
+    F: Future<Output = Result<(), E>> + Send + 'static,
+{
+    OptimisticMutationLifetimeFixer::<_, ST, E>::new(move |register: SideEffectRegistrar| {
+        let ((state, mutate_state, run_txn), set_in_flight_task) = register.register((
+            effects::raw::<MutRef<OptimisticState<ST, E>>>(OptimisticState {
+                value: ST::from_input(initial),
+                rollback: None,
+                status: AsyncState::Complete(()),
+            }),
+            in_flight_task(),
+        ));
+
+        let status = state
+            .status
+            .as_mut()
+            .map(|()| ())
+            .map_err(|err| err.clone());
+        let value = state.value.as_output();
+
+        let mutate = move |optimistic_value, future: F| {
+            let set_in_flight_task = set_in_flight_task.clone();
+            let mutate_state = mutate_state.clone();
+            run_txn(Box::new(move || {
+                mutate_state(Box::new(|state| {
+                    let old_value =
+                        std::mem::replace(&mut state.value, ST::from_input(optimistic_value));
+                    // Only snapshot the last settled value (and not a prior optimistic one)
+                    state.rollback.get_or_insert(old_value);
+                    state.status = AsyncState::Loading(None);
+                }));
+
+                let mutate_state = mutate_state.clone();
+                let handle = tokio::spawn(async move {
+                    let result = future.await;
+                    mutate_state(Box::new(move |state| {
+                        let rollback = state.rollback.take();
+                        match result {
+                            Ok(()) => state.status = AsyncState::Complete(()),
+                            Err(err) => {
+                                if let Some(rollback) = rollback {
+                                    state.value = rollback;
+                                }
+                                state.status = AsyncState::Error(err);
+                            }
+                        }
+                    }));
+                });
+                set_in_flight_task(Some(handle.abort_handle()));
+            }));
         };
+        (value, status, mutate)
+    })
+}
 
-        (state, persist)
+struct DebounceState<I, T> {
+    latest_input: I,
+    value: T,
+    is_pending: bool,
+    has_settled: bool,
+}
+
+struct DebouncedBuild<I, T, F> {
+    delay: Duration,
+    input: I,
+    compute: F,
+    data: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<I, T, F> SideEffect for DebouncedBuild<I, T, F>
+where
+    I: PartialEq + Send + 'static,
+    T: Send + 'static,
+    F: FnOnce(&I) -> T,
+{
+    type Api<'a> = (&'a T, bool);
+
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        let Self {
+            delay,
+            input,
+            compute,
+            ..
+        } = self;
+        let ((state, mutate_state, _), abort_timer) = registrar.register((
+            effects::raw::<MutRef<Option<DebounceState<I, T>>>>(None),
+            effects::value::<MutRef<_>>(FunctionalDrop(None)),
+        ));
+
+        let Some(mut new_state) = state.take() else {
+            let state = state.insert(DebounceState {
+                value: compute(&input),
+                latest_input: input,
+                is_pending: false,
+                has_settled: false,
+            });
+            return (&state.value, false);
+        };
+
+        if new_state.latest_input != input {
+            new_state.latest_input = input;
+            new_state.is_pending = true;
+            new_state.has_settled = false;
+
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                mutate_state(Box::new(|state| {
+                    if let Some(state) = state {
+                        state.has_settled = true;
+                    }
+                }));
+            });
+            // NOTE: this drops (and thus aborts) the previous timer, if there is one
+            *abort_timer = FunctionalDrop(Some(move || handle.abort()));
+        } else if new_state.is_pending && new_state.has_settled {
+            new_state.value = compute(&new_state.latest_input);
+            new_state.is_pending = false;
+            new_state.has_settled = false;
+        }
+
+        let state = state.insert(new_state);
+        (&state.value, state.is_pending)
+    }
+}
+
+/// Defers a capsule's own expensive computation until its `input` settles,
+/// which is useful for situations like search-as-you-type.
+///
+/// On the first build, `compute` is invoked immediately.
+/// Afterward, whenever `input` changes, `compute` is deferred until `input` has stopped changing
+/// for `delay`, at which point the capsule is rebuilt and `compute` is invoked with the latest
+/// `input` (any pending timer is aborted whenever `input` changes again, just like [`mutation`]).
+///
+/// Provides the latest computed value, alongside whether a recomputation is pending.
+/// During the debounce window, the provided value is the stale, previously computed one.
+pub fn debounced_build<I, T, F>(
+    delay: Duration,
+    input: I,
+    compute: F,
+) -> impl for<'a> SideEffect<Api<'a> = (&'a T, bool)>
+where
+    I: PartialEq + Send + 'static,
+    T: Send + 'static,
+    F: FnOnce(&I) -> T,
+{
+    DebouncedBuild {
+        delay,
+        input,
+        compute,
+        data: std::marker::PhantomData,
+    }
+}
+
+struct FutureOnce<ST, M> {
+    make_future: M,
+    state: std::marker::PhantomData<fn() -> ST>,
+}
+
+impl<ST, M, F> SideEffect for FutureOnce<ST, M>
+where
+    ST: StateTransformer,
+    M: FnOnce() -> F,
+    F: Future<Output = ST::Input> + Send + 'static,
+{
+    type Api<'a> = AsyncState<ST::Output<'a>>;
+
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        let ((state, mutate_state, _), abort_future, is_first_build) = registrar.register((
+            effects::raw::<MutRef<Option<ST>>>(None),
+            effects::value::<MutRef<_>>(FunctionalDrop(None)),
+            effects::is_first_build(),
+        ));
+
+        if is_first_build {
+            let future = (self.make_future)();
+            let handle = tokio::spawn(async move {
+                let data = ST::from_input(future.await);
+                mutate_state(Box::new(move |state| *state = Some(data)));
+            });
+            *abort_future = FunctionalDrop(Some(move || handle.abort()));
+        }
+
+        state.as_mut().map_or(AsyncState::Loading(None), |data| {
+            AsyncState::Complete(data.as_output())
+        })
+    }
+}
+
+/// Runs the future created by `make_future` exactly once, on the capsule's first build,
+/// providing its [`AsyncState`] (which is [`AsyncState::Loading`] until the future completes).
+///
+/// This is useful for one-shot async initialization (like loading a config or connecting).
+/// Subsequent rebuilds never re-run the future, but disposing the capsule aborts the future
+/// (if it is still running) and discards its state, so a re-initialized capsule runs it again.
+///
+/// This should normally *not* be used with [`MutRef`].
+pub fn future_once<ST, F>(
+    make_future: impl FnOnce() -> F,
+) -> impl for<'a> SideEffect<Api<'a> = AsyncState<ST::Output<'a>>>
+where
+    ST: StateTransformer,
+    F: Future<Output = ST::Input> + Send + 'static,
+{
+    FutureOnce {
+        make_future,
+        state: std::marker::PhantomData::<fn() -> ST>,
+    }
+}
+
+struct AdaptivePollState<T> {
+    value: T,
+    delay: Duration,
+    is_due: bool,
+}
+
+struct AdaptivePoll<T, F> {
+    initial: Duration,
+    max: Duration,
+    poll: F,
+    data: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, F> SideEffect for AdaptivePoll<T, F>
+where
+    T: PartialEq + Send + 'static,
+    F: FnOnce() -> T,
+{
+    type Api<'a> = &'a T;
+
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        let Self {
+            initial, max, poll, ..
+        } = self;
+        let ((state, mutate_state, _), abort_timer) = registrar.register((
+            effects::raw::<MutRef<Option<AdaptivePollState<T>>>>(None),
+            effects::value::<MutRef<_>>(FunctionalDrop(None)),
+        ));
+
+        let new_state = match state.take() {
+            // Rebuilt for some other reason, so the current timer is left running
+            Some(curr_state) if !curr_state.is_due => return &state.insert(curr_state).value,
+            Some(curr_state) => {
+                let value = poll();
+                let delay = if value == curr_state.value {
+                    curr_state.delay.saturating_mul(2).min(max)
+                } else {
+                    initial
+                };
+                AdaptivePollState {
+                    value,
+                    delay,
+                    is_due: false,
+                }
+            }
+            None => AdaptivePollState {
+                value: poll(),
+                delay: initial,
+                is_due: false,
+            },
+        };
+
+        let delay = new_state.delay;
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            mutate_state(Box::new(|state| {
+                if let Some(state) = state {
+                    state.is_due = true;
+                }
+            }));
+        });
+        // NOTE: this drops (and thus aborts) the previous timer, if there is one
+        *abort_timer = FunctionalDrop(Some(move || handle.abort()));
+
+        &state.insert(new_state).value
+    }
+}
+
+/// Polls with exponential backoff, which is useful for polling data that is often stable.
+///
+/// `poll` is invoked on the first build, and then again (in a rebuild) after `initial`.
+/// Every time `poll` returns the same value as before, the delay until the next poll is doubled
+/// (up to `max`); when the value changes, the delay is reset back to `initial`.
+/// Rebuilds for other reasons (like a dependency changing) reuse the current value
+/// and do not affect the polling schedule.
+///
+/// Provides the most recently polled value.
+/// The pending timer is aborted when the capsule is disposed, so disposed capsules stop polling.
+///
+/// Note: this must be used within a Tokio runtime.
+pub fn adaptive_poll<T, F>(
+    initial: Duration,
+    max: Duration,
+    poll: F,
+) -> impl for<'a> SideEffect<Api<'a> = &'a T>
+where
+    T: PartialEq + Send + 'static,
+    F: FnOnce() -> T,
+{
+    AdaptivePoll {
+        initial,
+        max,
+        poll,
+        data: std::marker::PhantomData,
+    }
+}
+
+/// Tracks the previously settled (i.e., [`AsyncState::Complete`]) value of an [`AsyncState`]
+/// across builds, as provided by [`previous_settled`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettledTracker<T> {
+    last_settled: Option<T>,
+    previous_settled: Option<T>,
+}
+
+impl<T: Clone + PartialEq> SettledTracker<T> {
+    /// Records the `current` [`AsyncState`],
+    /// returning it back alongside the previous settled value, which is:
+    /// - When `current` is [`AsyncState::Complete`], the value that was complete before it
+    /// - Otherwise, the most recently completed value
+    pub fn track<E>(&mut self, current: AsyncState<T, E>) -> (AsyncState<T, E>, Option<T>) {
+        let previous_settled = match &current {
+            AsyncState::Complete(data) => {
+                if self.last_settled.as_ref() != Some(data) {
+                    self.previous_settled = self.last_settled.replace(data.clone());
+                }
+                self.previous_settled.clone()
+            }
+            AsyncState::Loading(_) | AsyncState::Error(_) => self.last_settled.clone(),
+        };
+        (current, previous_settled)
+    }
+}
+
+/// Provides a [`SettledTracker`] to track the previously settled value of an [`AsyncState`]
+/// (such as one from [`future`]), which is handy for animating between an old and a new value.
+///
+/// Pass the capsule's current [`AsyncState`] into [`SettledTracker::track`] on every build.
+/// Loading and error states never replace the previous settled value,
+/// so rapidly re-triggered futures will still transition from the last fully-resolved value.
+/// Completing with a value equal to the last completed value is not considered a transition.
+#[must_use]
+pub fn previous_settled<T>() -> impl for<'a> SideEffect<Api<'a> = &'a mut SettledTracker<T>>
+where
+    T: Send + 'static,
+{
+    effects::value::<MutRef<_>>(SettledTracker {
+        last_settled: None,
+        previous_settled: None,
+    })
+}
+
+/// Provides a [`CancellationToken`] scoped to the current build of the capsule,
+/// which is cancelled as soon as the capsule rebuilds or is disposed.
+///
+/// Every build provides a fresh token (and cancels the previous one),
+/// so tasks spawned with the token (say, via [`tokio::select!`] on
+/// [`CancellationToken::cancelled`]) are automatically bounded to the current build.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::CapsuleHandle;
+/// # use rearch_tokio::build_scope;
+/// fn poller_capsule(CapsuleHandle { register, .. }: CapsuleHandle) {
+///     let scope = register.register(build_scope());
+///     tokio::spawn(async move {
+///         scope.cancelled().await;
+///         println!("The capsule rebuilt or was disposed");
+///     });
+/// }
+/// ```
+#[must_use]
+pub fn build_scope() -> impl for<'a> SideEffect<Api<'a> = CancellationToken> {
+    move |register: SideEffectRegistrar| {
+        let cancel_previous = register.register(effects::value::<MutRef<_>>(FunctionalDrop(None)));
+        let token = CancellationToken::new();
+        // NOTE: this drops (and thus cancels) the previous token, if there is one
+        *cancel_previous = FunctionalDrop(Some({
+            let token = token.clone();
+            move || token.cancel()
+        }));
+        token
+    }
+}
+
+/// A set of capsules whose data are [`AsyncState`]s (with the same error type),
+/// which can be aggregated via [`suspense`].
+///
+/// This is implemented for tuples of (up to 8) such capsules.
+pub trait SuspenseCapsules: Clone + Send + 'static {
+    /// The error type shared by all of the capsules' [`AsyncState`]s.
+    type Error: Clone + Send + Sync + 'static;
+
+    #[doc(hidden)]
+    fn aggregate(&self, get: &mut CapsuleReader) -> AsyncState<(), Self::Error>;
+
+    #[doc(hidden)]
+    fn key(&self) -> impl CapsuleKey;
+}
+
+/// Combines `state` into the running suspense aggregate `acc`,
+/// where errors take precedence over loading, which takes precedence over completion.
+fn aggregate_suspense<T, E: Clone>(
+    acc: AsyncState<(), E>,
+    state: &AsyncState<T, E>,
+) -> AsyncState<(), E> {
+    match (acc, state) {
+        (acc @ AsyncState::Error(_), _) => acc,
+        (_, AsyncState::Error(err)) => AsyncState::Error(err.clone()),
+        (_, AsyncState::Loading(_)) | (AsyncState::Loading(_), _) => AsyncState::Loading(None),
+        (AsyncState::Complete(()), AsyncState::Complete(_)) => AsyncState::Complete(()),
+    }
+}
+
+macro_rules! impl_suspense_capsules {
+    ($($capsule:ident $data:ident),+) => {
+        impl<$($capsule, $data,)+ E> SuspenseCapsules for ($($capsule,)+)
+        where
+            $($capsule: Capsule<Data = AsyncState<$data, E>> + Clone,)+
+            E: Clone + Send + Sync + 'static,
+        {
+            type Error = E;
+
+            #[allow(non_snake_case)]
+            fn aggregate(&self, get: &mut CapsuleReader) -> AsyncState<(), E> {
+                let ($($capsule,)+) = self;
+                let acc = AsyncState::Complete(());
+                $(let acc = aggregate_suspense(acc, get.as_ref($capsule.clone()));)+
+                acc
+            }
+
+            #[allow(non_snake_case)]
+            fn key(&self) -> impl CapsuleKey {
+                let ($($capsule,)+) = self;
+                ($($capsule.key(),)+)
+            }
+        }
+    };
+}
+impl_suspense_capsules!(A TA);
+impl_suspense_capsules!(A TA, B TB);
+impl_suspense_capsules!(A TA, B TB, C TC);
+impl_suspense_capsules!(A TA, B TB, C TC, D TD);
+impl_suspense_capsules!(A TA, B TB, C TC, D TD, F TF);
+impl_suspense_capsules!(A TA, B TB, C TC, D TD, F TF, G TG);
+impl_suspense_capsules!(A TA, B TB, C TC, D TD, F TF, G TG, H TH);
+impl_suspense_capsules!(A TA, B TB, C TC, D TD, F TF, G TG, H TH, I TI);
+
+/// Creates a capsule that aggregates the [`AsyncState`]s of the supplied (tuple of) `capsules`
+/// into one, which is handy for gating a loading indicator on a whole tree of async capsules.
+///
+/// The resulting [`AsyncState`] is:
+/// - [`AsyncState::Error`] with the first error, if any of the capsules have errored
+/// - Otherwise, [`AsyncState::Loading`] if any of the capsules are loading
+/// - Otherwise (when all of the capsules are complete), [`AsyncState::Complete`]
+///
+/// Dependents are only rebuilt when the resulting [`AsyncState`]'s variant changes
+/// (or on any error).
+///
+/// # Examples
+/// ```rust
+/// # use rearch::CapsuleHandle;
+/// # use rearch_effects::Cloned;
+/// # use rearch_tokio::{future_once, suspense, AsyncState};
+/// fn user_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> AsyncState<String> {
+///     register.register(future_once::<Cloned<_>, _>(|| async { "Alice".to_owned() }))
+/// }
+///
+/// fn settings_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> AsyncState<bool> {
+///     register.register(future_once::<Cloned<_>, _>(|| async { true }))
+/// }
+///
+/// fn show_spinner_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> bool {
+///     let loading_state = get.as_ref(suspense((user_capsule, settings_capsule)));
+///     matches!(loading_state, AsyncState::Loading(_))
+/// }
+/// ```
+pub fn suspense<Capsules: SuspenseCapsules>(
+    capsules: Capsules,
+) -> impl Capsule<Data = AsyncState<(), Capsules::Error>> + Clone {
+    SuspenseCapsule(capsules)
+}
+
+#[derive(Clone)]
+struct SuspenseCapsule<Capsules>(Capsules);
+
+impl<Capsules: SuspenseCapsules> Capsule for SuspenseCapsule<Capsules> {
+    type Data = AsyncState<(), Capsules::Error>;
+
+    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+        self.0.aggregate(&mut get)
+    }
+
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+        matches!(
+            (old, new),
+            (AsyncState::Loading(_), AsyncState::Loading(_))
+                | (AsyncState::Complete(()), AsyncState::Complete(()))
+        )
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        self.0.key()
+    }
+}
+
+/// Aborts all of the pending receiver tasks of a [`oneshot_state`] once dropped.
+#[derive(Default)]
+struct PendingReceivers(Mutex<Vec<tokio::task::AbortHandle>>);
+impl PendingReceivers {
+    fn push(&self, handle: tokio::task::AbortHandle) {
+        let mut handles = lock_ignoring_poison(&self.0);
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(handle);
+    }
+}
+impl Drop for PendingReceivers {
+    fn drop(&mut self) {
+        let handles = self
+            .0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for handle in handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+/// Provides the value received over a [`tokio::sync::oneshot`] channel (or `None` until then),
+/// alongside a factory to create a new [`oneshot::Sender`] for the channel.
+///
+/// This bridges imperative async completion (like a callback-based api, or a spawned task
+/// that does not fit [`future`] or [`mutation`]) into the reactive graph:
+/// hand a sender to the async work, and once a value is sent over it,
+/// the state is set to that value and the capsule is rebuilt.
+/// Every sender is independent, so each one created can set the state (once).
+///
+/// When the capsule is disposed, any pending receivers are dropped,
+/// so their senders will fail to send.
+///
+/// `T` must be [`Clone`] because the received value stays in the side effect's state
+/// and a copy of it is handed out on every build;
+/// to receive a value that isn't [`Clone`], send an [`Arc`] of it instead.
+///
+/// Note: the sender factory must be called within a Tokio runtime.
+#[must_use]
+pub fn oneshot_state<T: Clone + Send + 'static>(
+) -> impl for<'a> SideEffect<Api<'a> = (Option<T>, impl CData + Fn() -> oneshot::Sender<T>)> {
+    move |register: SideEffectRegistrar| {
+        let ((value, set_value, _), pending_receivers) = register.register((
+            effects::raw::<Cloned<_>>(None),
+            effects::value::<Cloned<_>>(Arc::new(PendingReceivers::default())),
+        ));
+        // NOTE: the receivers are owned by the side effect state (and not the sender factory),
+        // so that they are dropped on dispose, even if a copy of the factory is still around
+        let pending_receivers = Arc::downgrade(&pending_receivers);
+        let create_sender = move || {
+            let (sender, receiver) = oneshot::channel();
+            let Some(pending_receivers) = pending_receivers.upgrade() else {
+                return sender; // the capsule was disposed, so the receiver is dropped right away
+            };
+            let set_value = set_value.clone();
+            let handle = tokio::spawn(async move {
+                if let Ok(value) = receiver.await {
+                    set_value(Box::new(|state| *state = Some(value)));
+                }
+            });
+            pending_receivers.push(handle.abort_handle());
+            sender
+        };
+        (value, create_sender)
+    }
+}
+
+struct BatchedWriterState<T, F: Fn(Vec<T>)> {
+    batch: Arc<Mutex<Vec<T>>>,
+    flush: Arc<F>,
+    timer: tokio::task::JoinHandle<()>,
+}
+impl<T, F: Fn(Vec<T>)> Drop for BatchedWriterState<T, F> {
+    fn drop(&mut self) {
+        self.timer.abort();
+        let batch = std::mem::take(&mut *lock_ignoring_poison(&self.batch));
+        if !batch.is_empty() {
+            (self.flush)(batch);
+        }
+    }
+}
+
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Batches up items to write, such as to a database, and periodically `flush`es them together,
+/// which is far more efficient than writing each item on its own for write-heavy workloads.
+///
+/// Provides a `push` callback that adds an item to the current batch (without any rebuilds).
+/// Every `flush_interval`, the current batch (if non-empty) is passed to `flush` and cleared.
+/// When the side effect is disposed, one final `flush` is run for any remaining items.
+///
+/// Note: the periodic flush timer is spawned on the first build,
+/// which therefore must occur within a Tokio runtime.
+/// `flush` is only captured on the first build; those passed in later builds are ignored.
+pub fn batched_writer<T, F>(
+    flush_interval: Duration,
+    flush: F,
+) -> impl for<'a> SideEffect<Api<'a> = impl CData + Fn(T)>
+where
+    T: Send + 'static,
+    F: Fn(Vec<T>) + Send + Sync + 'static,
+{
+    move |register: SideEffectRegistrar| {
+        let (state, _, _) = register.raw(None);
+        let state = state.get_or_insert_with(|| {
+            let batch = Arc::new(Mutex::new(Vec::new()));
+            let flush = Arc::new(flush);
+            let timer = tokio::spawn({
+                let batch = Arc::clone(&batch);
+                let flush = Arc::clone(&flush);
+                async move {
+                    loop {
+                        tokio::time::sleep(flush_interval).await;
+                        let batch = std::mem::take(&mut *lock_ignoring_poison(&batch));
+                        if !batch.is_empty() {
+                            flush(batch);
+                        }
+                    }
+                }
+            });
+            BatchedWriterState {
+                batch,
+                flush,
+                timer,
+            }
+        });
+
+        let batch = Arc::clone(&state.batch);
+        move |item| lock_ignoring_poison(&batch).push(item)
+    }
+}
+
+/// Reads the supplied capsule's current data like [`Container::read`],
+/// but without blocking the async runtime's worker threads under contention.
+///
+/// When the capsule is already initialized and the container isn't locked for writing,
+/// its data is returned immediately; otherwise, the (possibly blocking) read is moved onto
+/// Tokio's blocking thread pool via [`tokio::task::spawn_blocking`].
+///
+/// Note: this must be called from within a Tokio runtime.
+///
+/// # Panics
+/// Propagates any panic that occurs while building the capsule.
+pub async fn read_async<C>(container: &Container, capsule: C) -> C::Data
+where
+    C: Capsule,
+    C::Data: Clone,
+{
+    if let Some(data) = container.try_read(&capsule) {
+        return data;
+    }
+
+    let container = container.clone();
+    tokio::task::spawn_blocking(move || container.read(capsule))
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+/// Eagerly initializes the supplied capsules on Tokio's blocking thread pool.
+///
+/// This is like [`Container::warm_up`], but via [`tokio::task::spawn_blocking`]
+/// so that the async runtime's worker threads are not blocked.
+///
+/// Note: this must be called from within a Tokio runtime.
+///
+/// # Panics
+/// Propagates any panic that occurs while building the capsules.
+pub async fn warm_up_async<Capsules>(container: &Container, capsules: Capsules)
+where
+    Capsules: CapsulesWithRefRead + Send + 'static,
+{
+    let container = container.clone();
+    tokio::task::spawn_blocking(move || container.warm_up(capsules))
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+}
+
+/// A [`Stream`] of a capsule's data, as created with [`value_stream`].
+///
+/// Dropping the `ValueStream` removes its internal listener from the [`Container`].
+pub struct ValueStream<T> {
+    receiver: mpsc::UnboundedReceiver<T>,
+    dispose: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<T> Stream for ValueStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for ValueStream<T> {
+    fn drop(&mut self) {
+        if let Some(dispose) = self.dispose.take() {
+            dispose();
+        }
+    }
+}
+
+// Each stream gets its own unique key, so that several streams
+// (even on the same capsule) can exist in a container simultaneously.
+struct ValueStreamCapsule<C: Capsule> {
+    capsule: C,
+    sender: mpsc::UnboundedSender<C::Data>,
+    key: u64,
+}
+
+impl<C: Capsule + Clone> Clone for ValueStreamCapsule<C> {
+    fn clone(&self) -> Self {
+        Self {
+            capsule: self.capsule.clone(),
+            sender: self.sender.clone(),
+            key: self.key,
+        }
+    }
+}
+
+impl<C> Capsule for ValueStreamCapsule<C>
+where
+    C: Capsule + Clone,
+    C::Data: Clone,
+{
+    type Data = ();
+
+    fn build(&self, CapsuleHandle { mut get, register }: CapsuleHandle) -> Self::Data {
+        register.register(()); // streams are listeners, so prevent the idempotent gc
+
+        // NOTE: sending only fails once the stream is dropped, which is about to dispose us
+        _ = self.sender.send(get.snapshot(self.capsule.clone()));
+    }
+
+    fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+        false
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        self.key
+    }
+}
+
+/// Creates a [`Stream`] of the supplied capsule's data,
+/// which yields the capsule's current data and then its data after every rebuild.
+///
+/// This makes capsules directly consumable by stream combinators and `select!` loops.
+/// Data is buffered (without bound) until it is consumed from the stream,
+/// so every change is observed, in order.
+/// Dropping the returned [`ValueStream`] removes its internal listener from the `container`.
+///
+/// Note: the [`ValueStream`] keeps the `container` alive until it is dropped.
+///
+/// # Concurrency
+/// Internally grabs a write lock, so this function is blocking.
+#[must_use]
+pub fn value_stream<C>(container: &Container, capsule: &C) -> ValueStream<C::Data>
+where
+    C: Capsule + Clone,
+    C::Data: Clone,
+{
+    static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let stream_capsule = ValueStreamCapsule {
+        capsule: capsule.clone(),
+        sender,
+        key: NEXT_KEY.fetch_add(1, Ordering::Relaxed),
+    };
+    container.read(stream_capsule.clone());
+
+    let container = container.clone();
+    ValueStream {
+        receiver,
+        dispose: Some(Box::new(move || {
+            container.dispose(&stream_capsule);
+        })),
+    }
+}
+
+/*
+TODO this should probably be reworked to be hydrate-like instead of state-like
+
+pub fn async_persist<T, R, Reader, Writer, ReadFuture, WriteFuture>(
+    read: Reader,
+    write: Writer,
+) -> impl for<'a> SideEffect<Api<'a> = (AsyncPersistState<R>, impl FnMut(T) + Send + Sync + Clone)>
+where
+    T: Send + 'static,
+    R: Clone + Send + 'static,
+    Reader: FnOnce() -> ReadFuture + Send + 'static,
+    Writer: Fn(T) -> WriteFuture + Send + Sync + 'static,
+    ReadFuture: Future<Output = R> + Send + 'static,
+    WriteFuture: Future<Output = R> + Send + 'static,
+{
+    move |register: SideEffectRegistrar| {
+        let ((get_read, mut set_read), (write_state, set_write, _), is_first_build) =
+            register.register((future(), mutation(), effects::is_first_build()));
+
+        if is_first_build {
+            set_read(read());
+        }
+        let state = match (write_state, get_read()) {
+            (AsyncState::Idle(_), AsyncState::Loading(prev))
+            | (AsyncState::Loading(prev @ Some(_)), _) => AsyncPersistState::Loading(prev),
+            (AsyncState::Idle(_), AsyncState::Complete(data)) | (AsyncState::Complete(data), _) => {
+                AsyncPersistState::Complete(data)
+            }
+            (AsyncState::Loading(None), read_state) => {
+                AsyncPersistState::Loading(read_state.data())
+            }
+            (_, AsyncState::Idle(_)) => {
+                unreachable!("Read should never be idle")
+            }
+        };
+
+        let write = Arc::new(write);
+        let persist = move |new_data| {
+            let write = Arc::clone(&write);
+            set_write(async move { write(new_data).await });
+        };
+
+        (state, persist)
+    }
+}
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use effects::Cloned;
+    use rearch::{CapsuleHandle, Container, ReadPath};
+    use std::{
+        future::Ready,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    async fn wait_for_tasks() {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_once_only_runs_on_first_build() {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+
+        fn init_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (AsyncState<u32>, impl CData + Fn()) {
+            let (init_state, ((), rebuild, _)) = register.register((
+                future_once::<Cloned<_>, _>(|| async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    RUNS.fetch_add(1, Ordering::SeqCst) + 1
+                }),
+                effects::raw::<Cloned<_>>(()),
+            ));
+            (init_state, move || rebuild(Box::new(|()| {})))
+        }
+
+        let container = Container::new();
+        let (init_state, rebuild) = container.read(init_capsule);
+        assert_eq!(init_state, AsyncState::Loading(None));
+        rebuild();
+        wait_for_tasks().await;
+        assert_eq!(container.read(init_capsule).0, AsyncState::Complete(1));
+
+        rebuild();
+        wait_for_tasks().await;
+        assert_eq!(container.read(init_capsule).0, AsyncState::Complete(1));
+
+        assert!(container.dispose(&init_capsule));
+        assert_eq!(container.read(init_capsule).0, AsyncState::Loading(None));
+        wait_for_tasks().await;
+        assert_eq!(container.read(init_capsule).0, AsyncState::Complete(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suspense_waits_for_all_capsules() {
+        async fn sleep_secs(secs: u64) {
+            tokio::time::sleep(Duration::from_secs(secs)).await;
+        }
+
+        fn one_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> AsyncState<u8> {
+            register.register(future_once::<Cloned<_>, _>(|| async {
+                sleep_secs(1).await;
+                1
+            }))
+        }
+        fn two_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> AsyncState<String> {
+            register.register(future_once::<Cloned<_>, _>(|| async {
+                sleep_secs(2).await;
+                "two".to_owned()
+            }))
+        }
+        fn three_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> AsyncState<bool> {
+            register.register(future_once::<Cloned<_>, _>(|| async {
+                sleep_secs(3).await;
+                true
+            }))
+        }
+
+        let all_capsules = suspense((one_capsule, two_capsule, three_capsule));
+        let container = Container::new();
+        assert_eq!(
+            container.read(all_capsules.clone()),
+            AsyncState::Loading(None)
+        );
+
+        // Each capsule completes one second after the previous one
+        for expected in [
+            AsyncState::Loading(None),
+            AsyncState::Loading(None),
+            AsyncState::Complete(()),
+        ] {
+            tokio::time::sleep(Duration::from_millis(1100)).await;
+            assert_eq!(container.read(all_capsules.clone()), expected);
+        }
+        assert_eq!(container.read(one_capsule), AsyncState::Complete(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adaptive_poll_backs_off_while_unchanged() {
+        static POLLS: AtomicU32 = AtomicU32::new(0);
+        static SOURCE: AtomicU32 = AtomicU32::new(0);
+
+        fn poll_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> u32 {
+            *register.register(adaptive_poll(
+                Duration::from_secs(1),
+                Duration::from_secs(4),
+                || {
+                    POLLS.fetch_add(1, Ordering::SeqCst);
+                    SOURCE.load(Ordering::SeqCst)
+                },
+            ))
+        }
+
+        async fn advance_secs(secs: f32) {
+            tokio::time::sleep(Duration::from_secs_f32(secs)).await;
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(poll_capsule), 0);
+        assert_eq!(POLLS.load(Ordering::SeqCst), 1);
+
+        // Polls at 1s, 3s, 7s, and 11s while the value is unchanged
+        advance_secs(1.5).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 2);
+        advance_secs(2.0).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 3);
+        advance_secs(3.0).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 3);
+        advance_secs(1.0).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 4);
+
+        // A change resets the delay, so it then polls at 12s
+        SOURCE.store(1, Ordering::SeqCst);
+        advance_secs(4.0).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 5);
+        assert_eq!(container.read(poll_capsule), 1);
+        advance_secs(1.0).await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 6);
+
+        drop(container);
+        wait_for_tasks().await;
+        assert_eq!(POLLS.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mutation_with_handle_can_be_awaited() {
+        #[allow(clippy::type_complexity)]
+        fn mutation_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            MutationState<u32>,
+            impl CData + Fn(Ready<u32>) -> tokio::task::JoinHandle<()>,
+            impl CData + Fn(),
+        ) {
+            register.register(mutation_with_handle::<Cloned<_>, _>())
+        }
+
+        let container = Container::new();
+        let (state, mutate, clear) = container.read(mutation_capsule);
+        assert_eq!(state, MutationState::Idle(None));
+
+        mutate(std::future::ready(1))
+            .await
+            .expect("Mutation should not be aborted");
+        assert_eq!(
+            container.read(mutation_capsule).0,
+            MutationState::Complete(1)
+        );
+
+        let handle = mutate(std::future::ready(2));
+        clear();
+        assert!(handle
+            .await
+            .expect_err("Mutation should be aborted")
+            .is_cancelled());
+        assert_eq!(
+            container.read(mutation_capsule).0,
+            MutationState::Idle(Some(1))
+        );
+    }
+
+    type BoxedFuture = Pin<Box<dyn Future<Output = u32> + Send>>;
+
+    fn delayed(secs: u64, data: u32) -> BoxedFuture {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_secs(secs)).await;
+            data
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn future_capsule(
+        CapsuleHandle { register, .. }: CapsuleHandle,
+    ) -> (
+        AsyncState<u32>,
+        impl CData + Fn(BoxedFuture),
+        impl CData + Fn(),
+    ) {
+        register.register(future::<Cloned<_>, _>())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_transitions_from_loading_to_complete() {
+        let container = Container::new();
+        let (state, run, _) = container.read(future_capsule);
+        assert_eq!(state, AsyncState::Loading(None));
+
+        run(delayed(1, 1));
+        assert_eq!(container.read(future_capsule).0, AsyncState::Loading(None));
+        wait_for_tasks().await;
+        assert_eq!(container.read(future_capsule).0, AsyncState::Complete(1));
+
+        run(delayed(1, 2));
+        assert_eq!(
+            container.read(future_capsule).0,
+            AsyncState::Loading(Some(1))
+        );
+        wait_for_tasks().await;
+        assert_eq!(container.read(future_capsule).0, AsyncState::Complete(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_aborts_in_flight_future() {
+        let container = Container::new();
+        let (_, run, abort) = container.read(future_capsule);
+
+        // The slow future would complete last, were it not aborted by the second one
+        run(delayed(2, 1));
+        run(delayed(1, 2));
+        wait_for_tasks().await;
+        assert_eq!(container.read(future_capsule).0, AsyncState::Complete(2));
+
+        run(delayed(1, 3));
+        abort();
+        wait_for_tasks().await;
+        assert_eq!(
+            container.read(future_capsule).0,
+            AsyncState::Loading(Some(2))
+        );
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn optimistic_capsule(
+        CapsuleHandle { register, .. }: CapsuleHandle,
+    ) -> (
+        u32,
+        AsyncState<(), &'static str>,
+        impl CData + Fn(u32, Ready<Result<(), &'static str>>),
+    ) {
+        register.register(optimistic_mutation::<Cloned<_>, _, _>(0))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounced_build_waits_for_input_to_settle() {
+        static COMPUTATIONS: AtomicU32 = AtomicU32::new(0);
+
+        fn query_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn(u32)) {
+            register.register(effects::state::<Cloned<_>>(0))
+        }
+
+        fn debounced_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) -> (u32, bool) {
+            let query = get.as_ref(query_capsule).0;
+            let (result, is_pending) =
+                register.register(debounced_build(Duration::from_secs(1), query, |query| {
+                    COMPUTATIONS.fetch_add(1, Ordering::SeqCst);
+                    query * 2
+                }));
+            (*result, is_pending)
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(debounced_capsule), (0, false));
+
+        container.read(query_capsule).1(1);
+        assert_eq!(container.read(debounced_capsule), (0, true));
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        container.read(query_capsule).1(2);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(container.read(debounced_capsule), (0, true));
+        assert_eq!(COMPUTATIONS.load(Ordering::SeqCst), 1);
+
+        wait_for_tasks().await;
+        assert_eq!(container.read(debounced_capsule), (4, false));
+        assert_eq!(COMPUTATIONS.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn optimistic_mutation_keeps_value_on_success() {
+        let container = Container::new();
+        assert_eq!(
+            container.read(optimistic_capsule).1,
+            AsyncState::Complete(())
+        );
+
+        container.read(optimistic_capsule).2(1, std::future::ready(Ok(())));
+        let (value, status, _) = container.read(optimistic_capsule);
+        assert_eq!((value, status), (1, AsyncState::Loading(None)));
+
+        wait_for_tasks().await;
+        let (value, status, _) = container.read(optimistic_capsule);
+        assert_eq!((value, status), (1, AsyncState::Complete(())));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn optimistic_mutation_rolls_back_on_failure() {
+        let container = Container::new();
+        container.read(optimistic_capsule).2(1, std::future::ready(Ok(())));
+        wait_for_tasks().await;
+
+        // Overlapping mutations should roll back to the last settled value
+        container.read(optimistic_capsule).2(2, std::future::ready(Ok(())));
+        container.read(optimistic_capsule).2(3, std::future::ready(Err("failed")));
+        assert_eq!(container.read(optimistic_capsule).0, 3);
+
+        wait_for_tasks().await;
+        let (value, status, _) = container.read(optimistic_capsule);
+        assert_eq!((value, status), (1, AsyncState::Error("failed")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_with_retry_retries_until_success() {
+        static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+        fn flaky_future() -> Ready<Result<u32, &'static str>> {
+            let attempt = ATTEMPTS.fetch_add(1, Ordering::SeqCst) + 1;
+            std::future::ready(if attempt < 3 {
+                Err("failed")
+            } else {
+                Ok(attempt)
+            })
+        }
+
+        #[allow(clippy::type_complexity)]
+        fn retrying_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            AsyncState<u32, &'static str>,
+            u32,
+            impl CData + Fn(fn() -> Ready<Result<u32, &'static str>>),
+            impl CData + Fn(),
+        ) {
+            register.register(future_with_retry::<Cloned<_>, _, _, _>(RetryPolicy::new(3)))
+        }
+
+        let container = Container::new();
+        let (state, attempt, trigger, _) = container.read(retrying_capsule);
+        assert_eq!((state, attempt), (AsyncState::Loading(None), 0));
+
+        trigger(flaky_future);
+        let (state, attempt, _, _) = container.read(retrying_capsule);
+        assert_eq!((state, attempt), (AsyncState::Loading(None), 1));
+
+        wait_for_tasks().await;
+        let (state, attempt, _, _) = container.read(retrying_capsule);
+        assert_eq!((state, attempt), (AsyncState::Complete(3), 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_with_retry_settles_into_error() {
+        #[allow(clippy::type_complexity)]
+        fn retrying_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            AsyncState<u32, &'static str>,
+            u32,
+            impl CData + Fn(fn() -> Ready<Result<u32, &'static str>>),
+            impl CData + Fn(),
+        ) {
+            register.register(future_with_retry::<Cloned<_>, _, _, _>(
+                RetryPolicy::new(2).with_backoff(Duration::from_secs(1), 1),
+            ))
+        }
+
+        let container = Container::new();
+        container.read(retrying_capsule).2(|| std::future::ready(Err("failed")));
+
+        wait_for_tasks().await;
+        let (state, attempt, _, _) = container.read(retrying_capsule);
+        assert_eq!((state, attempt), (AsyncState::Error("failed"), 2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn future_with_retry_cancel_aborts_retries() {
+        static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+        fn failing_future() -> Ready<Result<u32, ()>> {
+            ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Err(()))
+        }
+
+        #[allow(clippy::type_complexity)]
+        fn retrying_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            AsyncState<u32, ()>,
+            u32,
+            impl CData + Fn(fn() -> Ready<Result<u32, ()>>),
+            impl CData + Fn(),
+        ) {
+            register.register(future_with_retry::<Cloned<_>, _, _, _>(
+                RetryPolicy::new(5).with_backoff(Duration::from_secs(1), 1),
+            ))
+        }
+
+        let container = Container::new();
+        container.read(retrying_capsule).2(failing_future);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+
+        container.read(retrying_capsule).3();
+        wait_for_tasks().await;
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+        assert_eq!(container.read(retrying_capsule).1, 0);
+    }
+
+    #[tokio::test]
+    async fn read_async_reads_initialized_and_uninitialized_capsules() {
+        fn count_capsule(_: CapsuleHandle) -> u32 {
+            123
+        }
+
+        let container = Container::new();
+        assert_eq!(read_async(&container, count_capsule).await, 123);
+        assert_eq!(read_async(&container, count_capsule).await, 123);
+    }
+
+    #[test]
+    fn build_scope_cancels_on_rebuild_and_dispose() {
+        fn scoped_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (CancellationToken, impl CData + Fn(u8)) {
+            let (token, (_, set_count)) =
+                register.register((build_scope(), effects::state::<Cloned<_>>(0)));
+            (token, set_count)
+        }
+
+        let container = Container::new();
+        let (first_token, set_count) = container.read(scoped_capsule);
+        assert!(!first_token.is_cancelled());
+
+        set_count(1);
+        let (second_token, _) = container.read(scoped_capsule);
+        assert!(first_token.is_cancelled());
+        assert!(!second_token.is_cancelled());
+
+        container.dispose(&scoped_capsule);
+        assert!(second_token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn oneshot_state_sets_received_value() {
+        fn oneshot_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (Option<u8>, impl CData + Fn() -> oneshot::Sender<u8>) {
+            register.register(oneshot_state())
+        }
+
+        let container = Container::new();
+        let (value, create_sender) = container.read(oneshot_capsule);
+        assert_eq!(value, None);
+
+        create_sender().send(1).expect("Receiver should be pending");
+        tokio::task::yield_now().await;
+        assert_eq!(container.read(oneshot_capsule).0, Some(1));
+
+        let sender = create_sender();
+        assert!(container.dispose(&oneshot_capsule));
+        tokio::task::yield_now().await;
+        assert!(sender.is_closed());
+        assert!(create_sender().is_closed());
+    }
+
+    #[tokio::test]
+    async fn warm_up_async_initializes_capsules() {
+        fn count_capsule(_: CapsuleHandle) -> u32 {
+            123
+        }
+        fn count_plus_one_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+            get.as_ref(count_capsule) + 1
+        }
+
+        let container = Container::new();
+        warm_up_async(&container, (count_capsule, count_plus_one_capsule)).await;
+        assert_eq!(
+            container.read_instrumented((count_capsule, count_plus_one_capsule)),
+            ((123, 124), ReadPath::AllCached)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batched_writer_flushes_periodically_and_on_dispose() {
+        static FLUSHED_BATCHES: Mutex<Vec<Vec<u32>>> = Mutex::new(Vec::new());
+
+        fn writer_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn(u32) {
+            register.register(batched_writer(Duration::from_secs(1), |batch| {
+                FLUSHED_BATCHES
+                    .lock()
+                    .expect("Lock should not be poisoned")
+                    .push(batch);
+            }))
+        }
+
+        let flushed_batches = || {
+            FLUSHED_BATCHES
+                .lock()
+                .expect("Lock should not be poisoned")
+                .clone()
+        };
+
+        let container = Container::new();
+        let push = container.read(writer_capsule);
+        push(1);
+        push(2);
+        assert!(flushed_batches().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(flushed_batches(), vec![vec![1, 2]]);
+
+        wait_for_tasks().await;
+        assert_eq!(flushed_batches(), vec![vec![1, 2]]);
+
+        push(3);
+        drop(container);
+        assert_eq!(flushed_batches(), vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn previous_settled_tracks_last_complete_value() {
+        #[allow(clippy::type_complexity)]
+        fn transition_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (
+            (AsyncState<u32>, Option<u32>),
+            impl CData + Fn(AsyncState<u32>),
+        ) {
+            let ((state, set_state), tracker) = register.register((
+                effects::state::<Cloned<_>>(AsyncState::Loading(None)),
+                previous_settled(),
+            ));
+            (tracker.track(state), set_state)
+        }
+
+        let container = Container::new();
+        let set_state = container.read(transition_capsule).1;
+        assert_eq!(
+            container.read(transition_capsule).0,
+            (AsyncState::Loading(None), None)
+        );
+
+        set_state(AsyncState::Complete(1));
+        assert_eq!(
+            container.read(transition_capsule).0,
+            (AsyncState::Complete(1), None)
+        );
+
+        set_state(AsyncState::Loading(Some(1)));
+        set_state(AsyncState::Loading(Some(1)));
+        assert_eq!(
+            container.read(transition_capsule).0,
+            (AsyncState::Loading(Some(1)), Some(1))
+        );
+
+        set_state(AsyncState::Complete(2));
+        assert_eq!(
+            container.read(transition_capsule).0,
+            (AsyncState::Complete(2), Some(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn value_stream_yields_data_until_dropped() {
+        fn count_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn(u32)) {
+            register.register(effects::state::<Cloned<_>>(0))
+        }
+        fn count_value_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+            get.as_ref(count_capsule).0
+        }
+
+        async fn next<T>(stream: &mut ValueStream<T>) -> Option<T> {
+            std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+        }
+
+        let container = Container::new();
+        let mut stream = value_stream(&container, &count_value_capsule);
+        let other_stream = value_stream(&container, &count_value_capsule);
+        assert_eq!(next(&mut stream).await, Some(0));
+
+        let set_count = container.read(count_capsule).1;
+        set_count(1);
+        set_count(2);
+        assert_eq!(next(&mut stream).await, Some(1));
+        assert_eq!(next(&mut stream).await, Some(2));
+
+        let node_count = container.stats().node_count;
+        drop(other_stream);
+        drop(stream);
+        assert!(container.stats().node_count <= node_count - 2);
     }
 }
-*/
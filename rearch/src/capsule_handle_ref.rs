@@ -0,0 +1,76 @@
+use crate::{
+    read_capsules::{read_by_id, read_ref_by_id},
+    Capsule, CapsuleId, Container, CreateCapsuleId,
+};
+
+/// A typed handle onto a particular capsule in a [`Container`],
+/// as created with [`Container::handle`].
+///
+/// The handle caches the capsule's [`CapsuleId`],
+/// so repeatedly reading the capsule through the handle avoids
+/// reconstructing the capsule and recomputing its id on every read.
+/// This is particularly helpful in tight polling loops over dynamic capsules.
+pub struct CapsuleHandleRef<C> {
+    container: Container,
+    capsule: C,
+    id: CapsuleId,
+}
+
+impl<C: Capsule> CapsuleHandleRef<C> {
+    pub(crate) fn new(container: Container, capsule: C) -> Self {
+        let id = capsule.id();
+        Self {
+            container,
+            capsule,
+            id,
+        }
+    }
+
+    /// Returns the (cached) [`CapsuleId`] of this handle's capsule.
+    #[must_use]
+    pub const fn id(&self) -> &CapsuleId {
+        &self.id
+    }
+}
+
+impl<C: Capsule + Clone> CapsuleHandleRef<C> {
+    /// Reads the capsule's current data, like [`Container::read`].
+    ///
+    /// The capsule is only cloned if it must be (re)initialized.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if the capsule is not initialized, falls back to grabbing a write lock.
+    pub fn read(&self) -> C::Data
+    where
+        C::Data: Clone,
+    {
+        read_by_id(&self.container, &self.id, |txn| {
+            txn.ensure_initialized(self.capsule.clone());
+        })
+    }
+
+    /// Reads the capsule's current data via a ref, like [`Container::read_ref`].
+    ///
+    /// The capsule is only cloned if it must be (re)initialized.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if the capsule is not initialized, falls back to grabbing a write lock,
+    /// and will downgrade the write lock to a read lock once initialized.
+    ///
+    /// The callback will be invoked while holding a read lock on the container,
+    /// so it is best to keep the callback on the quicker side
+    /// (unless you don't mind blocking side effect updates and uninitialized reads).
+    pub fn read_ref<Callback, CallbackReturn>(&self, callback: Callback) -> CallbackReturn
+    where
+        Callback: FnOnce(&C::Data) -> CallbackReturn,
+    {
+        read_ref_by_id(
+            &self.container,
+            &self.id,
+            |txn| txn.ensure_initialized(self.capsule.clone()),
+            callback,
+        )
+    }
+}
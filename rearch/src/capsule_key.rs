@@ -1,5 +1,6 @@
 use std::{
     any::{Any, TypeId},
+    collections::{HashMap, HashSet},
     fmt::Debug,
     hash::{Hash, Hasher},
     sync::Arc,
@@ -44,16 +45,194 @@ impl PartialEq for dyn DynCapsuleKey {
 }
 impl Eq for dyn DynCapsuleKey {}
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// Composes a dynamic capsule key out of labeled parts,
+/// which is more ergonomic and less error-prone than raw tuples
+/// for capsule families with many dimensions.
+///
+/// Each part is identified by both its label and its type,
+/// so keys with the same values under different labels (or types) never collide.
+/// The builder itself implements [`CapsuleKey`], so return it directly from [`Capsule::key`]:
+/// ```
+/// # use rearch::{Capsule, CapsuleHandle, CapsuleKey, CapsuleKeyBuilder};
+/// struct CellCapsule {
+///     row: u32,
+///     col: u32,
+/// }
+/// impl Capsule for CellCapsule {
+///     type Data = u32;
+///
+///     fn build(&self, _: CapsuleHandle) -> Self::Data {
+///         self.row * self.col
+///     }
+///
+///     fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+///         old == new
+///     }
+///
+///     fn key(&self) -> impl CapsuleKey {
+///         CapsuleKeyBuilder::new()
+///             .field("row", self.row)
+///             .field("col", self.col)
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CapsuleKeyBuilder(Vec<(&'static str, CapsuleKeyPart)>);
+
+impl CapsuleKeyBuilder {
+    /// Creates a new, empty `CapsuleKeyBuilder`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a part to the key with the given `label` and `value`.
+    ///
+    /// Labels are expected to be unique within a key.
+    #[must_use]
+    pub fn field(mut self, label: &'static str, value: impl CapsuleKey) -> Self {
+        debug_assert!(
+            self.0.iter().all(|(existing, _)| *existing != label),
+            "Duplicate capsule key label {label:?}"
+        );
+        self.0.push((label, CapsuleKeyPart(Arc::new(value))));
+        self
+    }
+}
+
+/// A dynamic capsule key grouped under a `namespace`, which is included in its [`Debug`] output
+/// (i.e., `cells::(0, 1)`) so that related capsule families are easier to spot in logs.
+///
+/// The namespace participates in hashing and equality,
+/// so the same `key` under two different namespaces makes two different capsule keys.
+/// ```
+/// # use rearch::NamespacedKey;
+/// assert_eq!(format!("{:?}", NamespacedKey::new("cells", (0, 1))), "cells::(0, 1)");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NamespacedKey<K> {
+    pub namespace: &'static str,
+    pub key: K,
+}
+
+impl<K> NamespacedKey<K> {
+    /// Creates a new `NamespacedKey` with the given `namespace` and `key`.
+    pub const fn new(namespace: &'static str, key: K) -> Self {
+        Self { namespace, key }
+    }
+}
+
+impl<K: Debug> Debug for NamespacedKey<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{:?}", self.namespace, self.key)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CapsuleKeyPart(Arc<dyn DynCapsuleKey>);
+impl PartialEq for CapsuleKeyPart {
+    fn eq(&self, other: &Self) -> bool {
+        *self.0 == *other.0
+    }
+}
+impl Eq for CapsuleKeyPart {}
+impl Hash for CapsuleKeyPart {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_any().type_id().hash(state);
+        self.0.hash(state);
+    }
+}
+
+/// Uniquely identifies a capsule (by both its type and its [`Capsule::key`]) within a
+/// [`Container`](crate::Container).
+///
+/// Useful for building generic tooling atop rearch;
+/// see [`Container::read_erased`](crate::Container::read_erased).
+#[derive(Clone, Debug)]
 pub struct CapsuleId {
     // NOTE: we need to have a copy of the capsule's type to include in the Hash + Eq
     // so that if two capsules of different types have the same key,
     // they won't be kept under the same entry in the map.
     capsule_type: TypeId,
-    // NOTE: capsule_key is Arc<Box<_>> instead of just Arc<_> because of this:
+    // NOTE: we hand-roll PartialEq + Hash below instead of deriving them because of this:
     // https://github.com/rust-lang/rust/issues/78808#issuecomment-1664012270
-    // Hand-rolling a PartialEq + Hash sucks and I'd probably screw it up.
-    capsule_key: Arc<Box<dyn DynCapsuleKey>>,
+    // (which would otherwise force an extra allocation via Arc<Box<_>> on every id creation).
+    capsule_key: Arc<dyn DynCapsuleKey>,
+}
+impl PartialEq for CapsuleId {
+    fn eq(&self, other: &Self) -> bool {
+        self.capsule_type == other.capsule_type && *self.capsule_key == *other.capsule_key
+    }
+}
+impl Eq for CapsuleId {}
+impl Hash for CapsuleId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.capsule_type.hash(state);
+        self.capsule_key.hash(state);
+    }
+}
+
+impl CapsuleId {
+    /// Creates the `CapsuleId` of the given capsule.
+    pub fn of<C: Capsule>(capsule: &C) -> Self {
+        capsule.id()
+    }
+
+    /// Creates the `CapsuleId` of a capsule of type `C` with the given key.
+    pub(crate) fn new<C: 'static>(key: impl CapsuleKey) -> Self {
+        Self {
+            capsule_type: TypeId::of::<C>(),
+            capsule_key: Arc::new(key),
+        }
+    }
+
+    pub(crate) const fn capsule_type(&self) -> TypeId {
+        self.capsule_type
+    }
+}
+
+// NOTE: CapsuleIds are hashed on every read, so a faster hasher is worth having as an option.
+// SipHash's DoS-resistance isn't really needed here since CapsuleIds are not untrusted input,
+// but we still default to it just to be on the safe side.
+#[cfg(feature = "rustc-hash")]
+type CapsuleIdHasher = rustc_hash::FxBuildHasher;
+#[cfg(not(feature = "rustc-hash"))]
+type CapsuleIdHasher = std::hash::RandomState;
+
+pub type CapsuleIdMap<V> = HashMap<CapsuleId, V, CapsuleIdHasher>;
+pub type CapsuleIdSet = HashSet<CapsuleId, CapsuleIdHasher>;
+
+/// The set of a capsule's dependents.
+///
+/// With the `indexmap` feature, this preserves insertion order so that rebuilds cascade to
+/// dependents (including listeners) in a stable, reproducible order.
+#[derive(Default)]
+pub struct DependentSet(DependentSetInner);
+#[cfg(feature = "indexmap")]
+type DependentSetInner = indexmap::IndexSet<CapsuleId, CapsuleIdHasher>;
+#[cfg(not(feature = "indexmap"))]
+type DependentSetInner = CapsuleIdSet;
+
+impl DependentSet {
+    pub fn insert(&mut self, id: CapsuleId) -> bool {
+        self.0.insert(id)
+    }
+
+    pub fn remove(&mut self, id: &CapsuleId) -> bool {
+        // NOTE: shift_remove is O(n), but it preserves the order of the remaining dependents
+        #[cfg(feature = "indexmap")]
+        return self.0.shift_remove(id);
+        #[cfg(not(feature = "indexmap"))]
+        return self.0.remove(id);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CapsuleId> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 }
 
 pub trait CreateCapsuleId {
@@ -61,9 +240,49 @@ pub trait CreateCapsuleId {
 }
 impl<C: Capsule> CreateCapsuleId for C {
     fn id(&self) -> CapsuleId {
-        CapsuleId {
-            capsule_type: TypeId::of::<C>(),
-            capsule_key: Arc::new(Box::new(self.key())),
-        }
+        CapsuleId::new::<C>(self.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, RandomState};
+
+    use crate::{CapsuleKeyBuilder, NamespacedKey};
+
+    #[test]
+    fn capsule_key_builder_distinguishes_labels_and_types() {
+        let key = |row, col| CapsuleKeyBuilder::new().field("row", row).field("col", col);
+        let hasher = RandomState::new();
+
+        assert_eq!(key(1, 2), key(1, 2));
+        assert_eq!(hasher.hash_one(key(1, 2)), hasher.hash_one(key(1, 2)));
+        assert_ne!(key(1, 2), key(2, 1));
+        assert_ne!(
+            CapsuleKeyBuilder::new().field("row", 1),
+            CapsuleKeyBuilder::new().field("col", 1)
+        );
+        assert_ne!(
+            CapsuleKeyBuilder::new().field("row", 1_u32),
+            CapsuleKeyBuilder::new().field("row", 1_u64)
+        );
+    }
+
+    #[test]
+    fn namespaced_keys_include_namespace() {
+        let hasher = RandomState::new();
+        assert_eq!(
+            NamespacedKey::new("cells", 1),
+            NamespacedKey::new("cells", 1)
+        );
+        assert_eq!(
+            hasher.hash_one(NamespacedKey::new("cells", 1)),
+            hasher.hash_one(NamespacedKey::new("cells", 1))
+        );
+        assert_ne!(
+            NamespacedKey::new("cells", 1),
+            NamespacedKey::new("rows", 1)
+        );
+        assert_eq!(format!("{:?}", NamespacedKey::new("rows", 1)), "rows::1");
     }
 }
@@ -1,6 +1,9 @@
-use std::{any::Any, collections::HashMap, sync::Arc};
+use std::{any::Any, cell::OnceCell, sync::Arc};
 
-use crate::{Capsule, CapsuleId, ContainerWriteTxn, CreateCapsuleId};
+use crate::{
+    Capsule, CapsuleHandle, CapsuleId, CapsuleIdMap, ContainerWriteTxn, CreateCapsuleId, Error,
+    SideEffectRegistrar,
+};
 
 /// Allows you to read the current data of capsules based on the given state of the container txn.
 pub struct CapsuleReader<'scope, 'total>(InternalCapsuleReader<'scope, 'total>);
@@ -12,15 +15,29 @@ enum InternalCapsuleReader<'scope, 'total> {
     },
     /// To enable easy mocking in testing
     Mock {
-        mocks: HashMap<CapsuleId, Arc<dyn Any + Send + Sync>>,
+        mocks: CapsuleIdMap<Arc<dyn Any + Send + Sync>>,
     },
 }
 
 impl<'scope, 'total> CapsuleReader<'scope, 'total> {
-    pub(crate) fn new(id: CapsuleId, txn: &'scope mut ContainerWriteTxn<'total>) -> Self {
+    pub(crate) const fn new(id: CapsuleId, txn: &'scope mut ContainerWriteTxn<'total>) -> Self {
         Self(InternalCapsuleReader::Normal { id, txn })
     }
 
+    /// Creates a (shorter-lived) [`CapsuleReader`] that reads through this one,
+    /// which is needed to build a capsule several times within one build.
+    pub(crate) fn reborrow(&mut self) -> CapsuleReader<'_, 'total> {
+        CapsuleReader(match &mut self.0 {
+            InternalCapsuleReader::Normal { id, txn } => InternalCapsuleReader::Normal {
+                id: CapsuleId::clone(id),
+                txn,
+            },
+            InternalCapsuleReader::Mock { mocks } => InternalCapsuleReader::Mock {
+                mocks: mocks.clone(),
+            },
+        })
+    }
+
     /// Returns a ref to the current data of the supplied capsule, initializing it if needed.
     /// Internally forms a dependency graph amongst capsules, so feel free to conditionally invoke
     /// this function in case you only conditionally need a capsule's data.
@@ -34,13 +51,11 @@ impl<'scope, 'total> CapsuleReader<'scope, 'total> {
                 let (this, other) = (id, capsule.id());
                 if this == &other {
                     return txn.try_read_ref(&capsule).unwrap_or_else(|| {
-                        let name = std::any::type_name::<C>();
-                        panic!(
-                            "{name} ({id:?}) tried to read itself on its first build! {} {} {}",
-                            "This is disallowed since the capsule doesn't have data to read yet.",
-                            "To avoid this issue, wrap the `get()` call in an if statement",
-                            "with the builtin \"is_first_build\" side effect."
-                        );
+                        Error::SelfReadOnFirstBuild {
+                            capsule_type_name: std::any::type_name::<C>(),
+                            id: CapsuleId::clone(id),
+                        }
+                        .panic()
                     });
                 }
 
@@ -53,17 +68,62 @@ impl<'scope, 'total> CapsuleReader<'scope, 'total> {
                 let id = capsule.id();
                 mocks.get(&id).map_or_else(
                     || {
-                        panic!(
-                            "Mock CapsuleReader was used to read {} ({id:?}) {}",
-                            std::any::type_name::<C>(),
-                            "when it was not included in the mock!"
-                        );
+                        Error::CapsuleNotMocked {
+                            capsule_type_name: std::any::type_name::<C>(),
+                            id: CapsuleId::clone(&id),
+                        }
+                        .panic()
                     },
                     crate::downcast_capsule_data::<C>,
                 )
             }
         }
     }
+
+    /// Returns whether this is the first build of the capsule currently being built,
+    /// which is `true` exactly when there is no prior data for the capsule
+    /// (including after the capsule was disposed and is now being reinitialized).
+    ///
+    /// This is a lighter-weight alternative to registering an `is_first_build` side effect
+    /// for the common "initialize only once" branch.
+    /// A mocked [`CapsuleReader`] always reports `true`.
+    #[must_use]
+    pub fn is_first_build(&self) -> bool {
+        match &self.0 {
+            InternalCapsuleReader::Normal { id, txn } => !txn.data.contains_key(id),
+            InternalCapsuleReader::Mock { .. } => true,
+        }
+    }
+
+    /// Returns the number of capsules that currently depend upon the capsule being built,
+    /// which is useful for capsules that size themselves (say, a pool) based on their usage.
+    ///
+    /// Note that this reflects the dependents as of prior builds:
+    /// dependents are only added once they read this capsule, which is after it has been built.
+    /// As such, this is always 0 on the first build (including for the capsule that triggered it).
+    /// A mocked [`CapsuleReader`] always reports 0.
+    #[must_use]
+    pub fn dependent_count(&self) -> usize {
+        match &self.0 {
+            InternalCapsuleReader::Normal { id, txn } => txn.dependent_count(id),
+            InternalCapsuleReader::Mock { .. } => 0,
+        }
+    }
+
+    /// Returns a clone of the current data of the supplied capsule, initializing it if needed.
+    ///
+    /// This is equivalent to `reader.as_ref(capsule).clone()`, and is particularly handy
+    /// in [`crate::Container::listen`] listeners to move capsule data elsewhere.
+    /// See [`CapsuleReader::as_ref`] for more.
+    ///
+    /// # Panics
+    /// See [`CapsuleReader::as_ref`].
+    pub fn snapshot<C: Capsule>(&mut self, capsule: C) -> C::Data
+    where
+        C::Data: Clone,
+    {
+        self.as_ref(capsule).clone()
+    }
 }
 
 #[cfg(feature = "experimental-api")]
@@ -83,13 +143,13 @@ where
     A::Data: Clone,
 {
     extern "rust-call" fn call_mut(&mut self, args: (A,)) -> Self::Output {
-        self.as_ref(args.0).clone()
+        self.snapshot(args.0)
     }
 }
 
 /// Used to build a mocked [`CapsuleReader`] for use in unit testing capsules.
 #[derive(Clone, Default)]
-pub struct MockCapsuleReaderBuilder(HashMap<CapsuleId, Arc<dyn Any + Send + Sync>>);
+pub struct MockCapsuleReaderBuilder(CapsuleIdMap<Arc<dyn Any + Send + Sync>>);
 
 impl MockCapsuleReaderBuilder {
     /// Creates a new [`MockCapsuleReaderBuilder`].
@@ -113,9 +173,31 @@ impl MockCapsuleReaderBuilder {
     }
 }
 
+/// Builds the given `capsule` once, in isolation, for use in unit testing capsules.
+///
+/// Capsules read via the [`CapsuleReader`] are served from the supplied `mocks`,
+/// and side effects are registered with fresh state (so they return their initial values).
+/// Any side effect state mutations requested during the build are ignored.
+///
+/// # Panics
+/// Panics when the `capsule` reads a capsule that wasn't included in the `mocks`.
+#[allow(
+    clippy::needless_pass_by_value,
+    reason = "Mirrors Container::read, which also takes capsules by value"
+)]
+pub fn build_capsule<C: Capsule>(capsule: C, mocks: MockCapsuleReaderBuilder) -> C::Data {
+    let mut side_effect = OnceCell::new();
+    let register = SideEffectRegistrar::mock(&mut side_effect)
+        .with_capsule_type_name(std::any::type_name::<C>());
+    capsule.build(CapsuleHandle {
+        get: mocks.build(),
+        register,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{CapsuleHandle, CapsuleReader, MockCapsuleReaderBuilder};
+    use crate::{build_capsule, CapsuleHandle, CapsuleReader, MockCapsuleReaderBuilder};
 
     fn foo_capsule(_: CapsuleHandle) -> u8 {
         0
@@ -142,10 +224,113 @@ mod tests {
         drop(get);
     }
 
+    #[test]
+    fn snapshot_moves_data_out_of_listener() {
+        use std::sync::mpsc;
+
+        use crate::{CData, Container};
+
+        fn names_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (Vec<String>, impl CData + Fn(Vec<String>)) {
+            let (names, set_names, _) = register.raw(Vec::new());
+            (names.clone(), move |new_names| {
+                set_names(Box::new(|names| *names = new_names));
+            })
+        }
+
+        let container = Container::new();
+        let (tx, rx) = mpsc::channel();
+        let handle = container.listen_simple(move |mut get| {
+            let (names, _) = get.snapshot(names_capsule);
+            tx.send(names).expect("Receiver should be alive");
+        });
+        assert_eq!(rx.try_recv(), Ok(Vec::new()));
+
+        container.read(names_capsule).1(vec!["Alice".to_owned()]);
+        assert_eq!(rx.try_recv(), Ok(vec!["Alice".to_owned()]));
+
+        drop(handle);
+        assert_eq!(
+            MockCapsuleReaderBuilder::new()
+                .set(&foo_capsule, 123)
+                .build()
+                .snapshot(foo_capsule),
+            123
+        );
+    }
+
     #[test]
     #[allow(clippy::should_panic_without_expect)] // exact panic string is based on capsule TypeId
     #[should_panic]
     fn mock_capsule_reader_panics_on_unmocked_capsule() {
         create_mock_capsule_reader().as_ref(another_capsule);
     }
+
+    #[test]
+    fn is_first_build() {
+        use crate::{CData, Container};
+
+        fn rebuildable_capsule(
+            CapsuleHandle { get, register }: CapsuleHandle,
+        ) -> (bool, impl CData + Fn()) {
+            let ((), rebuild, _) = register.raw(());
+            (get.is_first_build(), move || rebuild(Box::new(|()| {})))
+        }
+        fn idempotent_capsule(CapsuleHandle { get, .. }: CapsuleHandle) -> bool {
+            get.is_first_build()
+        }
+
+        let container = Container::new();
+        assert!(container.read(rebuildable_capsule).0);
+        container.read(rebuildable_capsule).1();
+        assert!(!container.read(rebuildable_capsule).0);
+
+        assert!(container.read(idempotent_capsule));
+        assert_eq!(container.collect_garbage(), 1);
+        assert!(container.read(idempotent_capsule));
+
+        assert!(create_mock_capsule_reader().is_first_build());
+    }
+
+    #[test]
+    fn dependent_count() {
+        use crate::{CData, Container};
+
+        fn pool_capsule(
+            CapsuleHandle { get, register }: CapsuleHandle,
+        ) -> (usize, impl CData + Fn()) {
+            let ((), rebuild, _) = register.raw(());
+            (get.dependent_count(), move || rebuild(Box::new(|()| {})))
+        }
+        fn first_user_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            register.register(());
+            get.as_ref(pool_capsule);
+        }
+        fn second_user_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            register.register(());
+            get.as_ref(pool_capsule);
+        }
+
+        let container = Container::new();
+        container.read((first_user_capsule, second_user_capsule));
+        assert_eq!(container.read(pool_capsule).0, 0);
+
+        container.read(pool_capsule).1();
+        assert_eq!(container.read(pool_capsule).0, 2);
+
+        assert_eq!(create_mock_capsule_reader().dependent_count(), 0);
+    }
+
+    #[test]
+    fn build_capsule_uses_mocks_and_initial_side_effect_state() {
+        fn sum_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            let (state, set_state, _) = register.raw(1);
+            set_state(Box::new(|state| *state = 100));
+            *state + get.snapshot(foo_capsule)
+        }
+
+        let mocks = MockCapsuleReaderBuilder::new().set(&foo_capsule, 122);
+        assert_eq!(build_capsule(sum_capsule, mocks), 123);
+    }
 }
@@ -0,0 +1,163 @@
+use std::{any::Any, marker::PhantomData, sync::Arc};
+
+use crate::{
+    read_capsules::read_by_id, Capsule, CapsuleId, Container, ContainerWriteTxn, CreateCapsuleId,
+};
+
+/// A type-erased reference to some capsule whose data is a `T`,
+/// which can be stored in structs and collections regardless of the capsule's concrete type.
+///
+/// This is handy to hold several different capsules (say, a function capsule alongside
+/// some dynamic capsules) that all produce the same `T`, and to read them uniformly
+/// via [`Container::read_ref_handle`].
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, CapsuleRef, Container};
+/// fn english_capsule(_: CapsuleHandle) -> String {
+///     "Hello".to_owned()
+/// }
+///
+/// fn spanish_capsule(_: CapsuleHandle) -> String {
+///     "Hola".to_owned()
+/// }
+///
+/// let greetings = [CapsuleRef::new(english_capsule), CapsuleRef::new(spanish_capsule)];
+/// let container = Container::new();
+/// let greetings = greetings
+///     .iter()
+///     .map(|greeting| container.read_ref_handle(greeting))
+///     .collect::<Vec<_>>();
+/// assert_eq!(greetings, ["Hello", "Hola"]);
+/// ```
+pub struct CapsuleRef<T> {
+    id: CapsuleId,
+    capsule: Arc<dyn Any + Send + Sync>,
+    ensure_initialized: fn(&mut ContainerWriteTxn, &(dyn Any + Send + Sync)),
+    data: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> CapsuleRef<T> {
+    /// Creates a [`CapsuleRef`] that refers to the supplied capsule.
+    pub fn new<C: Capsule<Data = T> + Clone + Sync>(capsule: C) -> Self {
+        Self {
+            id: capsule.id(),
+            capsule: Arc::new(capsule),
+            ensure_initialized: ensure_initialized::<C>,
+            data: PhantomData,
+        }
+    }
+
+    /// Returns the [`CapsuleId`] of the referenced capsule.
+    #[must_use]
+    pub const fn id(&self) -> &CapsuleId {
+        &self.id
+    }
+
+    pub(crate) fn read(&self, container: &Container) -> T
+    where
+        T: Clone,
+    {
+        read_by_id(container, &self.id, |txn| {
+            (self.ensure_initialized)(txn, &*self.capsule);
+        })
+    }
+}
+
+impl<T> Clone for CapsuleRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: CapsuleId::clone(&self.id),
+            capsule: Arc::clone(&self.capsule),
+            ensure_initialized: self.ensure_initialized,
+            data: PhantomData,
+        }
+    }
+}
+
+fn ensure_initialized<C: Capsule + Clone>(
+    txn: &mut ContainerWriteTxn,
+    capsule: &(dyn Any + Send + Sync),
+) {
+    let capsule = capsule
+        .downcast_ref::<C>()
+        .expect("Types should be properly enforced due to generics");
+    txn.ensure_initialized(capsule.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CData, Capsule, CapsuleHandle, CapsuleKey, CapsuleRef, Container, SideEffectTxnRunner,
+    };
+
+    fn zero_capsule(_: CapsuleHandle) -> u32 {
+        0
+    }
+
+    #[derive(Clone)]
+    struct SquareCapsule(u32);
+    impl Capsule for SquareCapsule {
+        type Data = u32;
+
+        fn build(&self, _: CapsuleHandle) -> Self::Data {
+            self.0 * self.0
+        }
+
+        fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+            old == new
+        }
+
+        fn key(&self) -> impl CapsuleKey {
+            self.0
+        }
+    }
+
+    #[test]
+    fn capsule_refs_read_heterogeneous_capsules() {
+        let capsule_refs = [
+            CapsuleRef::new(zero_capsule),
+            CapsuleRef::new(SquareCapsule(2)),
+            CapsuleRef::new(SquareCapsule(3)),
+        ];
+        assert_eq!(capsule_refs[1].id(), capsule_refs[1].clone().id());
+        assert_ne!(capsule_refs[1].id(), capsule_refs[2].id());
+
+        let container = Container::new();
+        let read_all = || {
+            capsule_refs
+                .iter()
+                .map(|capsule_ref| container.read_ref_handle(capsule_ref))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(read_all(), vec![0, 4, 9]);
+        assert_eq!(container.read(SquareCapsule(3)), 9);
+        assert_eq!(read_all(), vec![0, 4, 9]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "observed stale data")]
+    fn capsule_ref_read_during_side_effect_txn_panics() {
+        fn stateful(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u32, impl CData + Fn(u32), SideEffectTxnRunner) {
+            let (state, rebuild, run_txn) = register.raw(0);
+            let set_state = move |new_state| rebuild(Box::new(move |state| *state = new_state));
+            (*state, set_state, run_txn)
+        }
+
+        fn state_only(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+            get.as_ref(stateful).0
+        }
+
+        let container = Container::new();
+        let capsule_ref = CapsuleRef::new(state_only);
+        let (_, set_state, run_txn) = container.read(stateful);
+        assert_eq!(container.read_ref_handle(&capsule_ref), 0);
+        run_txn(Box::new(|| {
+            set_state(1);
+            _ = container.read_ref_handle(&capsule_ref);
+        }));
+    }
+}
@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use crate::{Capsule, CapsuleHandle, CapsuleKey};
+
+/// Creates a capsule whose data is `f` applied to the data of the `source` capsule.
+///
+/// This makes simple projections (like `count -> count.to_string()`) a one-liner,
+/// without needing to write out a separate named capsule function.
+/// The resulting capsule uses `PartialEq` for its [`Capsule::eq`],
+/// so dependents only rebuild when the mapped data actually changes.
+///
+/// Note: the resulting capsule is keyed by the `source` capsule's key and `f`'s type
+/// (which is unique to each closure), but *not* any data captured by `f`.
+/// So, like with function capsules, `f` should not capture any data that may differ
+/// between calls; use a dynamic `source` capsule for that instead.
+pub fn map_capsule<S, F, T>(source: S, f: F) -> impl Capsule<Data = T> + Clone
+where
+    S: Capsule + Clone,
+    F: Fn(&S::Data) -> T + Clone + Send + 'static,
+    T: PartialEq + Send + Sync + 'static,
+{
+    MapCapsule { source, f }
+}
+
+/// Creates a capsule whose data is the data of the `source` capsule
+/// when `predicate` returns `true` for it, and `None` otherwise.
+///
+/// See [`map_capsule`] for more, including a note on the resulting capsule's key.
+pub fn filter_capsule<S, F>(source: S, predicate: F) -> impl Capsule<Data = Option<S::Data>> + Clone
+where
+    S: Capsule + Clone,
+    S::Data: Clone + PartialEq,
+    F: Fn(&S::Data) -> bool + Clone + Send + 'static,
+{
+    map_capsule(source, move |data| predicate(data).then(|| data.clone()))
+}
+
+/// Creates a capsule whose data is a tuple of the data of the `a` and `b` capsules.
+///
+/// See [`map_capsule`] for more.
+pub fn zip_capsules<A, B>(a: A, b: B) -> impl Capsule<Data = (A::Data, B::Data)> + Clone
+where
+    A: Capsule + Clone,
+    B: Capsule + Clone,
+    A::Data: Clone + PartialEq,
+    B::Data: Clone + PartialEq,
+{
+    ZipCapsule { a, b }
+}
+
+/// Adapts the supplied `capsule` into one whose data is an `Arc` of a trait object
+/// (or any other unsized type), via `coerce`, which is typically just `|data| data`.
+///
+/// This makes it easy for capsules with different concrete data types to share a
+/// [`Capsule::Data`] (such as `Arc<dyn Trait>`), which is required when switching between
+/// capsule implementations (i.e., with [`Container::override_with`](crate::Container::override_with)
+/// or an overridable capsule).
+///
+/// Unlike [`map_capsule`], `capsule` is built directly within the resulting capsule
+/// (which has the same key as `capsule`), so the data is never cloned.
+/// As the resulting data can't be compared, dependents are always rebuilt.
+///
+/// # Examples
+/// ```
+/// # use std::sync::Arc;
+/// # use rearch::{dyn_capsule, CapsuleHandle, Container};
+/// trait Greeter: Send + Sync {
+///     fn greet(&self) -> String;
+/// }
+///
+/// struct English;
+/// impl Greeter for English {
+///     fn greet(&self) -> String {
+///         "Hello".to_owned()
+///     }
+/// }
+///
+/// fn english_capsule(_: CapsuleHandle) -> English {
+///     English
+/// }
+///
+/// let greeter_capsule = dyn_capsule::<_, dyn Greeter>(english_capsule, |greeter| greeter);
+/// assert_eq!(Container::new().read(greeter_capsule).greet(), "Hello");
+/// ```
+pub fn dyn_capsule<C, T>(
+    capsule: C,
+    coerce: fn(Arc<C::Data>) -> Arc<T>,
+) -> impl Capsule<Data = Arc<T>> + Clone
+where
+    C: Capsule + Clone,
+    T: ?Sized + Send + Sync + 'static,
+{
+    DynDataCapsule { capsule, coerce }
+}
+
+/// A capsule that wraps a fallible `capsule` (whose data is a [`Result`]),
+/// re-running its build (up to `max_attempts` times in total) until it returns `Ok`.
+///
+/// This is useful for capsules that synchronously read a flaky resource;
+/// see `future_with_retry` in `rearch-tokio` for the asynchronous equivalent.
+/// Every attempt reads through the same [`CapsuleReader`](crate::CapsuleReader)
+/// and registers its side effects into the same state, so side effects are not duplicated
+/// across attempts (and any state they change in a failed attempt is kept).
+/// If every attempt fails, the error from the last attempt is the resulting data.
+///
+/// # Examples
+/// ```
+/// # use std::sync::atomic::{AtomicU8, Ordering};
+/// # use rearch::{CapsuleHandle, Container, Retrying};
+/// static ATTEMPTS: AtomicU8 = AtomicU8::new(0);
+///
+/// fn flaky_capsule(_: CapsuleHandle) -> Result<u8, String> {
+///     match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+///         0 => Err("Try again".to_owned()),
+///         attempts => Ok(attempts),
+///     }
+/// }
+///
+/// let container = Container::new();
+/// assert_eq!(container.read(Retrying::new(flaky_capsule, 3)), Ok(1));
+/// ```
+#[derive(Clone)]
+pub struct Retrying<C> {
+    capsule: C,
+    max_attempts: usize,
+}
+
+impl<C> Retrying<C> {
+    /// Wraps `capsule` so that its build is attempted up to `max_attempts` times
+    /// (where a `max_attempts` of 0 is treated as 1).
+    pub const fn new(capsule: C, max_attempts: usize) -> Self {
+        Self {
+            capsule,
+            max_attempts,
+        }
+    }
+}
+
+impl<C, T, E> Capsule for Retrying<C>
+where
+    C: Capsule<Data = Result<T, E>>,
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    type Data = C::Data;
+
+    fn build(
+        &self,
+        CapsuleHandle {
+            mut get,
+            mut register,
+        }: CapsuleHandle,
+    ) -> Self::Data {
+        let mut attempts_left = self.max_attempts.max(1);
+        loop {
+            attempts_left -= 1;
+            let result = self.capsule.build(CapsuleHandle {
+                get: get.reborrow(),
+                register: register.reborrow(),
+            });
+            if result.is_ok() || attempts_left == 0 {
+                return result;
+            }
+        }
+    }
+
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+        C::eq(old, new)
+    }
+
+    fn always_rebuild() -> bool {
+        C::always_rebuild()
+    }
+
+    fn dependencies() -> Option<&'static [&'static str]> {
+        C::dependencies()
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        (self.capsule.key(), self.max_attempts)
+    }
+}
+
+#[derive(Clone)]
+struct MapCapsule<S, F> {
+    source: S,
+    f: F,
+}
+
+impl<S, F, T> Capsule for MapCapsule<S, F>
+where
+    S: Capsule + Clone,
+    F: Fn(&S::Data) -> T + Send + 'static,
+    T: PartialEq + Send + Sync + 'static,
+{
+    type Data = T;
+
+    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+        (self.f)(get.as_ref(self.source.clone()))
+    }
+
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+        old == new
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        self.source.key()
+    }
+}
+
+struct DynDataCapsule<C: Capsule, T: ?Sized> {
+    capsule: C,
+    coerce: fn(Arc<C::Data>) -> Arc<T>,
+}
+
+impl<C: Capsule + Clone, T: ?Sized> Clone for DynDataCapsule<C, T> {
+    fn clone(&self) -> Self {
+        Self {
+            capsule: self.capsule.clone(),
+            coerce: self.coerce,
+        }
+    }
+}
+
+impl<C, T> Capsule for DynDataCapsule<C, T>
+where
+    C: Capsule,
+    T: ?Sized + Send + Sync + 'static,
+{
+    type Data = Arc<T>;
+
+    fn build(&self, handle: CapsuleHandle) -> Self::Data {
+        (self.coerce)(Arc::new(self.capsule.build(handle)))
+    }
+
+    fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+        false
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        self.capsule.key()
+    }
+}
+
+#[derive(Clone)]
+struct ZipCapsule<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Capsule for ZipCapsule<A, B>
+where
+    A: Capsule + Clone,
+    B: Capsule + Clone,
+    A::Data: Clone + PartialEq,
+    B::Data: Clone + PartialEq,
+{
+    type Data = (A::Data, B::Data);
+
+    fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+        (get.snapshot(self.a.clone()), get.snapshot(self.b.clone()))
+    }
+
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+        old == new
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        (self.a.key(), self.b.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{filter_capsule, map_capsule, zip_capsules, CapsuleHandle, Container, Retrying};
+
+    fn count_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl Fn(u8) + Clone) {
+        let (count, set_count, _) = register.raw(0);
+        (*count, move |new_count| {
+            set_count(Box::new(move |count| *count = new_count));
+        })
+    }
+
+    fn count_value_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+        get.as_ref(count_capsule).0
+    }
+
+    #[test]
+    fn map_filter_and_zip_capsules() {
+        let container = Container::new();
+        let count_string = map_capsule(count_value_capsule, u8::to_string);
+        let even_count = filter_capsule(count_value_capsule, |count| count % 2 == 0);
+        let zipped = zip_capsules(count_value_capsule, count_string.clone());
+
+        assert_eq!(container.read(count_string.clone()), "0");
+        assert_eq!(container.read(even_count.clone()), Some(0));
+        assert_eq!(container.read(zipped.clone()), (0, "0".to_owned()));
+
+        container.read(count_capsule).1(1);
+        assert_eq!(container.read(count_string), "1");
+        assert_eq!(container.read(even_count), None);
+        assert_eq!(container.read(zipped), (1, "1".to_owned()));
+    }
+
+    #[test]
+    fn dyn_capsules_can_be_overridden() {
+        use crate::dyn_capsule;
+
+        fn one_capsule(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn text_capsule(_: CapsuleHandle) -> String {
+            "text".to_owned()
+        }
+        type DynDebug = dyn std::fmt::Debug + Send + Sync;
+        fn debug_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> String {
+            let debug_data = get.as_ref(dyn_capsule::<_, DynDebug>(one_capsule, |data| data));
+            format!("{debug_data:?}")
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(debug_capsule), "1");
+
+        container.override_with(
+            &dyn_capsule::<_, DynDebug>(one_capsule, |data| data),
+            dyn_capsule::<_, DynDebug>(text_capsule, |data| data),
+        );
+        assert_eq!(container.read(debug_capsule), "\"text\"");
+    }
+
+    #[test]
+    fn retrying_reuses_side_effects_across_attempts() {
+        fn flaky_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> Result<u8, u8> {
+            let (attempts, _, _) = register.raw(0);
+            *attempts += 1;
+            if *attempts < 3 {
+                Err(*attempts)
+            } else {
+                Ok(*attempts)
+            }
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(Retrying::new(flaky_capsule, 3)), Ok(3));
+        assert_eq!(container.read(Retrying::new(flaky_capsule, 2)), Err(2));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use parking_lot::Mutex;
+
+use crate::{BuildMiddleware, Capsule, CapsuleId, Container, ContainerSetup, ContainerStore};
+
+/// Performs part of a [`Container`]'s initial setup; see [`ContainerBuilder::setup`].
+type SetupStep = Box<dyn FnOnce(&mut ContainerSetup)>;
+
+/// Configures a [`Container`] before it is created (and shared), as created with
+/// [`Container::builder`].
+///
+/// Since the `Container` does not exist until [`ContainerBuilder::build`] is called,
+/// nothing can observe it in a partially-configured state.
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use rearch::{CapsuleHandle, Container};
+/// fn config_capsule(_: CapsuleHandle) -> String {
+///     "default".to_owned()
+/// }
+///
+/// let container = Container::builder()
+///     .with_dispose_observer(Arc::new(|id| println!("Disposed {id:?}")))
+///     .seed(config_capsule, "seeded".to_owned())
+///     .build();
+/// assert_eq!(container.read(config_capsule), "seeded");
+/// ```
+#[must_use]
+#[derive(Default)]
+pub struct ContainerBuilder {
+    store: ContainerStore,
+    setup_steps: Vec<SetupStep>,
+}
+
+impl ContainerBuilder {
+    /// Defers rebuilds until [`Container::flush_rebuilds`] is called;
+    /// see [`Container::with_deferred_rebuilds`].
+    pub fn with_deferred_rebuilds(mut self) -> Self {
+        self.store.pending_rebuilds = Some(Mutex::default());
+        self
+    }
+
+    /// Disables the automatic garbage collection of idempotent capsules;
+    /// see [`Container::disable_idempotent_gc`].
+    pub fn without_idempotent_gc(self) -> Self {
+        self.store
+            .idempotent_gc_disabled
+            .store(true, Ordering::SeqCst);
+        self
+    }
+
+    /// Sets the observer of capsule disposals; see [`Container::set_dispose_observer`].
+    pub fn with_dispose_observer(self, observer: Arc<dyn Fn(&CapsuleId) + Send + Sync>) -> Self {
+        *self.store.dispose_observer.lock() = Some(observer);
+        self
+    }
+
+    /// Adds a build middleware; see [`Container::add_build_middleware`].
+    pub fn with_build_middleware(self, middleware: BuildMiddleware) -> Self {
+        self.store.add_build_middleware(middleware);
+        self
+    }
+
+    /// Seeds the supplied capsule with `data`; see [`ContainerSetup::seed`].
+    pub fn seed<C: Capsule>(self, capsule: C, data: C::Data) -> Self {
+        self.setup(move |setup| setup.seed(capsule, data))
+    }
+
+    /// Adds a step to the initial setup of the `Container`; see [`Container::new_with`].
+    ///
+    /// All setup steps are performed in order, in one atomic write transaction,
+    /// during [`ContainerBuilder::build`].
+    pub fn setup(mut self, step: impl FnOnce(&mut ContainerSetup) + 'static) -> Self {
+        self.setup_steps.push(Box::new(step));
+        self
+    }
+
+    /// Creates the configured [`Container`].
+    #[must_use]
+    pub fn build(self) -> Container {
+        Container::from_store_with(self.store, |setup| {
+            for step in self.setup_steps {
+                step(setup);
+            }
+        })
+    }
+}
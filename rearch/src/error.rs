@@ -0,0 +1,146 @@
+use std::fmt::Display;
+
+use crate::CapsuleId;
+
+/// The errors that can occur when misusing `ReArch`.
+///
+/// These are currently always raised as panics, whose messages are the errors'
+/// [`Display`] output, since they all indicate a bug in the calling code.
+/// Having them here as a type documents every such case in one place,
+/// and allows libraries built on `ReArch` to raise (and describe) them consistently.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A capsule tried to read itself on its first build, when it has no data to read yet.
+    SelfReadOnFirstBuild {
+        /// The type name of the capsule.
+        capsule_type_name: &'static str,
+        /// The id of the capsule.
+        id: CapsuleId,
+    },
+
+    /// A capsule registered side effect(s) with different types of state than it did before.
+    SideEffectTypeChanged {
+        /// The type name of the capsule.
+        capsule_type_name: &'static str,
+        /// The type name of the previously registered side effect state.
+        expected_type_name: &'static str,
+        /// The type name of the newly registered side effect state.
+        actual_type_name: &'static str,
+    },
+
+    /// A mocked [`CapsuleReader`](crate::CapsuleReader) read a capsule that was not mocked.
+    CapsuleNotMocked {
+        /// The type name of the capsule.
+        capsule_type_name: &'static str,
+        /// The id of the capsule.
+        id: CapsuleId,
+    },
+
+    /// A build middleware did not invoke the build it was given.
+    BuildNotInvoked {
+        /// The id of the capsule being built.
+        id: CapsuleId,
+    },
+
+    /// A build middleware invoked the build it was given more than once.
+    BuildInvokedRepeatedly {
+        /// The id of the capsule being built.
+        id: CapsuleId,
+    },
+
+    /// An internal invariant was broken: a capsule's node was expected to be in the graph.
+    NodeNotInGraph {
+        /// The id of the capsule.
+        id: CapsuleId,
+    },
+
+    /// An internal invariant was broken: a capsule's node was used while it was being built.
+    NodeInUse,
+}
+
+impl Error {
+    /// Panics with this error as the panic message.
+    #[track_caller]
+    pub(crate) fn panic(self) -> ! {
+        panic!("{self}")
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SelfReadOnFirstBuild {
+                capsule_type_name,
+                id,
+            } => write!(
+                f,
+                "{capsule_type_name} ({id:?}) tried to read itself on its first build! \
+                This is disallowed since the capsule doesn't have data to read yet. \
+                To avoid this issue, wrap the `get()` call in an if statement \
+                with the builtin \"is_first_build\" side effect."
+            ),
+            Self::SideEffectTypeChanged {
+                capsule_type_name,
+                expected_type_name,
+                actual_type_name,
+            } => write!(
+                f,
+                "You cannot change the side effect(s) passed to SideEffectRegistrar::register()! \
+                {capsule_type_name} expected side effect state of type {expected_type_name}, \
+                but found {actual_type_name} instead."
+            ),
+            Self::CapsuleNotMocked {
+                capsule_type_name,
+                id,
+            } => write!(
+                f,
+                "Mock CapsuleReader was used to read {capsule_type_name} ({id:?}) \
+                when it was not included in the mock!"
+            ),
+            Self::BuildNotInvoked { id } => {
+                write!(f, "A build middleware did not invoke the build of {id:?}")
+            }
+            Self::BuildInvokedRepeatedly { id } => write!(
+                f,
+                "A build middleware invoked the build of {id:?} more than once"
+            ),
+            Self::NodeNotInGraph { id } => write!(f, "Node ({id:?}) should be in graph"),
+            Self::NodeInUse => write!(
+                f,
+                "Attempted to use a CapsuleManager field when someone else already had ownership"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{build_capsule, CapsuleHandle, CreateCapsuleId, Error, MockCapsuleReaderBuilder};
+
+    fn count_capsule(_: CapsuleHandle) -> u8 {
+        0
+    }
+
+    fn count_plus_one_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+        get.as_ref(count_capsule) + 1
+    }
+
+    #[test]
+    fn panic_message_is_error_display() {
+        let payload = std::panic::catch_unwind(|| {
+            build_capsule(count_plus_one_capsule, MockCapsuleReaderBuilder::new())
+        })
+        .expect_err("Reading an unmocked capsule should panic");
+        let expected = Error::CapsuleNotMocked {
+            capsule_type_name: std::any::type_name_of_val(&count_capsule),
+            id: count_capsule.id(),
+        };
+        assert_eq!(
+            payload.downcast_ref::<String>(),
+            Some(&expected.to_string())
+        );
+    }
+}
@@ -0,0 +1,11 @@
+/// The result of a [`Container::try_garbage_collect`](crate::Container::try_garbage_collect).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcResult {
+    /// The capsule was not initialized in the container, so there was nothing to dispose.
+    NotPresent,
+    /// The capsule or one of its (transitive) dependents is nonidempotent,
+    /// so nothing was disposed.
+    ValidationFailed,
+    /// The capsule and all of its (transitive) dependents were disposed.
+    Success,
+}
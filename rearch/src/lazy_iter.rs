@@ -0,0 +1,69 @@
+use std::{fmt::Debug, sync::Arc};
+
+/// Capsule data that lazily produces a fresh [`Iterator`] on demand,
+/// for large computed sequences that are better streamed than materialized.
+///
+/// A `LazyIter` is cheap to clone (it is just an [`Arc`] around the iterator factory),
+/// and the iterators it creates do not borrow from (or lock) the [`crate::Container`].
+/// Thus, prefer [`crate::Container::read_iter`] to read one,
+/// which only holds the `Container`'s lock for as long as it takes to clone the `LazyIter`.
+///
+/// Since the factory is re-run for each iterator, any inputs it needs
+/// should be read from other capsules (and moved into the factory) during the build.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{CapsuleHandle, Container, LazyIter};
+/// fn limit_capsule(_: CapsuleHandle) -> u32 {
+///     1_000_000
+/// }
+///
+/// fn squares_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> LazyIter<u64> {
+///     let limit = *get.as_ref(limit_capsule);
+///     LazyIter::new(move || (0..u64::from(limit)).map(|n| n * n))
+/// }
+///
+/// let container = Container::new();
+/// let first_squares = container.read_iter(squares_capsule).take(3).collect::<Vec<_>>();
+/// assert_eq!(first_squares, [0, 1, 4]);
+/// ```
+pub struct LazyIter<T>(Arc<dyn Fn() -> Box<dyn Iterator<Item = T>> + Send + Sync>);
+
+impl<T> LazyIter<T> {
+    /// Creates a new `LazyIter` that calls `factory` every time a new iterator is needed.
+    pub fn new<I, F>(factory: F) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'static,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        Self(Arc::new(move || Box::new(factory().into_iter())))
+    }
+
+    /// Creates a fresh iterator over the sequence.
+    #[must_use]
+    pub fn iter(&self) -> Box<dyn Iterator<Item = T>> {
+        (self.0)()
+    }
+}
+
+impl<T> Clone for LazyIter<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Debug for LazyIter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyIter").finish_non_exhaustive()
+    }
+}
+
+impl<T> IntoIterator for &LazyIter<T> {
+    type Item = T;
+    type IntoIter = Box<dyn Iterator<Item = T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
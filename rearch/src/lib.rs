@@ -1,29 +1,142 @@
 #![cfg_attr(feature = "experimental-api", feature(unboxed_closures, fn_traits))]
 
-use parking_lot::{Mutex, ReentrantMutex, RwLock};
+// Allows for `::rearch` paths (used by our derive macros) within this crate
+extern crate self as rearch;
+
+use parking_lot::{Mutex, MutexGuard, ReentrantMutex, RwLock, RwLockWriteGuard};
 use std::{
     any::Any,
     cell::{OnceCell, RefCell},
-    collections::{HashMap, HashSet},
     ops::{Deref, DerefMut},
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
 };
 
 mod capsule_key;
-pub use capsule_key::CapsuleKey;
-pub(crate) use capsule_key::{CapsuleId, CreateCapsuleId};
+pub use capsule_key::{CapsuleId, CapsuleKey, CapsuleKeyBuilder, NamespacedKey};
+pub(crate) use capsule_key::{CapsuleIdMap, CapsuleIdSet, CreateCapsuleId, DependentSet};
+
+mod error;
+pub use error::Error;
+
+mod combinators;
+pub use combinators::{dyn_capsule, filter_capsule, map_capsule, zip_capsules, Retrying};
 
 mod capsule_reader;
-pub use capsule_reader::{CapsuleReader, MockCapsuleReaderBuilder};
+pub use capsule_reader::{build_capsule, CapsuleReader, MockCapsuleReaderBuilder};
 
 mod side_effect_registrar;
 pub use side_effect_registrar::SideEffectRegistrar;
 
 mod txn;
-use txn::{ContainerReadTxn, ContainerWriteTxn};
+#[cfg(feature = "experimental-api")]
+pub use txn::ContainerReadTxn;
+#[cfg(not(feature = "experimental-api"))]
+use txn::ContainerReadTxn;
+#[cfg(feature = "experimental-api")]
+pub use txn::ContainerWriteTxn;
+#[cfg(not(feature = "experimental-api"))]
+use txn::ContainerWriteTxn;
+
+mod capsule_handle_ref;
+pub use capsule_handle_ref::CapsuleHandleRef;
+
+mod gc;
+pub use gc::GcResult;
+
+mod capsule_ref;
+pub use capsule_ref::CapsuleRef;
+
+mod container_builder;
+pub use container_builder::ContainerBuilder;
+
+mod lazy_iter;
+pub use lazy_iter::LazyIter;
+
+mod stats;
+pub use stats::ContainerStats;
+use stats::StatsCounters;
+
+mod subscription;
+pub use subscription::Subscription;
+
+#[cfg(feature = "local-container")]
+mod local;
+#[cfg(feature = "local-container")]
+pub use local::{
+    LocalCapsule, LocalCapsuleHandle, LocalCapsuleReader, LocalContainer, LocalSideEffect,
+    LocalSideEffectRegistrar,
+};
+
+mod temporary_capsule;
+use temporary_capsule::TemporaryCapsule;
 
 mod read_capsules;
-pub use read_capsules::{CapsulesWithCloneRead, CapsulesWithRefRead};
+pub use read_capsules::{CapsulesWithCloneRead, CapsulesWithRefRead, ReadPath, RefReadGuard};
+
+/// Derives [`Capsule`] for a struct with an inherent `build` method.
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{Capsule, CapsuleHandle, Container};
+/// #[derive(Capsule)]
+/// #[capsule(data = u128, eq)]
+/// struct FibonacciCapsule(#[capsule_key] u8);
+///
+/// impl FibonacciCapsule {
+///     fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> u128 {
+///         match self.0 {
+///             0 => 0,
+///             1 => 1,
+///             n => *get.as_ref(Self(n - 1)) + get.as_ref(Self(n - 2)),
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Container::new().read(FibonacciCapsule(10)), 55);
+/// ```
+///
+/// Forgetting the inherent `build` method is a compile error
+/// (instead of a `build` that infinitely recurses into itself):
+/// ```compile_fail,E0034
+/// # use rearch::Capsule;
+/// #[derive(Capsule)]
+/// #[capsule(data = u8)]
+/// struct MissingBuildCapsule;
+/// ```
+pub use rearch_macros::Capsule;
+
+/// Turns a function into a static capsule, reading capsule-typed parameters for you.
+///
+/// The function becomes a unit struct of the same name that implements [`Capsule`],
+/// so it can be read (and passed as a parameter to other `#[capsule]` functions)
+/// just like a capsule function.
+/// Since that struct's name can't be reused as a binding, name such functions `*_capsule`.
+/// Parameters of type [`CapsuleHandle`], [`CapsuleReader`], and [`SideEffectRegistrar`]
+/// bind the capsule's handle (or part of it); any other parameter's type must be a capsule
+/// that implements [`Default`] (like those made by `#[capsule]`) and whose data is [`Clone`].
+/// Such a parameter's type names the capsule, but its pattern binds a clone of the capsule's
+/// *data*, so `Pair(a, b): pair_capsule` destructures the `Pair` that `pair_capsule` returns.
+/// To read capsules that aren't [`Default`] (such as keyed capsules), take a [`CapsuleReader`].
+///
+/// # Examples
+/// ```rust
+/// # use rearch::{capsule, CapsuleHandle, Container};
+/// #[capsule]
+/// fn count_capsule() -> u8 {
+///     1
+/// }
+///
+/// #[capsule(eq)]
+/// fn count_plus_one_capsule(count: count_capsule, _: CapsuleHandle) -> u8 {
+///     count + 1
+/// }
+///
+/// assert_eq!(Container::new().read(count_plus_one_capsule), 2);
+/// ```
+pub use rearch_macros::capsule;
 
 /// Capsules are blueprints for creating some immutable data
 /// and do not actually contain any data themselves.
@@ -37,6 +150,12 @@ pub trait Capsule: Send + 'static {
     ///
     /// Note: when your types do implement `Clone`, it is suggested to be a "cheap" Clone.
     /// `Arc`s, small collections/data structures, and the `im` crate are great for this.
+    /// For instance, prefer `Arc<Vec<T>>` over `Vec<T>` for large collections,
+    /// so that [`Container::read`] only clones an `Arc` (while `read_ref` can still provide `&T`).
+    ///
+    /// Note: the `Send + Sync` bounds exist because a [`Container`] may be shared across threads.
+    /// For main-thread-only data (such as `Rc`-based UI state),
+    /// use a `LocalContainer` (with the `local-container` feature) instead.
     type Data: Send + Sync + 'static;
 
     /// Builds the capsule's immutable data using a given snapshot of the data flow graph.
@@ -51,12 +170,47 @@ pub trait Capsule: Send + 'static {
     /// (and thus whether or not we can skip rebuilding dependents as an optimization).
     fn eq(old: &Self::Data, new: &Self::Data) -> bool;
 
+    /// Returns whether or not this capsule should rebuild whenever any of its dependencies rebuild,
+    /// even when their new data is [`Capsule::eq`] to their old data.
+    ///
+    /// This is an escape hatch for impure capsules (like those that log or emit metrics)
+    /// that must observe *every* upstream change.
+    /// Enabling this forfeits the [`Capsule::eq`] rebuild-skipping optimization for this capsule,
+    /// so it should only be used when needed.
+    #[must_use]
+    fn always_rebuild() -> bool {
+        false
+    }
+
+    /// Optionally declares the (type) names of the capsules this capsule reads,
+    /// such as `&["count_capsule", "FibonacciCapsule"]`, for documentation and tooling
+    /// (like generating a static dependency diagram).
+    ///
+    /// This is opt-in and purely advisory: the default, `None`, declares nothing.
+    /// Each name matches a dependency when it is the full type name of the dependency
+    /// or a `::`-separated suffix of it.
+    /// With the `logging` feature, a warning is logged after a capsule's first build
+    /// when its declared dependencies do not match the capsules it actually read.
+    #[must_use]
+    fn dependencies() -> Option<&'static [&'static str]> {
+        None
+    }
+
     /// Returns the key to use for this capsule.
     /// Most capsules should use the default implementation,
     /// which is for static capsules.
     /// If you specifically need dynamic capsules,
     /// such as for an incremental computation focused application,
     /// you will need to implement this function and return your capsule's key.
+    ///
+    /// Note: the key is created whenever the capsule's id is needed (such as on every read),
+    /// so it should be cheap to create.
+    /// For large keys (like a `String` path), consider storing the key in an `Arc`
+    /// (i.e., `Arc<str>`) so that returning it is only a reference count increment,
+    /// and/or use [`Container::handle`] to cache the capsule's id for repeated reads.
+    ///
+    /// To make a family of capsules easier to identify in logs,
+    /// wrap the key in a [`NamespacedKey`].
     fn key(&self) -> impl CapsuleKey {
         // NOTE: this default impl implicitly returns `()` (for static capsules)
     }
@@ -79,6 +233,23 @@ where
     }
 }
 
+/// A family of dynamic capsules (of the same type) that can be persisted and restored via
+/// [`Container::snapshot_family`] and [`Container::restore_family`].
+///
+/// Since capsule keys are type-erased inside of a [`Container`],
+/// a family must declare how to convert its capsules to and from a (serializable) key.
+pub trait CapsuleFamily: Capsule {
+    /// The serializable representation of a capsule in this family
+    /// (typically, the fields that make up its [`Capsule::key`]).
+    type SerializedKey;
+
+    /// Converts this capsule into its [`CapsuleFamily::SerializedKey`].
+    fn serialize_key(&self) -> Self::SerializedKey;
+
+    /// Recreates a capsule from its [`CapsuleFamily::SerializedKey`].
+    fn deserialize_key(key: Self::SerializedKey) -> Self;
+}
+
 /// Shorthand for `Clone + Send + Sync + 'static`,
 /// which makes returning `impl Trait` far easier from capsules,
 /// where `Trait` is often an `Fn` from side effects.
@@ -92,6 +263,22 @@ pub struct CapsuleHandle<'txn_scope, 'txn_total, 'build> {
     pub register: SideEffectRegistrar<'build>,
 }
 
+impl CapsuleHandle<'_, '_, '_> {
+    /// Returns whether this is the first build of the capsule being built.
+    /// See [`CapsuleReader::is_first_build`].
+    #[must_use]
+    pub fn is_first_build(&self) -> bool {
+        self.get.is_first_build()
+    }
+
+    /// Returns the number of capsules that currently depend upon the capsule being built.
+    /// See [`CapsuleReader::dependent_count`].
+    #[must_use]
+    pub fn dependent_count(&self) -> usize {
+        self.get.dependent_count()
+    }
+}
+
 /// Represents a side effect that can be utilized within the build function.
 ///
 /// The key observation about side effects is that they form a tree, where each side effect:
@@ -108,16 +295,14 @@ pub trait SideEffect {
     type Api<'registrar>;
 
     /// Construct this side effect's `Api` via the given [`SideEffectRegistrar`].
-    fn build(self, registrar: SideEffectRegistrar) -> Self::Api<'_>;
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_>;
 }
 impl<T, F: FnOnce(SideEffectRegistrar) -> T> SideEffect for F {
     type Api<'registrar> = T;
-    fn build(self, registrar: SideEffectRegistrar) -> Self::Api<'_> {
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
         self(registrar)
     }
 }
-const EFFECT_FAILED_CAST_MSG: &str =
-    "You cannot change the side effect(s) passed to SideEffectRegistrar::register()!";
 // These should be declarative macros, but they unfortunately would require macro_metavar_expr
 rearch_macros::generate_tuple_side_effect_impl!(); // () is the no-op side effect
 rearch_macros::generate_tuple_side_effect_impl!(A B);
@@ -128,6 +313,76 @@ rearch_macros::generate_tuple_side_effect_impl!(A B C D E F);
 rearch_macros::generate_tuple_side_effect_impl!(A B C D E F G);
 rearch_macros::generate_tuple_side_effect_impl!(A B C D E F G H);
 
+/// Allows for a fixed number of side effects of the *same* type to be registered together,
+/// each with its own independent state.
+///
+/// Unlike `multi` (from `rearch-effects`), the number of side effects is checked at compile time.
+impl<S: SideEffect, const N: usize> SideEffect for [S; N] {
+    type Api<'registrar> = [S::Api<'registrar>; N];
+
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        let capsule_type_name = registrar.capsule_type_name();
+        let finalizer_registrar = registrar.container_finalizer_registrar();
+        let (all_states, rebuild_all, run_txn) =
+            registrar.raw(std::array::from_fn::<_, N, _>(|_| {
+                OnceCell::<Box<dyn Any + Send>>::new()
+            }));
+
+        let mut states = all_states.iter_mut().enumerate();
+        self.map(|effect| {
+            let (i, state) = states
+                .next()
+                .expect("There should be exactly one state per side effect");
+            let rebuild_all = rebuild_all.clone();
+            let mutation_runner = Arc::new(move |mutation: SideEffectStateMutation| {
+                rebuild_all(Box::new(move |all_states| {
+                    // NOTE: the state is initialized whenever there is a mutation runner for it
+                    if let Some(state) = all_states[i].get_mut() {
+                        mutation(state.as_mut());
+                    }
+                }));
+            });
+            effect.build(
+                SideEffectRegistrar::new(state, mutation_runner, Arc::clone(&run_txn))
+                    .with_capsule_type_name(capsule_type_name)
+                    .with_container_finalizer_registrar(Arc::clone(&finalizer_registrar)),
+            )
+        })
+    }
+}
+
+/// Allows for a side effect to be registered conditionally,
+/// as `Some(effect)` on some builds and `None` on others, without panicking.
+///
+/// Whenever `None` is registered, the inner side effect's state is dropped,
+/// so the side effect will start over with fresh state the next time it is registered.
+impl<S: SideEffect> SideEffect for Option<S> {
+    type Api<'registrar> = Option<S::Api<'registrar>>;
+
+    fn build(self, registrar: SideEffectRegistrar<'_>) -> Self::Api<'_> {
+        let capsule_type_name = registrar.capsule_type_name();
+        let finalizer_registrar = registrar.container_finalizer_registrar();
+        let (state, rebuild, run_txn) = registrar.raw(OnceCell::<Box<dyn Any + Send>>::new());
+        let Some(effect) = self else {
+            state.take();
+            return None;
+        };
+
+        let mutation_runner = Arc::new(move |mutation: SideEffectStateMutation| {
+            rebuild(Box::new(move |state| {
+                // NOTE: the inner state may have been dropped since the mutation was requested
+                if let Some(state) = state.get_mut() {
+                    mutation(state.as_mut());
+                }
+            }));
+        });
+        let registrar = SideEffectRegistrar::new(state, mutation_runner, run_txn)
+            .with_capsule_type_name(capsule_type_name)
+            .with_container_finalizer_registrar(finalizer_registrar);
+        Some(registrar.register(effect))
+    }
+}
+
 /// Containers store the current data and state of the data flow graph created by capsules
 /// and their dependencies/dependents.
 /// See the README for more.
@@ -143,6 +398,109 @@ impl Container {
         Self::default()
     }
 
+    /// Creates a [`ContainerBuilder`] to configure a new `Container`
+    /// in one place before it is created.
+    pub fn builder() -> ContainerBuilder {
+        ContainerBuilder::default()
+    }
+
+    /// Initializes a new `Container`, performing its initial setup via `init`
+    /// in one atomic write transaction.
+    ///
+    /// This is safer than reading or seeding capsules after construction,
+    /// since nothing else can observe the `Container` until `init` returns.
+    ///
+    /// Since `init` runs while the write transaction is held,
+    /// it must not trigger any side effect state mutations (say, by calling a setter
+    /// read out of a capsule's data), as those would deadlock;
+    /// perform such mutations after `new_with` returns instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rearch::{CapsuleHandle, Container};
+    /// fn config_capsule(_: CapsuleHandle) -> String {
+    ///     "default".to_owned()
+    /// }
+    ///
+    /// fn greeting_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> String {
+    ///     format!("Hello from {}", get.as_ref(config_capsule))
+    /// }
+    ///
+    /// let container = Container::new_with(|setup| {
+    ///     setup.seed(config_capsule, "seeded".to_owned());
+    ///     setup.read(greeting_capsule);
+    /// });
+    /// assert_eq!(container.read(greeting_capsule), "Hello from seeded");
+    /// ```
+    #[must_use]
+    pub fn new_with(init: impl FnOnce(&mut ContainerSetup)) -> Self {
+        Self::from_store_with(ContainerStore::default(), init)
+    }
+
+    fn from_store_with(store: ContainerStore, init: impl FnOnce(&mut ContainerSetup)) -> Self {
+        let container = Self(Arc::new(store));
+        {
+            let _side_effect_txn_lock = container.0.curr_side_effect_txn_modified_ids.lock();
+            init(&mut ContainerSetup(container.0.write_txn()));
+        }
+        container
+    }
+
+    /// Initializes a new `Container` in which rebuilds are deferred
+    /// until [`Container::flush_rebuilds`] is called.
+    ///
+    /// In this mode, side effect state mutations are still applied immediately,
+    /// but the capsules they affect (and their dependents) are only rebuilt,
+    /// all together, during the next [`Container::flush_rebuilds`].
+    /// This decouples state mutation from rebuild timing,
+    /// which is useful when integrating with a render loop (say, by flushing once per frame).
+    /// Until then, reads (and listeners) will observe the capsules' stale data.
+    #[must_use]
+    pub fn with_deferred_rebuilds() -> Self {
+        Self::builder().with_deferred_rebuilds().build()
+    }
+
+    /// Rebuilds all capsules with side effect state mutations that were deferred
+    /// since the last flush; see [`Container::with_deferred_rebuilds`].
+    ///
+    /// Does nothing for `Container`s without deferred rebuilds.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock (if there are any rebuilds to perform).
+    pub fn flush_rebuilds(&self) {
+        let Some(pending_rebuilds) = &self.0.pending_rebuilds else {
+            return;
+        };
+
+        // Wait for any ongoing side effect txn so that we do not rebuild in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        let to_build = std::mem::take(&mut *pending_rebuilds.lock());
+        if !to_build.is_empty() {
+            self.0.write_txn().build_queued_capsules(to_build);
+        }
+    }
+
+    /// Pauses rebuilds in this `Container` until the returned [`RebuildPauseGuard`] is dropped
+    /// (or [`RebuildPauseGuard::resume`] is called),
+    /// at which point all of the capsules modified in the meantime are rebuilt in one sweep.
+    ///
+    /// Unlike a side effect transaction, which batches the mutations made within one closure,
+    /// the pause spans arbitrary code (and any number of independent setters),
+    /// which is useful for initialization phases and bulk imports.
+    /// While paused, side effect state mutations are still applied immediately,
+    /// but reads (and listeners) will observe the affected capsules' stale data.
+    /// Pauses may be nested; rebuilds resume once the last guard is dropped.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes.
+    pub fn pause_rebuilds(&self) -> RebuildPauseGuard<'_> {
+        // Wait for any ongoing side effect txn so that its rebuilds are not paused midway
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        self.0.paused_rebuilds.lock().guard_count += 1;
+        RebuildPauseGuard(self)
+    }
+
     /// Performs a *consistent* read on all supplied capsules that have cloneable data.
     ///
     /// Consistency is important here: if you need the current data from a few different capsules,
@@ -153,10 +511,97 @@ impl Container {
     /// # Concurrency
     /// First attempts to grab a read lock;
     /// if any of the requested capsules are not initialized, falls back to grabbing a write lock.
+    ///
+    /// In debug builds, this panics when reading (within a side effect txn) a capsule that was
+    /// modified by that txn, or that depends on such a capsule,
+    /// since its data is stale until the txn completes.
+    /// Reads from within a capsule's build are *not* detected (they deadlock instead).
     pub fn read<Capsules: CapsulesWithCloneRead>(&self, capsules: Capsules) -> Capsules::Data {
         capsules.read(self)
     }
 
+    /// Like [`Container::read`], but also returns the [`ReadPath`] taken by the read,
+    /// which tells whether the read was served by the fast (read lock) path
+    /// or whether it had to initialize capsules under a write lock.
+    ///
+    /// This is useful to understand cold versus warm read behavior, such as in benchmarks.
+    ///
+    /// # Concurrency
+    /// See [`Container::read`].
+    pub fn read_instrumented<Capsules: CapsulesWithCloneRead>(
+        &self,
+        capsules: Capsules,
+    ) -> (Capsules::Data, ReadPath) {
+        capsules.read_instrumented(self)
+    }
+
+    /// Eagerly initializes the supplied capsules (all in one write txn),
+    /// without returning their data.
+    ///
+    /// This is useful to pay the cost of building expensive capsules up front,
+    /// such as at server startup so that the first request is fast.
+    /// As the data is discarded, the capsules' data does not need to be `Clone`.
+    ///
+    /// # Concurrency
+    /// See [`Container::read_ref`].
+    pub fn warm_up<Capsules: CapsulesWithRefRead>(&self, capsules: Capsules) {
+        capsules.read(self, |_| ());
+    }
+
+    /// Reads the supplied capsule's [`LazyIter`] data, returning a fresh iterator over it.
+    ///
+    /// The returned iterator does not hold onto any of the `Container`'s locks,
+    /// so it can be consumed as slowly as needed.
+    ///
+    /// # Concurrency
+    /// Blocking only while reading the capsule; see [`Container::read`].
+    pub fn read_iter<C, T>(&self, capsule: C) -> Box<dyn Iterator<Item = T>>
+    where
+        C: Capsule<Data = LazyIter<T>>,
+        T: 'static,
+    {
+        self.read(capsule).iter()
+    }
+
+    /// Attempts to read the current data of the supplied capsule without ever blocking.
+    ///
+    /// Returns `None` when the capsule is not yet initialized,
+    /// or when the container is currently locked for writing
+    /// (in which case you can fall back to [`Container::read`]).
+    ///
+    /// # Concurrency
+    /// Only attempts to grab a read lock, returning `None` immediately if that fails.
+    pub fn try_read<C: Capsule>(&self, capsule: &C) -> Option<C::Data>
+    where
+        C::Data: Clone,
+    {
+        let data = self.0.data.try_read()?;
+        ContainerReadTxn::new(data).try_read(capsule)
+    }
+
+    /// Reads the current data of the supplied capsule, initializing it if needed,
+    /// but returns `None` instead of ever blocking on the container's locks.
+    ///
+    /// This is intended for async contexts, where blocking would occupy a runtime worker thread:
+    /// when `None` is returned, yield and retry later (such as on the next tick).
+    /// Unlike [`Container::try_read`], this initializes the capsule when it is uncontended.
+    /// Note that initializing a capsule still runs its build (and those of its dependencies)
+    /// on the current thread.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock; if the capsule is not initialized,
+    /// then attempts to grab a write lock.
+    /// Returns `None` immediately if either lock is unavailable.
+    pub fn read_async_or_none<C: Capsule>(&self, capsule: C) -> Option<C::Data>
+    where
+        C::Data: Clone,
+    {
+        if let Some(data) = self.try_read(&capsule) {
+            return Some(data);
+        }
+        Some(self.0.try_write_txn()?.read_or_init(capsule))
+    }
+
     /// Performs a *consistent* (ref) read on the supplied capsules.
     ///
     /// Consistency is important here: if you need the current data from a few different capsules,
@@ -186,476 +631,2591 @@ impl Container {
         capsules.read(self, callback)
     }
 
-    /// Provides a mechanism to *temporarily* listen to changes in some capsule(s).
-    /// The provided listener is called once at the time of the listener's registration,
-    /// and then once again everytime a dependency changes.
-    ///
-    /// Returns a [`ListenerHandle`], which doesn't do anything other than implement [`Drop`],
-    /// and its [`Drop`] implementation will remove `listener` from the Container.
+    /// Reads the supplied capsule's data by reference, returning the result of `f`.
     ///
-    /// Thus, if you want the handle to live for as long as the Container itself,
-    /// it is instead recommended to create a "non-idempotent" capsule
-    /// (use the `effects::as_listener()` side effect)
-    /// that acts as your listener. When you normally would call `Container::listen()`,
-    /// instead call `container.read(my_non_idempotent_listener)` to initialize it.
+    /// This is shorthand for the most common [`Container::read_ref`] use case:
+    /// projecting some owned data out of a single capsule without cloning all of its data,
+    /// like `container.read_map(list_capsule, |list| list.len())`.
     ///
     /// # Concurrency
-    /// Internally tries to grab a write lock, so this function is blocking.
+    /// See [`Container::read_ref`].
+    pub fn read_map<C: Capsule, R>(&self, capsule: C, f: impl FnOnce(&C::Data) -> R) -> R {
+        self.read_ref(capsule, f)
+    }
+
+    /// Reads (clones) the supplied capsule's data, and then invokes `f` with it,
+    /// returning the result of `f`.
     ///
-    /// # Panics
-    /// Panics if you attempt to register the same listener twice,
-    /// before the first `ListenerHandle` is dropped.
-    #[must_use]
-    pub fn listen<Effect, EffectFactory, Listener>(
-        &self,
-        effect_factory: EffectFactory,
-        listener: Listener,
-    ) -> ListenerHandle
+    /// Unlike with [`Container::read_ref`] (and [`Container::read_map`]),
+    /// `f` is invoked *after* the container's lock is released,
+    /// so long-running computations on the data won't block any writers.
+    /// Prefer this over [`Container::read_ref`] when cloning the data is cheap
+    /// (i.e., it is in an `Arc`) but the subsequent computation is not.
+    ///
+    /// # Concurrency
+    /// See [`Container::read`]; the lock is released before `f` is invoked.
+    pub fn read_then<C, R>(&self, capsule: C, f: impl FnOnce(C::Data) -> R) -> R
     where
-        Effect: SideEffect,
-        EffectFactory: 'static + Send + Fn() -> Effect,
-        Listener: Fn(CapsuleReader, <Effect as SideEffect>::Api<'_>) + Send + 'static,
+        C: Capsule,
+        C::Data: Clone,
     {
-        // We make a temporary non-idempotent capsule for the listener so that
-        // it doesn't get disposed by the idempotent gc
-        let tmp_capsule = move |CapsuleHandle { get, register }: CapsuleHandle| {
-            let effect = effect_factory();
-            let effect_api = register.register(effect);
-            listener(get, effect_api);
-        };
-        let id = tmp_capsule.id();
-
-        // Put the temporary capsule into the container to listen to updates
-        let mut txn = self.0.write_txn();
-        assert_eq!(
-            txn.try_read(&tmp_capsule),
-            None,
-            "You cannot pass the same listener into Container::listen() {}",
-            "until the original returned ListenerHandle is dropped!"
-        );
-        txn.ensure_initialized(tmp_capsule);
-        drop(txn);
-
-        ListenerHandle {
-            id,
-            store: Arc::downgrade(&self.0),
-        }
+        f(self.read(capsule))
     }
-}
 
-/// Represents a handle onto a particular listener, as created with [`Container::listen`].
-///
-/// This struct doesn't do anything other than implement [`Drop`],
-/// and its [`Drop`] implementation will remove the listener from the [`Container`].
-///
-/// Thus, if you want the handle to live for as long as the [`Container`] itself,
-/// it is instead recommended to create a non-idempotent capsule
-/// (just call `register(effects::as_listener());`)
-/// that acts as your listener. When you normally would call `container.listen()`,
-/// instead call `container.read(my_nonidempotent_listener)` to initialize it.
-pub struct ListenerHandle {
-    id: CapsuleId,
-    store: Weak<ContainerStore>,
-}
-impl Drop for ListenerHandle {
-    fn drop(&mut self) {
-        if let Some(store) = self.store.upgrade() {
-            // NOTE: The node is guaranteed to be in the graph here since it is a listener.
-            store.write_txn().dispose_node(&self.id);
-        }
+    /// Performs a *consistent* (ref) read on the supplied capsules like [`Container::read_ref`],
+    /// but only if all of them are already initialized;
+    /// otherwise, returns `None` without invoking the `callback` (or initializing any capsules).
+    ///
+    /// This is useful for strict, read-only query paths that must never trigger capsule builds.
+    ///
+    /// # Concurrency
+    /// Only grabs a read lock (never falling back to a write lock).
+    /// The callback will be invoked while holding that read lock;
+    /// see [`Container::read_ref`] for more.
+    pub fn try_read_ref<Capsules, Callback, CallbackReturn>(
+        &self,
+        capsules: Capsules,
+        callback: Callback,
+    ) -> Option<CallbackReturn>
+    where
+        Capsules: CapsulesWithRefRead,
+        Callback: FnOnce(Capsules::Data<'_>) -> CallbackReturn,
+    {
+        let guard = capsules.try_read_guard(self)?;
+        Some(callback(Capsules::data_from_guard(&guard)))
     }
-}
 
-/// The internal backing store for a `Container`.
-/// All capsule data is stored within `data`, and all data flow graph nodes are stored in `nodes`.
-/// When a side effect txn is underway, effected capsules of the txn will be recorded in
-/// `curr_side_effect_txn_modified_ids` to be later rebuilt in one sweep.
-///
-/// # Concurrency
-/// The concurrency here can be a bit hard to reason about (i.e., how do we prevent deadlocks?),
-/// but the concurrency model choosen enables as much parallelism as possible
-/// (favoring reads over side effect updates) while keeping different features logically separated.
-/// Here's a (very informal) proof that we won't encounter a deadlock.
-/// To start, capsule reads first attempt to grab a read lock on `data` to see if the capsule data
-/// is in the cache.
-/// If that fails (capsule is not built), we fallback to a write lock,
-/// in addition to grabbing a lock on the `nodes` Mutex,
-/// to initialize the capsule's data and any associated [`CapsuleManager`] data (like side effects).
-/// Finally, to rebuild a capsule and its downstream dependents,
-/// we must grab the side effect txn modified ids,
-/// grab and release the nodes Mutex for each capsule-dependent rebuild call,
-/// and then finally grab data write lock at the end to rebuild all necessary capsules.
-/// Thus, as long as we _always_ grab locks in the order of:
-/// 1. `curr_side_effect_txn_modified_ids`
-/// 2. `nodes`
-/// 3. `data`
-///
-/// Skipping the locks we don't need, then we will never face a deadlock.
-#[derive(Default)]
-struct ContainerStore {
-    data: RwLock<HashMap<CapsuleId, Box<dyn Any + Send + Sync>>>,
-    nodes: Mutex<HashMap<CapsuleId, CapsuleManager>>,
-    curr_side_effect_txn_modified_ids: ReentrantMutex<RefCell<Option<HashSet<CapsuleId>>>>,
-}
-trait ArcContainerStore {
-    fn read_txn(&self) -> ContainerReadTxn;
-    fn write_txn(&self) -> ContainerWriteTxn;
-    fn run_side_effect_mutation(&self, id: CapsuleId, mutation: SideEffectStateMutation);
-    fn run_side_effect_txn<F: FnOnce()>(&self, txn: F);
-}
-impl ArcContainerStore for Arc<ContainerStore> {
-    fn read_txn(&self) -> ContainerReadTxn {
-        ContainerReadTxn::new(self.data.read())
+    /// Performs a *consistent* (ref) read on the supplied capsules,
+    /// returning a [`RefReadGuard`] instead of invoking a callback like [`Container::read_ref`].
+    /// Use [`RefReadGuard::get`] (or deref a guard over a single capsule) to access the data.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if any of the requested capsules are not initialized, falls back to grabbing a write lock,
+    /// and will downgrade the write lock to a read lock once initialized.
+    ///
+    /// The returned guard holds the read lock on the container until it is dropped,
+    /// so it is best to drop it promptly
+    /// (unless you don't mind blocking side effect updates and uninitialized reads).
+    pub fn read_refs<Capsules: CapsulesWithRefRead>(
+        &self,
+        capsules: Capsules,
+    ) -> RefReadGuard<'_, Capsules> {
+        capsules.read_guard(self)
     }
 
-    fn write_txn(&self) -> ContainerWriteTxn {
-        // NOTE: nodes must be acquired before data to remain deadlock free
-        let nodes = self.nodes.lock();
-        let data = self.data.write();
-        ContainerWriteTxn::new(
-            data,
-            nodes,
-            SideEffectTxnOrchestrator(Self::downgrade(self)),
-        )
+    /// Performs a read on the supplied capsule like [`Container::read`],
+    /// but borrows the capsule instead of consuming it.
+    ///
+    /// This is useful for (large) dynamic capsules that you want to read repeatedly
+    /// without having to reconstruct them for every read.
+    /// The capsule is only cloned if it must be initialized.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if the requested capsule is not initialized, falls back to grabbing a write lock.
+    pub fn read_by_ref<C: Capsule + Clone>(&self, capsule: &C) -> C::Data
+    where
+        C::Data: Clone,
+    {
+        let attempted_read = self.0.read_txn().try_read(capsule);
+        attempted_read.unwrap_or_else(|| self.0.write_txn().read_or_init(capsule.clone()))
     }
 
-    fn run_side_effect_mutation(&self, id: CapsuleId, mutation: SideEffectStateMutation) {
+    /// Performs a (ref) read on the supplied capsule like [`Container::read_ref`],
+    /// but borrows the capsule instead of consuming it.
+    /// See [`Container::read_by_ref`] for more.
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if the requested capsule is not initialized, falls back to grabbing a write lock,
+    /// and will downgrade the write lock to a read lock once initialized.
+    ///
+    /// The callback will be invoked while holding a read lock on the container,
+    /// so it is best to keep the callback on the quicker side
+    /// (unless you don't mind blocking side effect updates and uninitialized reads).
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "The capsule is always initialized before the read"
+    )]
+    pub fn read_ref_by_ref<C, Callback, CallbackReturn>(
+        &self,
+        capsule: &C,
+        callback: Callback,
+    ) -> CallbackReturn
+    where
+        C: Capsule + Clone,
+        Callback: FnOnce(&C::Data) -> CallbackReturn,
+    {
+        let txn = Some(self.0.read_txn())
+            .filter(|txn| txn.try_read_ref(capsule).is_some())
+            .unwrap_or_else(|| {
+                let mut txn = self.0.write_txn();
+                txn.ensure_initialized(capsule.clone());
+                txn.downgrade()
+            });
+        callback(
+            txn.try_read_ref(capsule)
+                .expect("Ensured initialization above"),
+        )
+    }
+
+    /// Creates a [`CapsuleHandleRef`] onto the supplied capsule in this `Container`,
+    /// which caches the capsule's [`CapsuleId`] for cheap repeated reads.
+    ///
+    /// This does not initialize the capsule; that happens on the first read through the handle.
+    #[must_use]
+    pub fn handle<C: Capsule>(&self, capsule: C) -> CapsuleHandleRef<C> {
+        CapsuleHandleRef::new(self.clone(), capsule)
+    }
+
+    /// Returns whether `self` and `other` refer to the same `Container`
+    /// (i.e., one is a clone of the other).
+    ///
+    /// This is useful to assert that several components share the same `Container`,
+    /// such as to detect a `Container` that was accidentally recreated.
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Reads (clones) the current data of the capsule referred to by the supplied [`CapsuleRef`],
+    /// initializing it if needed, like [`Container::read`].
+    ///
+    /// # Concurrency
+    /// First attempts to grab a read lock;
+    /// if the capsule is not initialized, falls back to grabbing a write lock.
+    #[must_use]
+    pub fn read_ref_handle<T>(&self, capsule_ref: &CapsuleRef<T>) -> T
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        capsule_ref.read(self)
+    }
+
+    /// Overrides the supplied `target` capsule in this `Container` (only) with `replacement`,
+    /// so that `replacement` is built in place of `target` from now on
+    /// (including whenever `target` is disposed and later reinitialized).
+    ///
+    /// This enables dependency injection in tests without needing to restructure production
+    /// capsules into overridable ones; for example, a capsule that talks to a real database
+    /// can be overridden with one that returns canned data.
+    ///
+    /// If `target` is already initialized, its side effect state is discarded
+    /// and it (and its dependents) are rebuilt using `replacement`.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    pub fn override_with<C, R>(&self, target: &C, replacement: R)
+    where
+        C: Capsule,
+        R: Capsule<Data = C::Data> + Clone,
+    {
+        let CapsuleOverride { id, create_manager } = CapsuleOverride::new(target, replacement);
+
+        // Wait for any ongoing side effect txn so that we do not rebuild in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        let mut txn = self.0.write_txn();
+        let replacement_manager = create_manager();
+        self.0
+            .overrides
+            .insert(CapsuleId::clone(&id), create_manager);
+        txn.replace_node_if_present(&id, replacement_manager);
+    }
+
+    /// Computes what the supplied capsule's data *would* be with the supplied `overrides`
+    /// (see [`Container::override_with`]), without affecting this `Container` at all.
+    ///
+    /// The preview is computed in a transient scope layered over this `Container`:
+    /// every capsule that is neither overridden nor (transitively) dependent upon an overridden
+    /// capsule keeps its current data, including any side effect state it reflects.
+    /// The overridden capsules, their dependents, and any capsules that are not yet initialized
+    /// are built from scratch in the scope (with fresh side effect state and without this
+    /// `Container`'s own overrides), which is discarded once the data is read.
+    /// This is useful for speculative computations, like form validation.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use rearch::{CapsuleHandle, CapsuleOverride, Container};
+    /// fn tax_rate_capsule(_: CapsuleHandle) -> u32 {
+    ///     10
+    /// }
+    ///
+    /// fn price_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u32 {
+    ///     100 + get.as_ref(tax_rate_capsule)
+    /// }
+    ///
+    /// fn higher_tax_rate_capsule(_: CapsuleHandle) -> u32 {
+    ///     20
+    /// }
+    ///
+    /// let container = Container::new();
+    /// let overrides = [CapsuleOverride::new(&tax_rate_capsule, higher_tax_rate_capsule)];
+    /// assert_eq!(container.preview(overrides, price_capsule), 120);
+    /// assert_eq!(container.read(price_capsule), 110);
+    /// ```
+    pub fn preview<C>(
+        &self,
+        overrides: impl IntoIterator<Item = CapsuleOverride>,
+        capsule: C,
+    ) -> C::Data
+    where
+        C: Capsule,
+        C::Data: Clone,
+    {
+        self.preview_scope(overrides).read(capsule)
+    }
+
+    /// Creates the transient scope in which a [`Container::preview`] is computed.
+    fn preview_scope(&self, overrides: impl IntoIterator<Item = CapsuleOverride>) -> Self {
+        let preview = Self::new();
+        *preview.0.build_middlewares.write() = Arc::clone(&self.0.build_middlewares.read());
+        let mut overridden_ids = CapsuleIdSet::default();
+        for CapsuleOverride { id, create_manager } in overrides {
+            overridden_ids.insert(CapsuleId::clone(&id));
+            preview.0.overrides.insert(id, create_manager);
+        }
+
+        {
+            // Wait for any ongoing side effect txn so that we layer over a consistent state
+            let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+            self.0
+                .write_txn()
+                .copy_unaffected_nodes_into(&overridden_ids, &mut preview.0.write_txn());
+        }
+        preview
+    }
+
+    /// Returns the serialized key and current data of every initialized capsule in the `C` family,
+    /// which can later be restored with [`Container::restore_family`].
+    ///
+    /// # Concurrency
+    /// Blocks while capsules are being built, and holds a read lock while taking the snapshot.
+    #[must_use]
+    pub fn snapshot_family<C: CapsuleFamily>(&self) -> Vec<(C::SerializedKey, C::Data)>
+    where
+        C::Data: Clone,
+    {
+        // NOTE: nodes must be acquired before data to remain deadlock free
+        let nodes = self.0.nodes.lock();
+        let data = self.0.data.read();
+        nodes
+            .iter()
+            .filter(|(id, _)| id.capsule_type() == std::any::TypeId::of::<C>())
+            .filter_map(|(id, node)| {
+                let capsule = node.capsule.as_ref()?.downcast_ref::<C>()?;
+                let data = data.get(id).map(downcast_capsule_data::<C>)?;
+                Some((capsule.serialize_key(), data.clone()))
+            })
+            .collect()
+    }
+
+    /// Restores capsules in the `C` family from a [`Container::snapshot_family`],
+    /// seeding each capsule with its snapshotted data instead of building it.
+    ///
+    /// Capsules that are already initialized are left untouched.
+    /// Restored capsules have no dependencies and no side effect state until they are next built
+    /// (such as via [`Container::invalidate_type`]), so this is best suited for families whose data
+    /// is a source of truth rather than derived from other capsules.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    pub fn restore_family<C: CapsuleFamily>(
+        &self,
+        snapshot: impl IntoIterator<Item = (C::SerializedKey, C::Data)>,
+    ) {
+        // Wait for any ongoing side effect txn so that we do not modify data in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        let mut txn = self.0.write_txn();
+        for (key, data) in snapshot {
+            txn.seed_if_uninitialized(C::deserialize_key(key), data);
+        }
+    }
+
+    /// Forcefully disposes the supplied capsule from this `Container`,
+    /// returning whether the capsule was initialized.
+    ///
+    /// This is the counterpart to initializing a non-idempotent (listener) capsule via
+    /// [`Container::read`], as such capsules are otherwise kept until the `Container` is dropped.
+    /// The capsule's side effects are disposed too, and it will be built from scratch if read again.
+    /// Only the supplied capsule is disposed; any of its dependents (including listeners)
+    /// are rebuilt instead, and thus will re-initialize the capsule from scratch.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    pub fn dispose<C: Capsule>(&self, capsule: &C) -> bool {
+        // Wait for any ongoing side effect txn so that we do not dispose in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        self.0
+            .write_txn()
+            .dispose_node_and_rebuild_dependents(&capsule.id())
+    }
+
+    /// Rebuilds every capsule of type `C` currently in this `Container` in one sweep
+    /// (which is particularly useful for invalidating a family of dynamic capsules).
+    ///
+    /// The invalidated capsules are always treated as changed (ignoring [`Capsule::eq`]),
+    /// so all of their dependents will be rebuilt as well;
+    /// [`Capsule::eq`] is still respected further downstream.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    pub fn invalidate_type<C: Capsule>(&self) {
+        // Wait for any ongoing side effect txn so that we do not rebuild in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        let mut txn = self.0.write_txn();
+        let ids = txn.ids_of_type::<C>();
+        txn.invalidate_capsules_or_panic(&ids);
+    }
+
+    /// *EXPERIMENTAL*: Invokes `callback` with a [`ContainerReadTxn`] on this `Container`,
+    /// through which several consistent reads can be performed.
+    ///
+    /// The txn types may become crate-private before 1.0,
+    /// so this api may be changed or removed without a major version bump.
+    /// Only already-initialized capsules can be read through a [`ContainerReadTxn`].
+    ///
+    /// # Concurrency
+    /// The callback will be invoked while holding a read lock on the container,
+    /// so it is best to keep the callback on the quicker side
+    /// (unless you don't mind blocking side effect updates and uninitialized reads).
+    #[cfg(feature = "experimental-api")]
+    pub fn with_read_txn<R>(&self, callback: impl FnOnce(&ContainerReadTxn) -> R) -> R {
+        callback(&self.0.read_txn())
+    }
+
+    /// Test-support helper that invokes `f` and then asserts that no capsules were built
+    /// (i.e., initialized or rebuilt) in this `Container` while `f` ran.
+    ///
+    /// This is useful to pin down performance regressions, such as a [`Capsule::eq`]
+    /// that silently stopped working and now triggers unnecessary rebuilds.
+    /// Note that builds caused by other threads during `f` are also counted.
+    /// This method is only available in debug builds (it compiles out in release).
+    ///
+    /// # Panics
+    /// Panics if any capsule was built while `f` ran.
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    pub fn assert_no_builds(&self, f: impl FnOnce()) {
+        let builds_before = self.0.stats.builds();
+        f();
+        let builds = self.0.stats.builds() - builds_before;
+        assert_eq!(
+            builds, 0,
+            "Expected no capsule builds, but {builds} occurred"
+        );
+    }
+
+    /// Sets the observer that is invoked with the id of every capsule disposed from this
+    /// `Container`, replacing any previously set observer.
+    ///
+    /// This fires both for idempotent capsules reclaimed by garbage collection
+    /// (including the capsules of dropped [`ListenerHandle`]s)
+    /// and for capsules that are forcefully disposed, such as via [`Container::dispose`].
+    /// This is useful for releasing external resources tracked outside of `ReArch`.
+    ///
+    /// # Concurrency
+    /// The observer is invoked after the `Container`'s locks have been released,
+    /// so it is free to interact with the `Container`.
+    pub fn set_dispose_observer(&self, observer: Arc<dyn Fn(&CapsuleId) + Send + Sync>) {
+        *self.0.dispose_observer.lock() = Some(observer);
+    }
+
+    /// Adds a middleware that wraps the build of every capsule in this `Container`,
+    /// which is useful for cross-cutting concerns such as timing, tracing, or context.
+    ///
+    /// The middleware is invoked with the id of the capsule being built
+    /// and a callback that performs the actual build, which it *must* invoke exactly once.
+    /// Multiple middlewares compose like layers of an onion:
+    /// the first middleware added is the outermost one,
+    /// and so it runs first before the build and last after the build.
+    /// Middlewares only apply to builds that happen after they are added.
+    ///
+    /// # Concurrency
+    /// Middlewares are invoked while holding the `Container`'s write lock,
+    /// so they must not interact with the `Container` (which would deadlock).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use std::{sync::Arc, time::Instant};
+    /// # use rearch::{CapsuleHandle, Container};
+    /// fn count_capsule(_: CapsuleHandle) -> u8 {
+    ///     0
+    /// }
+    ///
+    /// let container = Container::new();
+    /// container.add_build_middleware(Arc::new(|id, build| {
+    ///     let start = Instant::now();
+    ///     build();
+    ///     println!("Built {id:?} in {:?}", start.elapsed());
+    /// }));
+    /// assert_eq!(container.read(count_capsule), 0);
+    /// ```
+    pub fn add_build_middleware(&self, middleware: BuildMiddleware) {
+        self.0.add_build_middleware(middleware);
+    }
+
+    /// Returns aggregate statistics about this `Container`,
+    /// such as its total number of builds and disposals.
+    ///
+    /// These statistics are always (cheaply) maintained,
+    /// so they are suitable for things like admin endpoints and metrics.
+    ///
+    /// # Concurrency
+    /// Briefly grabs the lock on the container's graph nodes,
+    /// which blocks capsule initialization and rebuilds.
+    #[must_use]
+    pub fn stats(&self) -> ContainerStats {
+        let node_count = self.0.nodes.lock().len();
+        self.0.stats.snapshot(node_count)
+    }
+
+    /// Returns how deeply nested the side effect transaction running on the current thread is,
+    /// or 0 when the current thread is not in a side effect transaction.
+    ///
+    /// Side effect transactions started within another transaction are batched into it,
+    /// so this is handy for debugging cascades that batch more rebuilds than expected.
+    #[must_use]
+    pub fn current_txn_depth(&self) -> usize {
+        // NOTE: try_lock only fails when another thread holds the (reentrant) lock,
+        // in which case the current thread is not in a txn
+        self.0
+            .curr_side_effect_txn_modified_ids
+            .try_lock()
+            .map_or(0, |_lock| {
+                self.0.curr_side_effect_txn_depth.load(Ordering::SeqCst)
+            })
+    }
+
+    /// Returns the number of capsules that currently depend upon the supplied capsule,
+    /// or `None` if the supplied capsule is not currently initialized in this `Container`.
+    ///
+    /// This is useful for backpressure decisions,
+    /// such as dropping external caches for capsules without any dependents.
+    ///
+    /// # Concurrency
+    /// Briefly grabs the lock on the container's graph nodes,
+    /// which blocks capsule initialization and rebuilds.
+    #[must_use]
+    pub fn dependent_count<C: Capsule>(&self, capsule: &C) -> Option<usize> {
+        self.0
+            .nodes
+            .lock()
+            .get(&capsule.id())
+            .map(|node| node.dependents.len())
+    }
+
+    /// Returns the ids of the capsules read by the supplied capsule during its last build,
+    /// in the order they were read (including repeated reads),
+    /// or `None` if the supplied capsule is not currently initialized in this `Container`.
+    ///
+    /// Unlike the capsule's dependencies, this reflects exactly the reads of the last build,
+    /// which is useful for devtools that visualize data flow (say, through conditional reads).
+    /// Reads of the capsule's own data are not included.
+    ///
+    /// # Concurrency
+    /// Briefly grabs the lock on the container's graph nodes,
+    /// which blocks capsule initialization and rebuilds.
+    #[cfg(feature = "read-tracking")]
+    #[must_use]
+    pub fn last_reads<C: Capsule>(&self, capsule: &C) -> Option<Vec<CapsuleId>> {
+        self.0
+            .nodes
+            .lock()
+            .get(&capsule.id())
+            .map(|node| node.last_reads.clone())
+    }
+
+    /// Disables the automatic garbage collection of idempotent capsules in this `Container`,
+    /// so that every capsule stays alive (and cached) until it is explicitly disposed
+    /// or [`Container::collect_garbage`] is called.
+    ///
+    /// This makes caching predictable, which is useful during development
+    /// (to rule out garbage collection when diagnosing rebuild behavior)
+    /// and for small applications.
+    /// However, memory usage will only ever grow as more capsules are read,
+    /// since idempotent capsules that nothing depends upon anymore are no longer disposed.
+    /// This is especially important to keep in mind when reading many dynamic capsules.
+    pub fn disable_idempotent_gc(&self) {
+        self.0.idempotent_gc_disabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Performs a full garbage collection sweep, disposing every idempotent capsule
+    /// that no nonidempotent capsule (transitively) depends upon.
+    /// Returns the number of capsules that were disposed.
+    ///
+    /// Idempotent capsules are normally only garbage collected when they would otherwise
+    /// be rebuilt, so this is useful as a periodic maintenance call in long-running applications.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    #[allow(
+        clippy::must_use_candidate,
+        reason = "Disposal is the point of this function; the count is just informational"
+    )]
+    pub fn collect_garbage(&self) -> usize {
+        // Wait for any ongoing side effect txn so that we do not dispose in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        self.0.write_txn().collect_garbage()
+    }
+
+    /// Disposes the supplied capsule and all of its (transitive) dependents,
+    /// but only when every one of them is idempotent.
+    ///
+    /// This reclaims capsules that went permanently out of use (such as dynamic capsules
+    /// for since-deleted entities) without dropping the whole `Container`.
+    /// When any capsule in the subgraph is nonidempotent, nothing is disposed;
+    /// see [`Container::dispose`] to forcefully dispose a capsule and its dependents instead.
+    ///
+    /// # Concurrency
+    /// Blocks until any ongoing side effect transaction completes,
+    /// and then internally grabs a write lock.
+    #[allow(
+        clippy::must_use_candidate,
+        reason = "Disposal is the point of this function; the result is just informational"
+    )]
+    pub fn try_garbage_collect<C: Capsule>(&self, capsule: &C) -> GcResult {
+        // Wait for any ongoing side effect txn so that we do not dispose in the middle of it
+        let _side_effect_txn_lock = self.0.curr_side_effect_txn_modified_ids.lock();
+        self.0.write_txn().try_garbage_collect(&capsule.id())
+    }
+
+    /// Reads the data of the capsule with the given [`CapsuleId`] without needing its type,
+    /// returning `None` if that capsule is not currently initialized in this `Container`.
+    ///
+    /// This is intended for generic tooling (such as serialization layers) built atop rearch;
+    /// the caller is responsible for downcasting the returned data to the capsule's `Data` type.
+    /// Unlike [`Container::read`], this *will not* initialize an unbuilt capsule.
+    ///
+    /// # Concurrency
+    /// Briefly grabs a read lock; the returned data is shared and does not hold onto the lock.
+    #[must_use]
+    pub fn read_erased(&self, id: &CapsuleId) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.0.read_txn().data.get(id).map(Arc::clone)
+    }
+
+    /// Invokes `callback` with the [`CapsuleId`] and (type-erased) data
+    /// of every capsule currently initialized in this `Container`, in no particular order.
+    ///
+    /// This is intended for generic tooling (such as full-container dumps or devtools);
+    /// see also [`Container::read_erased`]. This *will not* initialize any capsules.
+    ///
+    /// # Concurrency
+    /// The callback will be invoked while holding a read lock on the container,
+    /// so it is best to keep the callback on the quicker side
+    /// (unless you don't mind blocking side effect updates and uninitialized reads).
+    pub fn for_each_value(&self, mut callback: impl FnMut(&CapsuleId, &(dyn Any + Send + Sync))) {
+        for (id, data) in self.0.read_txn().data.iter() {
+            callback(id, &**data);
+        }
+    }
+
+    /// Subscribes to changes in the supplied capsule,
+    /// returning a [`Subscription`] that can be polled for the capsule's latest data.
+    ///
+    /// Unlike [`Container::listen`], which pushes changes into a callback,
+    /// this allows you to pull changes when convenient (such as once per frame in a game loop).
+    /// Dropping the [`Subscription`] removes its internal listener from the `Container`.
+    ///
+    /// # Concurrency
+    /// Internally grabs a write lock, so this function is blocking.
+    #[must_use]
+    pub fn subscribe<C>(&self, capsule: &C) -> Subscription<C::Data>
+    where
+        C: Capsule + Clone,
+        C::Data: Clone,
+    {
+        Subscription::new(self, capsule)
+    }
+
+    /// Provides a mechanism to *temporarily* listen to changes in some capsule(s).
+    /// The provided listener is called once at the time of the listener's registration,
+    /// and then once again everytime a dependency changes.
+    ///
+    /// Returns a [`ListenerHandle`], which doesn't do anything other than implement [`Drop`],
+    /// and its [`Drop`] implementation will remove `listener` from the Container.
+    ///
+    /// Thus, if you want the handle to live for as long as the Container itself,
+    /// it is instead recommended to create a "non-idempotent" capsule
+    /// (use the `effects::as_listener()` side effect)
+    /// that acts as your listener. When you normally would call `Container::listen()`,
+    /// instead call `container.read(my_non_idempotent_listener)` to initialize it.
+    ///
+    /// # Concurrency
+    /// Internally tries to grab a write lock, so this function is blocking.
+    ///
+    /// # Panics
+    /// Panics if you attempt to register the same listener twice,
+    /// before the first `ListenerHandle` is dropped.
+    #[must_use]
+    pub fn listen<Effect, EffectFactory, Listener>(
+        &self,
+        effect_factory: EffectFactory,
+        listener: Listener,
+    ) -> ListenerHandle
+    where
+        Effect: SideEffect,
+        EffectFactory: 'static + Send + Fn() -> Effect,
+        Listener: Fn(CapsuleReader, <Effect as SideEffect>::Api<'_>) + Send + 'static,
+    {
+        // We make a temporary non-idempotent capsule for the listener so that
+        // it doesn't get disposed by the idempotent gc
+        let tmp_capsule = move |CapsuleHandle { get, register }: CapsuleHandle| {
+            let effect = effect_factory();
+            let effect_api = register.register(effect);
+            listener(get, effect_api);
+        };
+        let id = tmp_capsule.id();
+
+        // Put the temporary capsule into the container to listen to updates
+        let mut txn = self.0.write_txn();
+        assert_eq!(
+            txn.try_read(&tmp_capsule),
+            None,
+            "You cannot pass the same listener into Container::listen() {}",
+            "until the original returned ListenerHandle is dropped!"
+        );
+        txn.ensure_initialized(tmp_capsule);
+        drop(txn);
+
+        ListenerHandle {
+            id,
+            store: Arc::downgrade(&self.0),
+        }
+    }
+
+    /// Like [`Container::listen`], but for the common case of a listener
+    /// that does not need any side effects (so there is no effect factory to supply).
+    ///
+    /// See [`Container::listen`] for more, including when to prefer a listener capsule instead.
+    ///
+    /// # Concurrency
+    /// Internally tries to grab a write lock, so this function is blocking.
+    ///
+    /// # Panics
+    /// Panics if you attempt to register the same listener twice,
+    /// before the first `ListenerHandle` is dropped.
+    #[must_use]
+    pub fn listen_simple<Listener>(&self, listener: Listener) -> ListenerHandle
+    where
+        Listener: Fn(CapsuleReader) + Send + 'static,
+    {
+        self.listen(|| (), move |get, ()| listener(get))
+    }
+
+    /// Like [`Container::listen`], but listens to exactly the supplied capsules,
+    /// invoking `listener` with their current data every time any of them change.
+    ///
+    /// Unlike [`Container::listen`], every call registers a separate listener,
+    /// even when the same listener type is passed in more than once.
+    ///
+    /// See [`Container::listen`] for more, including when to prefer a listener capsule instead.
+    ///
+    /// # Concurrency
+    /// Internally tries to grab a write lock, so this function is blocking.
+    #[must_use]
+    pub fn listen_to<Capsules, Listener>(
+        &self,
+        capsules: Capsules,
+        listener: Listener,
+    ) -> ListenerHandle
+    where
+        Capsules: CapsulesWithCloneRead + Clone + Send + 'static,
+        Listener: Fn(Capsules::Data) + Send + 'static,
+    {
+        self.listen_temporary(move |CapsuleHandle { mut get, register }| {
+            register.register(()); // listeners must not be disposed by the idempotent gc
+            listener(capsules.clone().read_from(&mut get));
+        })
+    }
+
+    /// Registers a listener for `build` under a uniquely keyed [`TemporaryCapsule`],
+    /// so that listeners of the same type can be registered more than once.
+    fn listen_temporary(&self, build: impl Fn(CapsuleHandle) + Send + 'static) -> ListenerHandle {
+        let tmp_capsule = TemporaryCapsule::new(build);
+        let id = tmp_capsule.id();
+        self.0.write_txn().ensure_initialized(tmp_capsule);
+
+        ListenerHandle {
+            id,
+            store: Arc::downgrade(&self.0),
+        }
+    }
+
+    /// Like [`Container::listen_to`] for a single capsule, but only invokes `listener`
+    /// when the capsule's data is not equal (via [`PartialEq`]) to the data last passed to it.
+    ///
+    /// This filters out redundant updates regardless of the capsule's own [`Capsule::eq`],
+    /// which is useful when listening to a capsule that may rebuild with equal data.
+    /// Like [`Container::listen_to`], every call registers a separate listener.
+    ///
+    /// See [`Container::listen`] for more, including when to prefer a listener capsule instead.
+    ///
+    /// # Concurrency
+    /// Internally tries to grab a write lock, so this function is blocking.
+    #[must_use]
+    pub fn listen_distinct<C, Listener>(&self, capsule: &C, listener: Listener) -> ListenerHandle
+    where
+        C: Capsule + Clone,
+        C::Data: Clone + PartialEq,
+        Listener: Fn(C::Data) + Send + 'static,
+    {
+        let capsule = capsule.clone();
+        self.listen_temporary(move |CapsuleHandle { mut get, register }| {
+            let (last_data, _, _) = register.raw(None::<C::Data>);
+            let data = get.as_ref(capsule.clone());
+            if last_data.as_ref() != Some(data) {
+                *last_data = Some(data.clone());
+                listener(data.clone());
+            }
+        })
+    }
+}
+
+/// Represents a handle onto a particular listener, as created with [`Container::listen`].
+///
+/// This struct doesn't do anything other than implement [`Drop`],
+/// and its [`Drop`] implementation will remove the listener from the [`Container`].
+///
+/// Thus, if you want the handle to live for as long as the [`Container`] itself,
+/// it is instead recommended to create a non-idempotent capsule
+/// (just call `register(effects::as_listener());`)
+/// that acts as your listener. When you normally would call `container.listen()`,
+/// instead call `container.read(my_nonidempotent_listener)` to initialize it.
+pub struct ListenerHandle {
+    id: CapsuleId,
+    store: Weak<ContainerStore>,
+}
+impl Drop for ListenerHandle {
+    fn drop(&mut self) {
+        if let Some(store) = self.store.upgrade() {
+            // NOTE: the node may no longer be in the graph (i.e., after Container::dispose)
+            store.write_txn().dispose_node_if_present(&self.id);
+        }
+    }
+}
+
+/// Performs the initial setup of a [`Container`]; see [`Container::new_with`].
+pub struct ContainerSetup<'a>(ContainerWriteTxn<'a>);
+impl ContainerSetup<'_> {
+    /// Reads the current data of the supplied capsule, initializing it if needed.
+    pub fn read<C: Capsule>(&mut self, capsule: C) -> C::Data
+    where
+        C::Data: Clone,
+    {
+        self.0.read_or_init(capsule)
+    }
+
+    /// Reads a reference to the current data of the supplied capsule,
+    /// initializing it if needed.
+    pub fn read_ref<C: Capsule>(&mut self, capsule: C) -> &C::Data {
+        self.0.read_or_init_ref(capsule)
+    }
+
+    /// Seeds the supplied capsule with `data` instead of building it,
+    /// if the capsule is not already initialized.
+    ///
+    /// Like with [`Container::restore_family`], a seeded capsule has no dependencies
+    /// and no side effect state until it is next built.
+    pub fn seed<C: Capsule>(&mut self, capsule: C, data: C::Data) {
+        self.0.seed_if_uninitialized(capsule, data);
+    }
+}
+
+/// *EXPERIMENTAL*: the txn types may become crate-private before 1.0,
+/// so this api may be changed or removed without a major version bump.
+#[cfg(feature = "experimental-api")]
+impl<'a> ContainerSetup<'a> {
+    /// Provides raw access to the underlying [`ContainerWriteTxn`].
+    pub const fn txn(&mut self) -> &mut ContainerWriteTxn<'a> {
+        &mut self.0
+    }
+}
+
+/// Represents a pause on a [`Container`]'s rebuilds, as created with [`Container::pause_rebuilds`].
+///
+/// Rebuilds resume when this guard is dropped (or [`RebuildPauseGuard::resume`] is called).
+#[must_use = "Rebuilds are resumed as soon as the RebuildPauseGuard is dropped"]
+pub struct RebuildPauseGuard<'a>(&'a Container);
+impl RebuildPauseGuard<'_> {
+    /// Resumes rebuilds, which is equivalent to dropping this guard.
+    pub fn resume(self) {}
+}
+impl Drop for RebuildPauseGuard<'_> {
+    fn drop(&mut self) {
+        let store = &(self.0).0;
+        let _side_effect_txn_lock = store.curr_side_effect_txn_modified_ids.lock();
+        let to_build = {
+            let mut paused_rebuilds = store.paused_rebuilds.lock();
+            paused_rebuilds.guard_count -= 1;
+            if paused_rebuilds.guard_count > 0 {
+                return;
+            }
+            std::mem::take(&mut paused_rebuilds.ids)
+        };
+        if !to_build.is_empty() {
+            store.write_txn().build_queued_capsules(to_build);
+        }
+    }
+}
+
+/// The internal backing store for a `Container`.
+/// All capsule data is stored within `data`, and all data flow graph nodes are stored in `nodes`.
+/// When a side effect txn is underway, effected capsules of the txn will be recorded in
+/// `curr_side_effect_txn_modified_ids` to be later rebuilt in one sweep.
+///
+/// # Concurrency
+/// The concurrency here can be a bit hard to reason about (i.e., how do we prevent deadlocks?),
+/// but the concurrency model choosen enables as much parallelism as possible
+/// (favoring reads over side effect updates) while keeping different features logically separated.
+/// Here's a (very informal) proof that we won't encounter a deadlock.
+/// To start, capsule reads first attempt to grab a read lock on `data` to see if the capsule data
+/// is in the cache.
+/// If that fails (capsule is not built), we fallback to a write lock,
+/// in addition to grabbing a lock on the `nodes` Mutex,
+/// to initialize the capsule's data and any associated [`CapsuleManager`] data (like side effects).
+/// Finally, to rebuild a capsule and its downstream dependents,
+/// we must grab the side effect txn modified ids,
+/// grab and release the nodes Mutex for each capsule-dependent rebuild call,
+/// and then finally grab data write lock at the end to rebuild all necessary capsules.
+/// Thus, as long as we _always_ grab locks in the order of:
+/// 1. `curr_side_effect_txn_modified_ids`
+/// 2. `nodes`
+/// 3. `data`
+///
+/// Skipping the locks we don't need, then we will never face a deadlock.
+///
+/// (`overrides`, `paused_rebuilds`, `dispose_observer`, `build_middlewares`, and `finalizers`
+/// are only ever grabbed last and for a short while,
+/// so they are exempt from the above.)
+#[derive(Default)]
+struct ContainerStore {
+    data: RwLock<CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
+    nodes: Mutex<CapsuleIdMap<CapsuleManager>>,
+    curr_side_effect_txn_modified_ids: ReentrantMutex<RefCell<Option<CapsuleIdSet>>>,
+    /// The nesting depth of the ongoing side effect txn,
+    /// which is only modified while holding `curr_side_effect_txn_modified_ids`.
+    curr_side_effect_txn_depth: AtomicUsize,
+    stats: StatsCounters,
+    overrides: CapsuleOverrides,
+    /// The ids of capsules awaiting a [`Container::flush_rebuilds`],
+    /// or `None` when rebuilds are not deferred.
+    pending_rebuilds: Option<Mutex<CapsuleIdSet>>,
+    paused_rebuilds: Mutex<PausedRebuilds>,
+    dispose_observer: Mutex<Option<DisposeObserver>>,
+    /// Replaced (rather than mutated) when a middleware is added,
+    /// so that builds only need to clone the `Arc`.
+    build_middlewares: RwLock<Arc<[BuildMiddleware]>>,
+    /// Run (in reverse order) when the store is dropped, before any capsule data is dropped.
+    finalizers: Mutex<Vec<(ContainerFinalizerKey, ContainerFinalizer)>>,
+    idempotent_gc_disabled: AtomicBool,
+}
+
+impl ContainerStore {
+    fn add_build_middleware(&self, middleware: BuildMiddleware) {
+        let mut middlewares = self.build_middlewares.write();
+        *middlewares = middlewares.iter().cloned().chain([middleware]).collect();
+    }
+}
+
+impl Drop for ContainerStore {
+    fn drop(&mut self) {
+        let finalizers = std::mem::take(self.finalizers.get_mut());
+        for (_, finalizer) in finalizers.into_iter().rev() {
+            finalizer();
+        }
+    }
+}
+
+/// Observes the disposal of capsules; see [`Container::set_dispose_observer`].
+type DisposeObserver = Arc<dyn Fn(&CapsuleId) + Send + Sync>;
+
+/// Wraps the build of every capsule; see [`Container::add_build_middleware`].
+type BuildMiddleware = Arc<dyn Fn(&CapsuleId, &mut dyn FnMut()) + Send + Sync>;
+
+/// The ids of capsules awaiting the end of a [`Container::pause_rebuilds`],
+/// alongside the number of live [`RebuildPauseGuard`]s.
+#[derive(Default)]
+struct PausedRebuilds {
+    guard_count: usize,
+    ids: CapsuleIdSet,
+}
+
+/// Creates the [`CapsuleManager`] for a capsule that was overridden via [`Container::override_with`].
+type CreateOverrideManager = Box<dyn Fn() -> CapsuleManager + Send>;
+
+/// A replacement of some capsule with another, as used by [`Container::preview`].
+pub struct CapsuleOverride {
+    id: CapsuleId,
+    create_manager: CreateOverrideManager,
+}
+impl CapsuleOverride {
+    /// Creates an override of the `target` capsule with `replacement`;
+    /// see [`Container::override_with`].
+    pub fn new<C, R>(target: &C, replacement: R) -> Self
+    where
+        C: Capsule,
+        R: Capsule<Data = C::Data> + Clone,
+    {
+        Self {
+            id: target.id(),
+            create_manager: Box::new(move || CapsuleManager::new(replacement.clone())),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CapsuleOverrides(Mutex<CapsuleIdMap<CreateOverrideManager>>);
+impl CapsuleOverrides {
+    fn insert(&self, id: CapsuleId, create_manager: CreateOverrideManager) {
+        self.0.lock().insert(id, create_manager);
+    }
+
+    /// Creates the [`CapsuleManager`] for the capsule with the given id,
+    /// respecting any override of it.
+    fn create_manager<C: Capsule>(&self, id: &CapsuleId, capsule: C) -> CapsuleManager {
+        self.0
+            .lock()
+            .get(id)
+            .map_or_else(|| CapsuleManager::new(capsule), |create| create())
+    }
+}
+trait ArcContainerStore {
+    fn read_txn(&self) -> ContainerReadTxn<'_>;
+    fn write_txn(&self) -> ContainerWriteTxn<'_>;
+    fn try_write_txn(&self) -> Option<ContainerWriteTxn<'_>>;
+    fn run_side_effect_mutation(&self, id: CapsuleId, mutation: SideEffectStateMutation);
+    fn run_side_effect_txn<F: FnOnce()>(&self, txn: F);
+    #[cfg(debug_assertions)]
+    fn debug_assert_unmodified_in_side_effect_txn(&self, ids: &[CapsuleId]);
+}
+fn new_write_txn<'a>(
+    store: &'a Arc<ContainerStore>,
+    nodes: MutexGuard<'a, CapsuleIdMap<CapsuleManager>>,
+    data: RwLockWriteGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
+) -> ContainerWriteTxn<'a> {
+    ContainerWriteTxn::new(
+        data,
+        nodes,
+        SideEffectTxnOrchestrator(Arc::downgrade(store)),
+        &store.stats,
+        &store.overrides,
+        &store.build_middlewares,
+        store.idempotent_gc_disabled.load(Ordering::SeqCst),
+    )
+}
+impl ArcContainerStore for Arc<ContainerStore> {
+    fn read_txn(&self) -> ContainerReadTxn<'_> {
+        ContainerReadTxn::new(self.data.read())
+    }
+
+    fn write_txn(&self) -> ContainerWriteTxn<'_> {
+        // NOTE: nodes must be acquired before data to remain deadlock free
+        let nodes = self.nodes.lock();
+        let data = self.data.write();
+        new_write_txn(self, nodes, data)
+    }
+
+    fn try_write_txn(&self) -> Option<ContainerWriteTxn<'_>> {
+        let nodes = self.nodes.try_lock()?;
+        let data = self.data.try_write()?;
+        Some(new_write_txn(self, nodes, data))
+    }
+
+    fn run_side_effect_mutation(&self, id: CapsuleId, mutation: SideEffectStateMutation) {
+        #[cfg(feature = "logging")]
+        log::debug!("Mutating side effect state in Capsule ({id:?})");
+
+        self.run_side_effect_txn(|| {
+            mutation(
+                self.nodes
+                    .lock()
+                    .deref_mut()
+                    .get_mut(&id)
+                    .expect("The node must be in the graph since it registers a side effect")
+                    .side_effect
+                    .as_mut()
+                    .expect("We should have sole ownership over side_effect since we hold the lock")
+                    .get_mut()
+                    .expect("Side effect must have been previously initialized to invoke a rebuild")
+                    .as_mut(),
+            );
+            self.curr_side_effect_txn_modified_ids
+                .lock()
+                .deref()
+                .borrow_mut()
+                .as_mut()
+                .expect("Called in a side effect txn, so txn should be Some")
+                .insert(id);
+        });
+    }
+
+    /// Asserts that none of the supplied capsules had their side effect state modified
+    /// by a side effect txn ongoing on the current thread (or depend on one that did),
+    /// since reading them would observe data that is about to be rebuilt.
+    #[cfg(debug_assertions)]
+    fn debug_assert_unmodified_in_side_effect_txn(&self, ids: &[CapsuleId]) {
+        // NOTE: try_lock only fails when another thread holds the (reentrant) lock,
+        // in which case the current thread is not in a txn
+        let Some(curr_txn_modified_ids) = self.curr_side_effect_txn_modified_ids.try_lock() else {
+            return;
+        };
+        let curr_txn_modified_ids = curr_txn_modified_ids.borrow();
+        let Some(modified_ids) = curr_txn_modified_ids.as_ref() else {
+            return;
+        };
+        if modified_ids.is_empty() {
+            return;
+        }
+
+        // Find all of the capsules that will be rebuilt once the txn completes.
+        // NOTE: the nodes may already be locked (say, by the current thread when reading within
+        // a side effect state mutation), in which case only the modified capsules are checked
+        let mut stale_ids = modified_ids.clone();
+        if let Some(nodes) = self.nodes.try_lock() {
+            let mut to_visit = modified_ids.iter().cloned().collect::<Vec<_>>();
+            while let Some(id) = to_visit.pop() {
+                let Some(node) = nodes.get(&id) else {
+                    continue;
+                };
+                for dependent in node.dependents.iter() {
+                    if stale_ids.insert(dependent.clone()) {
+                        to_visit.push(dependent.clone());
+                    }
+                }
+            }
+        }
+
+        let stale_id = ids.iter().find(|id| stale_ids.contains(id));
+        assert!(
+            stale_id.is_none(),
+            "Container::read() of {stale_id:?} observed stale data, {} {}",
+            "since the capsule (or one of its dependencies) was modified by an ongoing",
+            "side effect transaction and will only be rebuilt once that transaction completes"
+        );
+    }
+
+    fn run_side_effect_txn<F: FnOnce()>(&self, txn: F) {
+        let curr_txn_modified_ids = self.curr_side_effect_txn_modified_ids.lock();
+
+        let is_root_txn = curr_txn_modified_ids.borrow().is_none();
+        #[cfg_attr(
+            not(feature = "logging"),
+            allow(unused_variables, reason = "Depth is only logged")
+        )]
+        let depth = self
+            .curr_side_effect_txn_depth
+            .fetch_add(1, Ordering::SeqCst)
+            + 1;
+        // NOTE: decremented on drop so that a panicking txn doesn't leave the depth inflated
+        let depth_guard = SideEffectTxnDepthGuard(&self.curr_side_effect_txn_depth);
+        // NOTE: nested txns are only logged at the trace level, to keep debug logs readable
+        #[cfg(feature = "logging")]
+        let log_level = if is_root_txn {
+            log::Level::Debug
+        } else {
+            log::Level::Trace
+        };
+        #[cfg(feature = "logging")]
+        log::log!(
+            log_level,
+            "Starting side effect transaction (depth {depth})"
+        );
+        if is_root_txn {
+            *curr_txn_modified_ids.deref().borrow_mut() = Some(CapsuleIdSet::default());
+        }
+
+        txn();
+        drop(depth_guard);
+
+        if is_root_txn {
+            let to_build = curr_txn_modified_ids
+                .deref()
+                .borrow_mut()
+                .take()
+                .expect("Ensured initialization above");
+            let mut paused_rebuilds = self.paused_rebuilds.lock();
+            if let Some(pending_rebuilds) = &self.pending_rebuilds {
+                pending_rebuilds.lock().extend(to_build);
+            } else if paused_rebuilds.guard_count > 0 {
+                paused_rebuilds.ids.extend(to_build);
+            } else {
+                drop(paused_rebuilds);
+                // NOTE: the txn may have (reentrantly) disposed some of the capsules it modified
+                self.write_txn().build_queued_capsules(to_build);
+            }
+        }
+
+        #[cfg(feature = "logging")]
+        log::log!(
+            log_level,
+            "Completed side effect transaction (depth {depth})"
+        );
+
+        drop(curr_txn_modified_ids); // ensure the lock is held until after the last store write txn
+    }
+}
+
+struct SideEffectTxnDepthGuard<'a>(&'a AtomicUsize);
+
+impl Drop for SideEffectTxnDepthGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+type SideEffectStateMutation<'f> = Box<dyn 'f + FnOnce(&mut dyn Any)>;
+type SideEffectStateMutationRunner = Arc<dyn Send + Sync + Fn(SideEffectStateMutation)>;
+type SideEffectTxn<'f> = Box<dyn 'f + FnOnce()>;
+type SideEffectTxnRunner = Arc<dyn Send + Sync + Fn(SideEffectTxn)>;
+type ContainerFinalizer = Box<dyn Send + FnOnce()>;
+/// Identifies a [`ContainerFinalizer`] by its capsule and its order of registration in a build.
+type ContainerFinalizerKey = (CapsuleId, usize);
+type ContainerFinalizerRegistrar = Arc<dyn Send + Sync + Fn(ContainerFinalizer)>;
+
+#[derive(Clone)]
+struct SideEffectTxnOrchestrator(Weak<ContainerStore>);
+impl SideEffectTxnOrchestrator {
+    fn create_state_mutater_for_id(self, id: CapsuleId) -> SideEffectStateMutationRunner {
+        Arc::new(move |mutation| {
+            let Some(store) = self.0.upgrade() else {
+                #[cfg(feature = "logging")]
+                log::warn!(
+                    "Attempted to mutate side effect after Container disposal on Capsule ({id:?})"
+                );
+                return;
+            };
+
+            store.run_side_effect_mutation(id.clone(), mutation);
+        })
+    }
+
+    /// Notifies the container's dispose observer (if any) of the supplied disposed capsules.
+    fn notify_dispose_observer(&self, disposed_ids: &[CapsuleId]) {
+        let Some(store) = self.0.upgrade() else {
+            return;
+        };
+
+        // NOTE: the observer is cloned out so that it is not invoked while holding the lock
+        let observer = store.dispose_observer.lock().clone();
+        if let Some(observer) = observer {
+            for id in disposed_ids {
+                observer(id);
+            }
+        }
+    }
+
+    /// Creates a finalizer registrar for one build of the capsule with the supplied id.
+    ///
+    /// Finalizers are keyed by the capsule and their order of registration within the build,
+    /// so that a capsule that is disposed and later reinitialized replaces its old finalizers
+    /// (instead of piling up duplicates for the lifetime of the container).
+    fn create_finalizer_registrar_for_id(self, id: CapsuleId) -> ContainerFinalizerRegistrar {
+        let registrations = AtomicUsize::new(0);
+        Arc::new(move |finalizer| {
+            let Some(store) = self.0.upgrade() else {
+                return;
+            };
+            let key = (
+                CapsuleId::clone(&id),
+                registrations.fetch_add(1, Ordering::SeqCst),
+            );
+            let mut finalizers = store.finalizers.lock();
+            finalizers.retain(|(other_key, _)| other_key != &key);
+            finalizers.push((key, finalizer));
+        })
+    }
+
+    fn create_txn_runner(self) -> SideEffectTxnRunner {
+        Arc::new(move |txn| {
+            let Some(store) = self.0.upgrade() else {
+                #[cfg(feature = "logging")]
+                log::warn!("Attempted to run a side effect txn after Container disposal");
+                return;
+            };
+
+            store.run_side_effect_txn(txn);
+        })
+    }
+}
+
+fn downcast_capsule_data<C: Capsule>(x: &impl Deref<Target = dyn Any + Send + Sync>) -> &C::Data {
+    x.downcast_ref::<C::Data>()
+        .expect("Types should be properly enforced due to generics")
+}
+
+// This struct is completely typeless in order to avoid *a lot* of dynamic dispatch
+// that we used to have when dealing with the graph nodes.
+// We avoid needing types by storing a fn pointer of a function that performs the actual build.
+// A capsule's build is a capsule's only type-specific behavior!
+// Note: we use Option over a few fields in CapsuleManager to enforce a safer ownership model
+// (ownership of some of the CapsuleManager's fields must be taken during builds).
+struct CapsuleManager {
+    capsule: Option<Box<dyn Any + Send>>,
+    side_effect: Option<OnceCell<Box<dyn Any + Send>>>,
+    dependencies: CapsuleIdSet,
+    dependents: DependentSet,
+    always_rebuild: bool,
+    type_name: &'static str,
+    build: fn(CapsuleId, &mut ContainerWriteTxn) -> bool,
+    /// This capsule type's build counter in the container's stats, set on the first build.
+    build_counter: Option<Arc<AtomicUsize>>,
+    /// The capsules read during the last build, in the order they were read.
+    #[cfg(feature = "read-tracking")]
+    last_reads: Vec<CapsuleId>,
+}
+
+impl CapsuleManager {
+    fn new<C: Capsule>(capsule: C) -> Self {
+        Self {
+            capsule: Some(Box::new(capsule)),
+            side_effect: Some(OnceCell::new()),
+            dependencies: CapsuleIdSet::default(),
+            dependents: DependentSet::default(),
+            always_rebuild: C::always_rebuild(),
+            type_name: std::any::type_name::<C>(),
+            build: Self::build::<C>,
+            build_counter: None,
+            #[cfg(feature = "read-tracking")]
+            last_reads: Vec::new(),
+        }
+    }
+
+    /// Creates a stand-in for the supplied `node` in a [`Container::preview`] scope,
+    /// whose data is copied over from the original container and which is never built.
+    fn preview_placeholder(node: &Self) -> Self {
+        Self {
+            capsule: None,
+            // NOTE: placeholders must never be garbage collected (as they cannot be rebuilt),
+            // so they are given initialized side effect state to mark them as nonidempotent
+            side_effect: Some(OnceCell::from(Box::new(()) as Box<dyn Any + Send>)),
+            dependencies: CapsuleIdSet::default(),
+            dependents: DependentSet::default(),
+            always_rebuild: node.always_rebuild,
+            type_name: node.type_name,
+            build: |_, _| unreachable!("Preview placeholders have no dependencies to rebuild them"),
+            build_counter: None,
+            #[cfg(feature = "read-tracking")]
+            last_reads: Vec::new(),
+        }
+    }
+
+    /// Builds a capsule's new data and puts it into the txn, returning true when the data changes.
+    fn build<C: Capsule>(id: CapsuleId, txn: &mut ContainerWriteTxn) -> bool {
         #[cfg(feature = "logging")]
-        log::debug!("Mutating side effect state in Capsule ({:?})", id);
+        log::trace!("Building {} ({:?})", std::any::type_name::<C>(), id);
+
+        let new_data = {
+            let side_effect_state_mutater = txn
+                .side_effect_txn_orchestrator
+                .clone()
+                .create_state_mutater_for_id(CapsuleId::clone(&id));
+            let side_effect_txn_runner =
+                txn.side_effect_txn_orchestrator.clone().create_txn_runner();
+            let container_finalizer_registrar = txn
+                .side_effect_txn_orchestrator
+                .clone()
+                .create_finalizer_registrar_for_id(CapsuleId::clone(&id));
+
+            let (capsule, mut side_effect) = txn.take_capsule_and_side_effect(&id);
+            let new_data = capsule
+                .downcast_ref::<C>()
+                .expect("Types should be properly enforced due to generics")
+                .build(CapsuleHandle {
+                    get: CapsuleReader::new(CapsuleId::clone(&id), txn),
+                    register: SideEffectRegistrar::new(
+                        &mut side_effect,
+                        side_effect_state_mutater,
+                        side_effect_txn_runner,
+                    )
+                    .with_capsule_type_name(std::any::type_name::<C>())
+                    .with_container_finalizer_registrar(container_finalizer_registrar),
+                });
+            txn.yield_capsule_and_side_effect(&id, capsule, side_effect);
+
+            new_data
+        };
+
+        let did_change = txn
+            .data
+            .remove(&id)
+            .as_ref()
+            .map(downcast_capsule_data::<C>)
+            .is_none_or(|old_data| !C::eq(old_data, &new_data));
+
+        txn.data.insert(id, Arc::new(new_data));
+
+        did_change
+    }
+
+    fn is_idempotent(&self) -> bool {
+        self.side_effect
+            .as_ref()
+            .unwrap_or_else(|| Error::NodeInUse.panic())
+            .get()
+            .is_none()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use crate::*;
+
+    mod effects {
+        use super::*;
+
+        pub fn as_listener() -> impl for<'a> SideEffect<Api<'a> = ()> {}
+
+        pub fn cloned_state<T: Clone + Send + 'static>(
+            initial: T,
+        ) -> impl for<'a> SideEffect<Api<'a> = (T, impl CData + Fn(T))> {
+            move |register: SideEffectRegistrar| {
+                let (state, rebuild, _) = register.raw(initial);
+                let set_state = move |new_state| {
+                    rebuild(Box::new(|state| *state = new_state));
+                };
+                (state.clone(), set_state)
+            }
+        }
+
+        pub fn is_first_build() -> impl for<'a> SideEffect<Api<'a> = bool> {
+            move |register: SideEffectRegistrar| {
+                let (has_built_before, _, _) = register.raw(false);
+                let is_first_build = !*has_built_before;
+                *has_built_before = true;
+                is_first_build
+            }
+        }
+
+        pub fn rebuilder() -> impl for<'a> SideEffect<Api<'a> = impl CData + Fn()> {
+            move |register: SideEffectRegistrar| {
+                let ((), rebuild, _) = register.raw(());
+                move || rebuild(Box::new(|()| {}))
+            }
+        }
+    }
+
+    #[test]
+    const fn container_send_sync() {
+        const fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::Container>();
+    }
+
+    /// Check for some fundamental functionality with the classic count example
+    #[test]
+    fn basic_count() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        let txn = container.0.read_txn();
+        assert_eq!(
+            (None, None),
+            (txn.try_read(&count), txn.try_read(&count_plus_one))
+        );
+        drop(txn);
+        assert_eq!(1, container.0.write_txn().read_or_init(count_plus_one));
+        assert_eq!(0, container.0.read_txn().try_read(&count).unwrap());
+
+        let container = Container::new();
+        assert_eq!((0, 1), container.read((count, count_plus_one)));
+    }
+
+    mod state_updates {
+        use super::*;
+
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn dependent(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
+
+        #[test]
+        fn state_gets_updates() {
+            let container = Container::new();
+
+            let (state, set_state) = container.read(stateful);
+            assert_eq!(state, 0);
+
+            set_state(1);
+            let (state, set_state) = container.read(stateful);
+            assert_eq!(state, 1);
+
+            set_state(2);
+            set_state(3);
+            let (state, _) = container.read(stateful);
+            assert_eq!(state, 3);
+        }
+
+        #[test]
+        fn dependent_gets_updates() {
+            let container = Container::new();
+
+            let ((state, set_state), plus_one) = container.read((stateful, dependent));
+            assert_eq!(0, state);
+            assert_eq!(1, plus_one);
+            set_state(1);
+
+            let ((state, _), plus_one) = container.read((stateful, dependent));
+            assert_eq!(1, state);
+            assert_eq!(2, plus_one);
+        }
+    }
+
+    #[test]
+    fn multiple_side_effect() {
+        fn foo(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, u8, impl CData + Fn(u8), impl CData + Fn(u8)) {
+            let ((s1, ss1), (s2, ss2)) =
+                register.register((effects::cloned_state(0), effects::cloned_state(1)));
+            (s1, s2, ss1, ss2)
+        }
+
+        let container = Container::new();
+
+        let (s1, s2, set1, set2) = container.read(foo);
+        assert_eq!(0, s1);
+        assert_eq!(1, s2);
+
+        set1(1);
+        set2(2);
+        let (s1, s2, _, _) = container.read(foo);
+        assert_eq!(1, s1);
+        assert_eq!(2, s2);
+    }
+
+    #[cfg(feature = "experimental-api")]
+    #[test]
+    fn read_txn_read_with() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        container.read(count_plus_one);
+        let reads = container.with_read_txn(|txn| {
+            txn.read_with(|txn| (txn.try_read(&count), txn.try_read(&count_plus_one)))
+        });
+        assert_eq!(reads, (Some(0), Some(1)));
+    }
+
+    #[cfg(feature = "experimental-api")]
+    #[test]
+    fn get_and_register() {
+        fn rebuildable(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
+            register(effects::rebuilder(), effects::as_listener()).0
+        }
+
+        fn build_counter(CapsuleHandle { mut get, register }: CapsuleHandle) -> usize {
+            _ = get(rebuildable); // mark dep
+
+            let is_first_build = register(effects::is_first_build());
+            if is_first_build {
+                1
+            } else {
+                get(build_counter) + 1
+            }
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(build_counter), 1);
+        container.read(rebuildable)();
+        assert_eq!(container.read(build_counter), 2);
+        container.read(rebuildable)();
+        assert_eq!(container.read(build_counter), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "but found u16")]
+    fn changing_side_effect_type_panics() {
+        fn toggle(CapsuleHandle { register, .. }: CapsuleHandle) -> (bool, impl CData + Fn(bool)) {
+            register.register(effects::cloned_state(false))
+        }
+
+        fn inconsistent_capsule(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            if get.as_ref(toggle).0 {
+                _ = register.raw(0_u16);
+            } else {
+                _ = register.raw(0_u8);
+            }
+        }
+
+        let container = Container::new();
+        container.read(inconsistent_capsule);
+        container.read(toggle).1(true);
+    }
+
+    #[test]
+    fn read_by_ref() {
+        #[derive(Clone)]
+        struct DynamicCapsule(Vec<u32>);
+        impl Capsule for DynamicCapsule {
+            type Data = u32;
+
+            fn build(&self, _: CapsuleHandle) -> Self::Data {
+                self.0.iter().sum()
+            }
+
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+
+            fn key(&self) -> impl CapsuleKey {
+                self.0.clone()
+            }
+        }
+
+        let container = Container::new();
+        let capsule = DynamicCapsule(vec![1, 2, 3]);
+        assert_eq!(container.read_by_ref(&capsule), 6);
+        assert_eq!(container.read_by_ref(&capsule), 6);
+        container.read_ref_by_ref(&capsule, |data| assert_eq!(*data, 6));
+        container.read_ref_by_ref(&DynamicCapsule(vec![4]), |data| assert_eq!(*data, 4));
+        assert_eq!(container.read(DynamicCapsule(vec![4])), 4);
+    }
+
+    #[test]
+    fn read_map() {
+        fn list(_: CapsuleHandle) -> Vec<u8> {
+            vec![1, 2, 3]
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read_map(list, Vec::len), 3);
+        assert_eq!(container.read_map(list, |list| list[1]), 2);
+    }
+
+    #[test]
+    fn read_then_releases_lock() {
+        fn count(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        let container = Container::new();
+        let new_count = container.read_then(count, |(curr_count, set_count)| {
+            // Would deadlock if the read lock were still held here
+            set_count(curr_count + 1);
+            container.read(count).0
+        });
+        assert_eq!(new_count, 1);
+    }
+
+    #[test]
+    fn capsule_handle_ref_reads() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
+
+        let container = Container::new();
+        let handle = container.handle(plus_one);
+        assert_eq!(handle.id(), &plus_one.id());
+        assert_eq!(handle.read(), 1);
+
+        container.read(stateful).1(1);
+        assert_eq!(handle.read(), 2);
+        handle.read_ref(|data| assert_eq!(*data, 2));
+    }
+
+    #[test]
+    fn invalidate_type_rebuilds_keyed_capsules() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        static CELL_BUILDS: AtomicU8 = AtomicU8::new(0);
+        static TOTAL_BUILDS: AtomicU8 = AtomicU8::new(0);
+        static DOWNSTREAM_BUILDS: AtomicU8 = AtomicU8::new(0);
+
+        #[derive(Clone, Copy)]
+        struct Cell(u8, u8);
+        impl Capsule for Cell {
+            type Data = u8;
+
+            fn build(&self, _: CapsuleHandle) -> Self::Data {
+                CELL_BUILDS.fetch_add(1, Ordering::SeqCst);
+                self.0 + self.1
+            }
+
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+
+            fn key(&self) -> impl CapsuleKey {
+                (self.0, self.1)
+            }
+        }
+
+        struct Total;
+        impl Capsule for Total {
+            type Data = u8;
+
+            fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+                TOTAL_BUILDS.fetch_add(1, Ordering::SeqCst);
+                get.snapshot(Cell(0, 1)) + get.snapshot(Cell(1, 1))
+            }
+
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+        }
+
+        fn downstream(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            register.register(effects::as_listener());
+            DOWNSTREAM_BUILDS.fetch_add(1, Ordering::SeqCst);
+            *get.as_ref(Total)
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read((downstream, Cell(2, 2))), (3, 4));
+        assert_eq!(CELL_BUILDS.load(Ordering::SeqCst), 3);
+        assert_eq!(TOTAL_BUILDS.load(Ordering::SeqCst), 1);
+        assert_eq!(DOWNSTREAM_BUILDS.load(Ordering::SeqCst), 1);
+
+        container.invalidate_type::<Cell>();
+        assert_eq!(container.read((downstream, Cell(2, 2))), (3, 4));
+        assert_eq!(CELL_BUILDS.load(Ordering::SeqCst), 6);
+        assert_eq!(TOTAL_BUILDS.load(Ordering::SeqCst), 2);
+        assert_eq!(DOWNSTREAM_BUILDS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn optional_side_effect() {
+        fn logged_in(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (bool, impl CData + Fn(bool)) {
+            register.register(effects::cloned_state(false))
+        }
+
+        fn session(
+            CapsuleHandle { mut get, register }: CapsuleHandle,
+        ) -> Option<(u8, impl CData + Fn(u8))> {
+            let is_logged_in = get.as_ref(logged_in).0;
+            register.register(is_logged_in.then(|| effects::cloned_state(0)))
+        }
+
+        let container = Container::new();
+        assert!(container.read(session).is_none());
+
+        container.read(logged_in).1(true);
+        let (state, set_state) = container.read(session).unwrap();
+        assert_eq!(state, 0);
+        set_state(1);
+        assert_eq!(container.read(session).unwrap().0, 1);
+
+        container.read(logged_in).1(false);
+        assert!(container.read(session).is_none());
+        container.read(logged_in).1(true);
+        assert_eq!(container.read(session).unwrap().0, 0);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn assert_no_builds_passes_on_cached_reads() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        let container = Container::new();
+        container.read(count);
+        container.assert_no_builds(|| assert_eq!(container.read(count), 0));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Expected no capsule builds, but 2 occurred")]
+    fn assert_no_builds_panics_on_builds() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn plus_one(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            register.register(effects::as_listener());
+            get.as_ref(stateful).0 + 1
+        }
+
+        let container = Container::new();
+        let set_state = container.read((stateful, plus_one)).0 .1;
+        container.assert_no_builds(|| set_state(1));
+    }
+
+    #[test]
+    fn dispose_removes_listener_capsules() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        static LISTENER_BUILDS: AtomicU8 = AtomicU8::new(0);
+
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn listener(CapsuleHandle { mut get, register }: CapsuleHandle) {
+            register.register(effects::as_listener());
+            get.as_ref(stateful);
+            LISTENER_BUILDS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let container = Container::new();
+        container.read(listener);
+        container.read(stateful).1(1);
+        assert_eq!(LISTENER_BUILDS.load(Ordering::SeqCst), 2);
+
+        assert!(container.dispose(&listener));
+        assert!(!container.dispose(&listener));
+        container.read(stateful).1(2);
+        assert_eq!(LISTENER_BUILDS.load(Ordering::SeqCst), 2);
+
+        assert!(container.dispose(&stateful));
+        assert_eq!(container.stats().node_count, 0);
+    }
+
+    #[test]
+    fn dispose_keeps_dependent_listeners() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = container.listen_simple(move |mut get| {
+            tx.send(get.as_ref(stateful).0).unwrap();
+        });
+        let subscription = container.subscribe(&stateful);
+        container.read(stateful).1(1);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1]);
 
-        self.run_side_effect_txn(|| {
-            mutation(
-                self.nodes
-                    .lock()
-                    .deref_mut()
-                    .get_mut(&id)
-                    .expect("The node must be in the graph since it registers a side effect")
-                    .side_effect
-                    .as_mut()
-                    .expect("We should have sole ownership over side_effect since we hold the lock")
-                    .get_mut()
-                    .expect("Side effect must have been previously initialized to invoke a rebuild")
-                    .as_mut(),
-            );
-            self.curr_side_effect_txn_modified_ids
-                .lock()
-                .deref()
-                .borrow_mut()
-                .as_mut()
-                .expect("Called in a side effect txn, so txn should be Some")
-                .insert(id);
+        // The listeners are rebuilt (with a fresh stateful) instead of being disposed
+        assert!(container.dispose(&stateful));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(subscription.try_recv().map(|(count, _)| count), Some(0));
+
+        container.read(stateful).1(2);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![2]);
+        drop((handle, subscription));
+        assert_eq!(container.dependent_count(&stateful), Some(0));
+    }
+
+    #[test]
+    fn dropping_listeners_after_dispose() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        let container = Container::new();
+        let handle = container.listen_simple(|mut get| {
+            get.as_ref(stateful);
         });
+        let subscription = container.subscribe(&stateful);
+
+        // Dispose the listener capsules out from under their handles
+        let listener_ids = container
+            .0
+            .nodes
+            .lock()
+            .keys()
+            .filter(|id| **id != stateful.id())
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in listener_ids {
+            container.0.write_txn().dispose_node_if_present(&id);
+        }
+        assert_eq!(container.stats().node_count, 1);
+
+        drop(handle);
+        drop(subscription);
+        assert_eq!(container.stats().node_count, 1);
     }
 
-    fn run_side_effect_txn<F: FnOnce()>(&self, txn: F) {
-        let curr_txn_modified_ids = self.curr_side_effect_txn_modified_ids.lock();
+    #[test]
+    fn dependent_count() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
 
-        let is_root_txn = curr_txn_modified_ids.borrow().is_none();
-        if is_root_txn {
-            #[cfg(feature = "logging")]
-            log::debug!("Starting side effect transaction");
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
 
-            *curr_txn_modified_ids.deref().borrow_mut() = Some(HashSet::new());
+        fn plus_two(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 2
         }
 
-        txn();
+        let container = Container::new();
+        assert_eq!(container.dependent_count(&count), None);
 
-        if is_root_txn {
-            let to_build = curr_txn_modified_ids
-                .deref()
-                .borrow_mut()
-                .take()
-                .expect("Ensured initialization above");
-            self.write_txn().build_capsules_or_panic(&to_build);
+        container.read(count);
+        assert_eq!(container.dependent_count(&count), Some(0));
+
+        container.read((plus_one, plus_two));
+        assert_eq!(container.dependent_count(&count), Some(2));
+        assert_eq!(container.dependent_count(&plus_one), Some(0));
+    }
 
-            #[cfg(feature = "logging")]
-            log::debug!("Completed side effect transaction");
+    #[cfg(feature = "read-tracking")]
+    #[test]
+    fn last_reads_tracks_conditional_reads() {
+        fn flag(CapsuleHandle { register, .. }: CapsuleHandle) -> (bool, impl CData + Fn(bool)) {
+            register.register(effects::cloned_state(false))
         }
 
-        drop(curr_txn_modified_ids); // ensure the lock is held until after the last store write txn
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn conditional(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            register.register(effects::as_listener());
+            if get.as_ref(flag).0 {
+                get.snapshot(count) + get.snapshot(count)
+            } else {
+                0
+            }
+        }
+
+        let container = Container::new();
+        assert_eq!(container.last_reads(&conditional), None);
+
+        container.read(conditional);
+        assert_eq!(
+            container.last_reads(&conditional),
+            Some(vec![CapsuleId::of(&flag)])
+        );
+
+        container.read(flag).1(true);
+        assert_eq!(
+            container.last_reads(&conditional),
+            Some(vec![
+                CapsuleId::of(&flag),
+                CapsuleId::of(&count),
+                CapsuleId::of(&count)
+            ])
+        );
+
+        container.read(flag).1(false);
+        assert_eq!(
+            container.last_reads(&conditional),
+            Some(vec![CapsuleId::of(&flag)])
+        );
     }
-}
 
-type SideEffectStateMutation<'f> = Box<dyn 'f + FnOnce(&mut dyn Any)>;
-type SideEffectStateMutationRunner = Arc<dyn Send + Sync + Fn(SideEffectStateMutation)>;
-type SideEffectTxn<'f> = Box<dyn 'f + FnOnce()>;
-type SideEffectTxnRunner = Arc<dyn Send + Sync + Fn(SideEffectTxn)>;
+    #[test]
+    fn disable_idempotent_gc_keeps_capsules_alive() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
 
-#[derive(Clone)]
-struct SideEffectTxnOrchestrator(Weak<ContainerStore>);
-impl SideEffectTxnOrchestrator {
-    fn create_state_mutater_for_id(self, id: CapsuleId) -> SideEffectStateMutationRunner {
-        Arc::new(move |mutation| {
-            let Some(store) = self.0.upgrade() else {
-                #[cfg(feature = "logging")]
-                log::warn!(
-                    "Attempted to mutate side effect after Container disposal on Capsule ({:?})",
-                    id
-                );
-                return;
-            };
+        let container = Container::new();
+        container.read(plus_one);
+        container.read(stateful).1(1);
+        assert_eq!(container.try_read(&plus_one), None);
 
-            store.run_side_effect_mutation(id.clone(), mutation);
-        })
+        let container = Container::builder().without_idempotent_gc().build();
+        container.read(plus_one);
+        container.read(stateful).1(1);
+        assert_eq!(container.try_read(&plus_one), Some(2));
+
+        // Explicit garbage collection still works
+        assert_eq!(container.collect_garbage(), 1);
+        assert_eq!(container.try_read(&plus_one), None);
     }
 
-    fn create_txn_runner(self) -> SideEffectTxnRunner {
-        Arc::new(move |txn| {
-            let Some(store) = self.0.upgrade() else {
-                #[cfg(feature = "logging")]
-                log::warn!("Attempted to run a side effect txn after Container disposal");
-                return;
-            };
+    #[test]
+    fn side_effect_txn_skips_capsules_disposed_within_it() {
+        fn count(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, impl CData + Fn(u8), SideEffectTxnRunner) {
+            let (count, set_count, run_txn) = register.raw(0);
+            (
+                *count,
+                move |new_count| {
+                    set_count(Box::new(move |count| *count = new_count));
+                },
+                run_txn,
+            )
+        }
 
-            store.run_side_effect_txn(txn);
-        })
+        let container = Container::new();
+        let (_, set_count, run_txn) = container.read(count);
+        run_txn(Box::new(|| {
+            set_count(1);
+            assert!(container.dispose(&count));
+            container.collect_garbage();
+        }));
+        assert_eq!(container.read(count).0, 0);
     }
-}
 
-fn downcast_capsule_data<C: Capsule>(x: &impl Deref<Target = dyn Any + Send + Sync>) -> &C::Data {
-    x.downcast_ref::<C::Data>()
-        .expect("Types should be properly enforced due to generics")
-}
+    #[test]
+    fn collect_garbage_keeps_nonidempotent_dependencies() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
 
-const EXCLUSIVE_OWNER_MSG: &str =
-    "Attempted to use a CapsuleManager field when someone else already had ownership";
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
 
-// This struct is completely typeless in order to avoid *a lot* of dynamic dispatch
-// that we used to have when dealing with the graph nodes.
-// We avoid needing types by storing a fn pointer of a function that performs the actual build.
-// A capsule's build is a capsule's only type-specific behavior!
-// Note: we use Option over a few fields in CapsuleManager to enforce a safer ownership model
-// (ownership of some of the CapsuleManager's fields must be taken during builds).
-struct CapsuleManager {
-    capsule: Option<Box<dyn Any + Send>>,
-    side_effect: Option<OnceCell<Box<dyn Any + Send>>>,
-    dependencies: HashSet<CapsuleId>,
-    dependents: HashSet<CapsuleId>,
-    build: fn(CapsuleId, &mut ContainerWriteTxn) -> bool,
-}
+        fn plus_two(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(plus_one) + 1
+        }
 
-impl CapsuleManager {
-    fn new<C: Capsule>(capsule: C) -> Self {
-        Self {
-            capsule: Some(Box::new(capsule)),
-            side_effect: Some(OnceCell::new()),
-            dependencies: HashSet::new(),
-            dependents: HashSet::new(),
-            build: Self::build::<C>,
+        fn unrelated(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn listener(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            register.register(effects::as_listener());
+            get.as_ref(plus_one) + 1
         }
+
+        let container = Container::new();
+        container.read((plus_two, unrelated, listener));
+        assert_eq!(container.collect_garbage(), 2);
+
+        assert_eq!(container.dependent_count(&plus_two), None);
+        assert_eq!(container.dependent_count(&unrelated), None);
+        assert_eq!(container.dependent_count(&plus_one), Some(1));
+        assert_eq!(container.dependent_count(&count), Some(1));
+        assert_eq!(container.dependent_count(&listener), Some(0));
+        assert_eq!(container.collect_garbage(), 0);
     }
 
-    /// Builds a capsule's new data and puts it into the txn, returning true when the data changes.
-    fn build<C: Capsule>(id: CapsuleId, txn: &mut ContainerWriteTxn) -> bool {
-        #[cfg(feature = "logging")]
-        log::trace!("Building {} ({:?})", std::any::type_name::<C>(), id);
+    #[test]
+    fn try_garbage_collect_only_disposes_idempotent_subgraphs() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
 
-        let new_data = {
-            let side_effect_state_mutater = txn
-                .side_effect_txn_orchestrator
-                .clone()
-                .create_state_mutater_for_id(CapsuleId::clone(&id));
-            let side_effect_txn_runner =
-                txn.side_effect_txn_orchestrator.clone().create_txn_runner();
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        fn plus_two(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(plus_one) + 1
+        }
+
+        fn listener(CapsuleHandle { mut get, register }: CapsuleHandle) -> u8 {
+            register.register(effects::as_listener());
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        assert_eq!(container.try_garbage_collect(&count), GcResult::NotPresent);
+
+        container.read((plus_two, listener));
+        let node_count = container.stats().node_count;
+        assert_eq!(
+            container.try_garbage_collect(&count),
+            GcResult::ValidationFailed
+        );
+        assert_eq!(container.stats().node_count, node_count);
+
+        assert_eq!(container.try_garbage_collect(&plus_one), GcResult::Success);
+        assert_eq!(container.dependent_count(&plus_one), None);
+        assert_eq!(container.dependent_count(&plus_two), None);
+        assert_eq!(container.dependent_count(&count), Some(1));
+        assert_eq!(
+            container.try_garbage_collect(&plus_two),
+            GcResult::NotPresent
+        );
+    }
+
+    #[test]
+    fn side_effect_cleanup_can_mutate_other_capsules() {
+        struct OnDrop<F: FnOnce()>(Option<F>);
+        impl<F: FnOnce()> Drop for OnDrop<F> {
+            fn drop(&mut self) {
+                if let Some(callback) = self.0.take() {
+                    callback();
+                }
+            }
+        }
+
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        let container = Container::new();
+        let set_state = container.read(stateful).1;
+        let handle = container.listen(
+            move || {
+                let set_state = set_state.clone();
+                move |register: SideEffectRegistrar| {
+                    _ = register.raw(OnDrop(Some(move || set_state(1))));
+                }
+            },
+            |_, ()| {},
+        );
+
+        drop(handle);
+        assert_eq!(container.read(stateful).0, 1);
+    }
+
+    #[test]
+    fn subscription_receives_changes() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
+
+        let container = Container::new();
+        let subscription = container.subscribe(&plus_one);
+        let other_subscription = container.subscribe(&plus_one);
+        assert_eq!(subscription.try_recv(), Some(1));
+        assert_eq!(subscription.try_recv(), None);
+
+        container.read(stateful).1(1);
+        container.read(stateful).1(2);
+        assert_eq!(subscription.try_recv(), Some(3));
+        assert_eq!(subscription.try_recv(), None);
+        assert_eq!(other_subscription.try_recv(), Some(3));
+
+        drop((subscription, other_subscription));
+        assert_eq!(container.dependent_count(&plus_one), Some(0));
+    }
+
+    #[test]
+    fn arc_keyed_dynamic_capsules() {
+        #[derive(Clone)]
+        struct PathLengthCapsule(Arc<str>);
+        impl Capsule for PathLengthCapsule {
+            type Data = usize;
+
+            fn build(&self, _: CapsuleHandle) -> Self::Data {
+                self.0.len()
+            }
+
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+
+            fn key(&self) -> impl CapsuleKey {
+                Arc::clone(&self.0)
+            }
+        }
+
+        let container = Container::new();
+        let capsule = PathLengthCapsule(Arc::from("/some/long/path"));
+        assert_eq!(container.read(capsule.clone()), 15);
+        assert_eq!(
+            container.read(PathLengthCapsule(Arc::from("/some/long/path"))),
+            15
+        );
+        assert_eq!(
+            CapsuleId::of(&capsule),
+            CapsuleId::of(&PathLengthCapsule(Arc::from("/some/long/path")))
+        );
+        assert_ne!(
+            CapsuleId::of(&capsule),
+            CapsuleId::of(&PathLengthCapsule(Arc::from("/other")))
+        );
+    }
+
+    #[test]
+    fn try_read_does_not_block_or_initialize() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        let container = Container::new();
+        assert_eq!(container.try_read(&count), None);
+        container.read(count);
+        assert_eq!(container.try_read(&count), Some(0));
+
+        let write_lock = container.0.data.write();
+        assert_eq!(container.try_read(&count), None);
+        drop(write_lock);
+    }
+
+    #[test]
+    fn read_async_or_none_initializes_without_blocking() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn other_count(_: CapsuleHandle) -> u8 {
+            1
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read_async_or_none(count), Some(0));
+        assert_eq!(container.try_read(&count), Some(0));
 
-            let (capsule, mut side_effect) = txn.take_capsule_and_side_effect(&id);
-            let new_data = capsule
-                .downcast_ref::<C>()
-                .expect("Types should be properly enforced due to generics")
-                .build(CapsuleHandle {
-                    get: CapsuleReader::new(CapsuleId::clone(&id), txn),
-                    register: SideEffectRegistrar::new(
-                        &mut side_effect,
-                        side_effect_state_mutater,
-                        side_effect_txn_runner,
-                    ),
-                });
-            txn.yield_capsule_and_side_effect(&id, capsule, side_effect);
+        let nodes_lock = container.0.nodes.lock();
+        assert_eq!(container.read_async_or_none(count), Some(0));
+        assert_eq!(container.read_async_or_none(other_count), None);
+        drop(nodes_lock);
 
-            new_data
-        };
+        let write_lock = container.0.data.write();
+        assert_eq!(container.read_async_or_none(count), None);
+        drop(write_lock);
 
-        let did_change = txn
-            .data
-            .remove(&id)
-            .as_ref()
-            .map(downcast_capsule_data::<C>)
-            .is_none_or(|old_data| !C::eq(old_data, &new_data));
+        assert_eq!(container.read_async_or_none(other_count), Some(1));
+    }
+
+    #[test]
+    fn override_with_replaces_capsules() {
+        fn base_capsule(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn stateful_capsule(CapsuleHandle { register, .. }: CapsuleHandle) -> u8 {
+            *register.raw(1).0
+        }
+        fn plus_one_capsule(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(base_capsule) + 1
+        }
 
-        txn.data.insert(id, Box::new(new_data));
+        // Overriding an uninitialized capsule, which also sticks after disposal
+        let container = Container::new();
+        container.override_with(&base_capsule, |_: CapsuleHandle| 10);
+        assert_eq!(container.read(plus_one_capsule), 11);
+        assert_eq!(container.collect_garbage(), 2);
+        assert_eq!(container.read(base_capsule), 10);
 
-        did_change
+        // Overriding an initialized capsule rebuilds its dependents (and discards its state)
+        let container = Container::new();
+        let _handle = container.listen(|| (), |mut get, ()| _ = get.as_ref(plus_one_capsule));
+        assert_eq!(container.read(plus_one_capsule), 1);
+        container.override_with(&base_capsule, stateful_capsule);
+        assert_eq!(container.read((base_capsule, plus_one_capsule)), (1, 2));
+        container.override_with(&base_capsule, |_: CapsuleHandle| 20);
+        assert_eq!(container.read((base_capsule, plus_one_capsule)), (20, 21));
+
+        // Overrides are per-container
+        assert_eq!(Container::new().read(plus_one_capsule), 1);
     }
 
-    fn is_idempotent(&self) -> bool {
-        self.side_effect
-            .as_ref()
-            .expect(EXCLUSIVE_OWNER_MSG)
-            .get()
-            .is_none()
+    #[test]
+    fn try_read_ref_does_not_initialize() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        assert_eq!(container.try_read_ref(count, |count| *count), None);
+
+        container.read(count);
+        assert_eq!(container.try_read_ref(count, |count| *count), Some(0));
+        assert_eq!(
+            container.try_read_ref((count, count_plus_one), |(a, b)| (*a, *b)),
+            None
+        );
+        assert_eq!(container.try_read(&count_plus_one), None);
     }
-}
 
-#[cfg(test)]
-#[allow(clippy::unwrap_used)]
-mod tests {
-    use crate::*;
+    #[test]
+    fn snapshot_and_restore_capsule_family() {
+        #[derive(Clone, Copy)]
+        struct Cell(u8, u8);
+        impl Capsule for Cell {
+            type Data = u16;
 
-    mod effects {
-        use super::*;
+            fn build(&self, _: CapsuleHandle) -> Self::Data {
+                u16::from(self.0) * u16::from(self.1)
+            }
 
-        pub fn as_listener() -> impl for<'a> SideEffect<Api<'a> = ()> {}
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
 
-        pub fn cloned_state<T: Clone + Send + 'static>(
-            initial: T,
-        ) -> impl for<'a> SideEffect<Api<'a> = (T, impl CData + Fn(T))> {
-            move |register: SideEffectRegistrar| {
-                let (state, rebuild, _) = register.raw(initial);
-                let set_state = move |new_state| {
-                    rebuild(Box::new(|state| *state = new_state));
-                };
-                (state.clone(), set_state)
+            fn key(&self) -> impl CapsuleKey {
+                (self.0, self.1)
             }
         }
+        impl CapsuleFamily for Cell {
+            type SerializedKey = (u8, u8);
 
-        pub fn is_first_build() -> impl for<'a> SideEffect<Api<'a> = bool> {
-            move |register: SideEffectRegistrar| {
-                let (has_built_before, _, _) = register.raw(false);
-                let is_first_build = !*has_built_before;
-                *has_built_before = true;
-                is_first_build
+            fn serialize_key(&self) -> Self::SerializedKey {
+                (self.0, self.1)
             }
-        }
 
-        pub fn rebuilder() -> impl for<'a> SideEffect<Api<'a> = impl CData + Fn()> {
-            move |register: SideEffectRegistrar| {
-                let ((), rebuild, _) = register.raw(());
-                move || rebuild(Box::new(|()| {}))
+            fn deserialize_key((row, col): Self::SerializedKey) -> Self {
+                Self(row, col)
             }
         }
+
+        let container = Container::new();
+        container.read((Cell(2, 3), Cell(4, 5)));
+        let mut snapshot = container.snapshot_family::<Cell>();
+        snapshot.sort_unstable();
+        assert_eq!(snapshot, vec![((2, 3), 6), ((4, 5), 20)]);
+
+        let container = Container::new();
+        container.read(Cell(4, 5));
+        container.restore_family::<Cell>(vec![((2, 3), 100), ((4, 5), 100)]);
+        assert_eq!(container.read((Cell(2, 3), Cell(4, 5))), (100, 20));
+
+        container.invalidate_type::<Cell>();
+        assert_eq!(container.read((Cell(2, 3), Cell(4, 5))), (6, 20));
     }
 
     #[test]
-    const fn container_send_sync() {
-        const fn assert_send_sync<T: Send + Sync>() {}
-        assert_send_sync::<crate::Container>();
+    fn deferred_rebuilds() {
+        fn count(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            let (count, set_count, _) = register.raw(0);
+            (*count, move |new_count| {
+                set_count(Box::new(move |count| *count = new_count));
+            })
+        }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count).0 + 1
+        }
+
+        let container = Container::with_deferred_rebuilds();
+        assert_eq!(container.read(count_plus_one), 1);
+        let set_count = container.read(count).1;
+        set_count(1);
+        set_count(2);
+        assert_eq!(container.read(count_plus_one), 1);
+
+        container.flush_rebuilds();
+        assert_eq!(container.read(count_plus_one), 3);
+        container.flush_rebuilds();
+        assert_eq!(container.read(count_plus_one), 3);
+
+        // Capsules disposed before the flush are skipped
+        set_count(3);
+        assert!(container.dispose(&count));
+        container.flush_rebuilds();
+        assert_eq!(container.read(count_plus_one), 1);
+
+        let container = Container::new();
+        container.read(count).1(1);
+        container.flush_rebuilds();
+        assert_eq!(container.read(count_plus_one), 2);
     }
 
-    /// Check for some fundamental functionality with the classic count example
     #[test]
-    fn basic_count() {
+    fn builder_configures_container() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        fn config(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+        fn sum(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            *get.as_ref(config) + get.as_ref(stateful).0
+        }
+
+        let build_count = Arc::new(AtomicUsize::new(0));
+        let container = Container::builder()
+            .with_deferred_rebuilds()
+            .with_build_middleware(Arc::new({
+                let build_count = Arc::clone(&build_count);
+                move |_, build| {
+                    build_count.fetch_add(1, Ordering::SeqCst);
+                    build();
+                }
+            }))
+            .seed(config, 10)
+            .setup(|setup| {
+                setup.read(sum);
+            })
+            .build();
+        assert_eq!(build_count.load(Ordering::SeqCst), 2);
+        assert_eq!(container.read(sum), 10);
+
+        container.read(stateful).1(1);
+        assert_eq!(container.read(sum), 10);
+        container.flush_rebuilds();
+        assert_eq!(container.read(sum), 11);
+    }
+
+    #[test]
+    fn new_with_performs_initial_setup() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        static BUILDS: AtomicU8 = AtomicU8::new(0);
+
         fn count(_: CapsuleHandle) -> u8 {
             0
         }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            BUILDS.fetch_add(1, Ordering::SeqCst);
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new_with(|setup| {
+            setup.seed(count, 1);
+            assert_eq!(setup.read(count_plus_one), 2);
+            assert_eq!(*setup.read_ref(count_plus_one), 2);
+        });
+        assert_eq!(container.read(count_plus_one), 2);
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 1);
+    }
 
+    #[test]
+    fn build_middlewares_compose_in_order() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
         fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
             get.as_ref(count) + 1
         }
 
+        let events = Arc::new(Mutex::new(Vec::new()));
         let container = Container::new();
-        let txn = container.0.read_txn();
+        for name in ["outer", "inner"] {
+            let events = Arc::clone(&events);
+            container.add_build_middleware(Arc::new(move |id, build| {
+                events
+                    .lock()
+                    .push(format!("{name} start {}", id == &count.id()));
+                build();
+                events
+                    .lock()
+                    .push(format!("{name} end {}", id == &count.id()));
+            }));
+        }
+
+        assert_eq!(container.read(count_plus_one), 1);
         assert_eq!(
-            (None, None),
-            (txn.try_read(&count), txn.try_read(&count_plus_one))
+            *events.lock(),
+            [
+                "outer start false",
+                "inner start false",
+                "outer start true",
+                "inner start true",
+                "inner end true",
+                "outer end true",
+                "inner end false",
+                "outer end false",
+            ]
         );
-        drop(txn);
-        assert_eq!(1, container.0.write_txn().read_or_init(count_plus_one));
-        assert_eq!(0, container.0.read_txn().try_read(&count).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "did not invoke the build")]
+    fn build_middleware_must_invoke_build() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
 
         let container = Container::new();
-        assert_eq!((0, 1), container.read((count, count_plus_one)));
+        container.add_build_middleware(Arc::new(|_, _| {}));
+        container.read(count);
     }
 
-    mod state_updates {
-        use super::*;
+    #[test]
+    fn dispose_observer_sees_disposals() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
 
-        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+        let container = Container::new();
+        let disposed_ids = Arc::new(Mutex::new(Vec::new()));
+        container.set_dispose_observer(Arc::new({
+            let disposed_ids = Arc::clone(&disposed_ids);
+            let container = container.clone();
+            move |id| {
+                // Would deadlock if the observer were invoked while holding the container's locks
+                _ = container.stats();
+                disposed_ids.lock().push(id.clone());
+            }
+        }));
+
+        let handle = container.listen(|| (), |mut get, ()| _ = get.as_ref(count_plus_one));
+        assert!(disposed_ids.lock().is_empty());
+
+        drop(handle);
+        assert_eq!(disposed_ids.lock().len(), 1);
+
+        assert_eq!(container.collect_garbage(), 2);
+        assert_eq!(disposed_ids.lock().len(), 3);
+        assert!(disposed_ids.lock().contains(&CapsuleId::of(&count)));
+        assert!(disposed_ids
+            .lock()
+            .contains(&CapsuleId::of(&count_plus_one)));
+
+        // Break the reference cycle between the container and its observer
+        container.set_dispose_observer(Arc::new(|_| {}));
+    }
+
+    #[test]
+    fn pause_rebuilds() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        static BUILDS: AtomicU8 = AtomicU8::new(0);
+
+        fn count(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
             register.register(effects::cloned_state(0))
         }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            BUILDS.fetch_add(1, Ordering::SeqCst);
+            get.as_ref(count).0 + 1
+        }
 
-        fn dependent(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
-            get.as_ref(stateful).0 + 1
+        let container = Container::new();
+        assert_eq!(container.read(count_plus_one), 1);
+        let set_count = container.read(count).1;
+
+        let outer_pause = container.pause_rebuilds();
+        let inner_pause = container.pause_rebuilds();
+        set_count(1);
+        set_count(2);
+        assert_eq!(container.read(count_plus_one), 1);
+        inner_pause.resume();
+        assert_eq!(container.read(count_plus_one), 1);
+        drop(outer_pause);
+        assert_eq!(container.read(count_plus_one), 3);
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 2);
+
+        set_count(3);
+        assert_eq!(container.read(count_plus_one), 4);
+
+        // Capsules disposed while paused are skipped
+        let pause = container.pause_rebuilds();
+        set_count(4);
+        assert!(container.dispose(&count));
+        drop(pause);
+        assert_eq!(container.read(count_plus_one), 1);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn dependents_rebuild_in_insertion_order() {
+        static BUILDS: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+
+        fn count(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            let (count, set_count, _) = register.raw(0);
+            (*count, move |new_count| {
+                set_count(Box::new(move |count| *count = new_count));
+            })
         }
 
-        #[test]
-        fn state_gets_updates() {
-            let container = Container::new();
+        struct Listener(u8);
+        impl Capsule for Listener {
+            type Data = ();
 
-            let (state, set_state) = container.read(stateful);
-            assert_eq!(state, 0);
+            fn build(&self, CapsuleHandle { mut get, register }: CapsuleHandle) -> Self::Data {
+                register.register(|register: SideEffectRegistrar| _ = register.raw(()));
+                get.as_ref(count);
+                BUILDS
+                    .lock()
+                    .expect("Lock should not be poisoned")
+                    .push(self.0);
+            }
 
-            set_state(1);
-            let (state, set_state) = container.read(stateful);
-            assert_eq!(state, 1);
+            fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+                false
+            }
 
-            set_state(2);
-            set_state(3);
-            let (state, _) = container.read(stateful);
-            assert_eq!(state, 3);
+            fn key(&self) -> impl CapsuleKey {
+                self.0
+            }
         }
 
-        #[test]
-        fn dependent_gets_updates() {
-            let container = Container::new();
+        let container = Container::new();
+        for i in 0..16 {
+            container.read(Listener(i));
+        }
 
-            let ((state, set_state), plus_one) = container.read((stateful, dependent));
-            assert_eq!(0, state);
-            assert_eq!(1, plus_one);
-            set_state(1);
+        BUILDS.lock().expect("Lock should not be poisoned").clear();
+        container.read(count).1(1);
+        assert_eq!(
+            *BUILDS.lock().expect("Lock should not be poisoned"),
+            (0..16).collect::<Vec<_>>()
+        );
+    }
 
-            let ((state, _), plus_one) = container.read((stateful, dependent));
-            assert_eq!(1, state);
-            assert_eq!(2, plus_one);
+    #[test]
+    fn stats() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
         }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        assert_eq!(container.stats(), ContainerStats::default());
+
+        container.read((count, count_plus_one));
+        let stats = container.stats();
+        assert_eq!(stats.total_builds, 2);
+        assert_eq!(stats.node_count, 2);
+        assert_eq!(stats.builds_by_type.values().sum::<usize>(), 2);
+
+        container.collect_garbage();
+        let stats = container.stats();
+        assert_eq!(stats.total_disposals, 2);
+        assert_eq!(stats.node_count, 0);
     }
 
     #[test]
-    fn multiple_side_effect() {
-        fn foo(
-            CapsuleHandle { register, .. }: CapsuleHandle,
-        ) -> (u8, u8, impl CData + Fn(u8), impl CData + Fn(u8)) {
-            let ((s1, ss1), (s2, ss2)) =
-                register.register((effects::cloned_state(0), effects::cloned_state(1)));
-            (s1, s2, ss1, ss2)
+    fn read_erased() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
         }
 
         let container = Container::new();
+        assert!(container.read_erased(&CapsuleId::of(&count)).is_none());
 
-        let (s1, s2, set1, set2) = container.read(foo);
-        assert_eq!(0, s1);
-        assert_eq!(1, s2);
-
-        set1(1);
-        set2(2);
-        let (s1, s2, _, _) = container.read(foo);
-        assert_eq!(1, s1);
-        assert_eq!(2, s2);
+        container.read(count);
+        let data = container
+            .read_erased(&CapsuleId::of(&count))
+            .expect("count should be initialized");
+        assert_eq!(data.downcast_ref::<u8>(), Some(&0));
     }
 
-    #[cfg(feature = "experimental-api")]
     #[test]
-    fn get_and_register() {
-        fn rebuildable(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
-            register(effects::rebuilder(), effects::as_listener()).0
-        }
+    fn for_each_value_visits_initialized_capsules() {
+        use std::collections::HashMap;
 
-        fn build_counter(CapsuleHandle { mut get, register }: CapsuleHandle) -> usize {
-            _ = get(rebuildable); // mark dep
-
-            let is_first_build = register(effects::is_first_build());
-            if is_first_build {
-                1
-            } else {
-                get(build_counter) + 1
-            }
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        fn unread(_: CapsuleHandle) -> u8 {
+            123
         }
 
         let container = Container::new();
-        assert_eq!(container.read(build_counter), 1);
-        container.read(rebuildable)();
-        assert_eq!(container.read(build_counter), 2);
-        container.read(rebuildable)();
-        assert_eq!(container.read(build_counter), 3);
+        container.read(count_plus_one);
+
+        let mut values = HashMap::new();
+        container.for_each_value(|id, data| {
+            values.insert(id.clone(), *data.downcast_ref::<u8>().unwrap());
+        });
+        assert_eq!(
+            values,
+            HashMap::from([(count.id(), 0), (count_plus_one.id(), 1)])
+        );
+        assert!(!values.contains_key(&unread.id()));
     }
 
     #[test]
@@ -704,7 +3264,7 @@ mod tests {
     fn listener_side_effects_update() {
         use std::sync::{Arc, Mutex};
 
-        fn rebuildable(CapsuleHandle { register, .. }: CapsuleHandle) -> (impl CData + Fn()) {
+        fn rebuildable(CapsuleHandle { register, .. }: CapsuleHandle) -> impl CData + Fn() {
             register.register(effects::rebuilder())
         }
 
@@ -728,6 +3288,340 @@ mod tests {
         drop(handle);
     }
 
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "observed stale data")]
+    fn read_during_side_effect_txn_panics() {
+        fn stateful(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, impl CData + Fn(u8), SideEffectTxnRunner) {
+            let (state, rebuild, run_txn) = register.raw(0);
+            let set_state = move |new_state| rebuild(Box::new(move |state| *state = new_state));
+            (*state, set_state, run_txn)
+        }
+
+        let container = Container::new();
+        let (_, set_state, run_txn) = container.read(stateful);
+        run_txn(Box::new(|| {
+            set_state(1);
+            _ = container.read(stateful);
+        }));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "observed stale data")]
+    fn read_dependent_during_side_effect_txn_panics() {
+        fn stateful(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, impl CData + Fn(u8), SideEffectTxnRunner) {
+            let (state, rebuild, run_txn) = register.raw(0);
+            let set_state = move |new_state| rebuild(Box::new(move |state| *state = new_state));
+            (*state, set_state, run_txn)
+        }
+
+        fn dependent(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
+
+        let container = Container::new();
+        let (_, set_state, run_txn) = container.read(stateful);
+        assert_eq!(container.read(dependent), 1);
+        run_txn(Box::new(|| {
+            set_state(1);
+            _ = container.read(dependent);
+        }));
+    }
+
+    #[test]
+    fn read_instrumented_reports_read_path() {
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+        fn count_plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count) + 1
+        }
+
+        let container = Container::new();
+        assert_eq!(
+            container.read_instrumented(count_plus_one),
+            (1, ReadPath::InitializedSome(2))
+        );
+        assert_eq!(
+            container.read_instrumented((count, count_plus_one)),
+            ((0, 1), ReadPath::AllCached)
+        );
+    }
+
+    #[test]
+    fn warm_up_initializes_non_clone_capsules() {
+        struct NotClone(u8);
+        fn not_clone(_: CapsuleHandle) -> NotClone {
+            NotClone(1)
+        }
+        fn count(_: CapsuleHandle) -> u8 {
+            0
+        }
+
+        let container = Container::new();
+        container.warm_up((not_clone, count));
+        assert_eq!(container.read_instrumented(count), (0, ReadPath::AllCached));
+        assert_eq!(container.read_ref(not_clone, |data| data.0), 1);
+    }
+
+    #[test]
+    fn read_iter_does_not_hold_lock() {
+        fn numbers_capsule(_: CapsuleHandle) -> LazyIter<u8> {
+            LazyIter::new(|| 0..3)
+        }
+        fn count_capsule(_: CapsuleHandle) -> u8 {
+            3
+        }
+
+        let container = Container::new();
+        let numbers = container
+            .read_iter(numbers_capsule)
+            // Initializing another capsule mid-iteration requires the write lock
+            .map(|n| n + container.read(count_capsule))
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, [3, 4, 5]);
+
+        let numbers = container.read(numbers_capsule);
+        assert_eq!((&numbers).into_iter().sum::<u8>(), 3);
+        assert_eq!(numbers.iter().count(), 3);
+    }
+
+    #[test]
+    fn preview_does_not_affect_container() {
+        fn rate(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn preview_rate(_: CapsuleHandle) -> u8 {
+            2
+        }
+        fn total(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(rate) * 10
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(total), 10);
+        let node_count = container.stats().node_count;
+
+        let overrides = [CapsuleOverride::new(&rate, preview_rate)];
+        assert_eq!(container.preview(overrides, total), 20);
+        assert_eq!(container.preview([], total), 10);
+
+        assert_eq!(container.read(total), 10);
+        assert_eq!(container.stats().node_count, node_count);
+    }
+
+    #[test]
+    fn preview_reflects_current_state() {
+        fn count(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+        fn rate(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn preview_rate(_: CapsuleHandle) -> u8 {
+            2
+        }
+        fn total(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(count).0 * get.as_ref(rate)
+        }
+
+        let container = Container::new();
+        container.read(count).1(5);
+        assert_eq!(container.read(total), 5);
+
+        let overrides = [CapsuleOverride::new(&rate, preview_rate)];
+        assert_eq!(container.preview(overrides, total), 10);
+        assert_eq!(container.read(total), 5);
+
+        // The mutated state stays intact in the original container
+        container.read(count).1(6);
+        assert_eq!(container.read(total), 6);
+    }
+
+    #[test]
+    fn preview_scope_gc_and_dispose() {
+        fn rate(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn preview_rate(_: CapsuleHandle) -> u8 {
+            2
+        }
+        fn total(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(rate) * 10
+        }
+        fn unaffected(_: CapsuleHandle) -> u8 {
+            3
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read((total, unaffected)), (10, 3));
+
+        let overrides = [CapsuleOverride::new(&rate, preview_rate)];
+        let preview = container.preview_scope(overrides);
+        assert_eq!(preview.read(total), 20);
+
+        // Placeholders (like unaffected) are kept, while the idempotent total is collected
+        assert_eq!(preview.collect_garbage(), 2);
+        assert_eq!(preview.read(unaffected), 3);
+        assert!(preview.dispose(&unaffected));
+        assert_eq!(preview.read(unaffected), 3);
+
+        assert_eq!(container.read((total, unaffected)), (10, 3));
+    }
+
+    #[test]
+    fn ptr_eq() {
+        let container = Container::new();
+        assert!(container.ptr_eq(&container.clone()));
+        assert!(!container.ptr_eq(&Container::new()));
+    }
+
+    #[test]
+    fn array_side_effects_have_independent_state() {
+        fn counters_capsule(
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> [(u8, impl CData + Fn(u8)); 3] {
+            register.register(std::array::from_fn(|_| effects::cloned_state(0)))
+        }
+
+        let container = Container::new();
+        let [(_, set_first), _, (_, set_third)] = container.read(counters_capsule);
+        set_first(1);
+        set_third(3);
+        assert_eq!(
+            container.read(counters_capsule).map(|(count, _)| count),
+            [1, 0, 3]
+        );
+
+        container.read(counters_capsule)[1].1(2);
+        assert_eq!(
+            container.read(counters_capsule).map(|(count, _)| count),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn listen_simple_gets_updates() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = container.listen_simple(move |mut get| {
+            tx.send(get.as_ref(stateful).0).unwrap();
+        });
+        assert_eq!(rx.try_recv(), Ok(0));
+
+        container.read(stateful).1(1);
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        drop(handle);
+        container.read(stateful).1(2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "You cannot pass the same listener into Container::listen()")]
+    fn listen_same_listener_twice_panics() {
+        fn listener(_: CapsuleReader) {}
+
+        let container = Container::new();
+        let _handle = container.listen_simple(listener);
+        _ = container.listen_simple(listener);
+    }
+
+    #[test]
+    fn listen_distinct_skips_equal_data() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+        fn parity(CapsuleHandle { mut get, .. }: CapsuleHandle) -> bool {
+            get.as_ref(stateful).0 % 2 == 0
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = container.listen_distinct(&parity, move |is_even| {
+            tx.send(is_even).unwrap();
+        });
+        assert_eq!(rx.try_recv(), Ok(true));
+
+        container.read(stateful).1(2);
+        assert!(rx.try_recv().is_err());
+
+        container.read(stateful).1(3);
+        assert_eq!(rx.try_recv(), Ok(false));
+
+        drop(handle);
+        container.read(stateful).1(4);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn listen_distinct_with_the_same_listener_type_registers_both() {
+        fn a(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn b(_: CapsuleHandle) -> u8 {
+            2
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let listener = move |tx: std::sync::mpsc::Sender<u8>| move |data| tx.send(data).unwrap();
+        let _a_handle = container.listen_distinct(&a, listener(tx.clone()));
+        let _b_handle = container.listen_distinct(&b, listener(tx));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn listen_to_gets_updates() {
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        fn plus_one(CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+            get.as_ref(stateful).0 + 1
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = container.listen_to((stateful, plus_one), move |((count, _), plus_one)| {
+            tx.send((count, plus_one)).unwrap();
+        });
+        assert_eq!(rx.try_recv(), Ok((0, 1)));
+
+        container.read(stateful).1(1);
+        assert_eq!(rx.try_recv(), Ok((1, 2)));
+
+        drop(handle);
+        container.read(stateful).1(2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn listen_to_with_the_same_listener_type_registers_both() {
+        fn a(_: CapsuleHandle) -> u8 {
+            1
+        }
+        fn b(_: CapsuleHandle) -> u8 {
+            2
+        }
+
+        let container = Container::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let listener = move |tx: std::sync::mpsc::Sender<u8>| move |data| tx.send(data).unwrap();
+        let _a_handle = container.listen_to(a, listener(tx.clone()));
+        let _b_handle = container.listen_to(b, listener(tx));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
     #[test]
     fn listener_with_multiple_effects() {
         let container = Container::new();
@@ -867,6 +3761,54 @@ mod tests {
         assert_eq!(get_build_count(ChangingWatcher), 4);
     }
 
+    #[test]
+    fn always_rebuild_ignores_eq_of_dependencies() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        static OBSERVER_BUILDS: AtomicU8 = AtomicU8::new(0);
+
+        fn stateful(CapsuleHandle { register, .. }: CapsuleHandle) -> (u8, impl CData + Fn(u8)) {
+            register.register(effects::cloned_state(0))
+        }
+
+        struct UnchangingDep;
+        impl Capsule for UnchangingDep {
+            type Data = u8;
+            fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> Self::Data {
+                _ = get.as_ref(stateful);
+                0
+            }
+            fn eq(old: &Self::Data, new: &Self::Data) -> bool {
+                old == new
+            }
+        }
+
+        struct Observer;
+        impl Capsule for Observer {
+            type Data = ();
+            fn build(&self, CapsuleHandle { mut get, register }: CapsuleHandle) -> Self::Data {
+                register.register(effects::as_listener());
+                OBSERVER_BUILDS.fetch_add(1, Ordering::SeqCst);
+                _ = get.as_ref(UnchangingDep);
+            }
+            fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+                false
+            }
+            fn always_rebuild() -> bool {
+                true
+            }
+        }
+
+        let container = Container::new();
+        container.read(Observer);
+        assert_eq!(OBSERVER_BUILDS.load(Ordering::SeqCst), 1);
+
+        container.read(stateful).1(1);
+        container.read(stateful).1(2);
+        assert_eq!(container.read(UnchangingDep), 0);
+        assert_eq!(OBSERVER_BUILDS.load(Ordering::SeqCst), 3);
+    }
+
     #[test]
     fn fib_dynamic_capsules() {
         struct FibCapsule(u8);
@@ -895,6 +3837,72 @@ mod tests {
         assert_eq!(container.read(FibCapsule(100)), 354_224_848_179_261_915_075);
     }
 
+    #[test]
+    fn derived_capsules() {
+        #[derive(Capsule)]
+        #[capsule(data = u8, eq)]
+        struct Sum {
+            #[capsule_key]
+            a: u8,
+            #[capsule_key]
+            b: u8,
+        }
+        impl Sum {
+            fn build(&self, _: CapsuleHandle) -> u8 {
+                self.a + self.b
+            }
+        }
+
+        #[derive(Capsule)]
+        #[capsule(data = u8)]
+        struct Double(#[capsule_key] u8);
+        impl Double {
+            fn build(&self, CapsuleHandle { mut get, .. }: CapsuleHandle) -> u8 {
+                get.as_ref(Sum { a: self.0, b: 0 }) * 2
+            }
+        }
+
+        let container = Container::new();
+        assert_eq!(
+            container.read((Sum { a: 1, b: 2 }, Sum { a: 2, b: 1 })),
+            (3, 3)
+        );
+        assert_eq!(container.read((Double(1), Double(2))), (2, 4));
+    }
+
+    #[test]
+    fn attribute_capsules() {
+        #[derive(Clone)]
+        struct Pair(u8, u8);
+
+        #[capsule]
+        fn pair_capsule() -> Pair {
+            Pair(1, 2)
+        }
+
+        #[capsule(eq)]
+        fn sum_capsule(Pair(a, b): pair_capsule, get: &mut CapsuleReader) -> u8 {
+            a + b + get.as_ref(pair_capsule).0
+        }
+
+        #[capsule]
+        fn sum_with_state_capsule(
+            sum: sum_capsule,
+            CapsuleHandle { register, .. }: CapsuleHandle,
+        ) -> (u8, Arc<dyn Fn(u8) + Send + Sync>) {
+            let (state, set_state) = register.register(effects::cloned_state(0));
+            (sum + state, Arc::new(set_state))
+        }
+
+        let container = Container::new();
+        assert_eq!(container.read(sum_capsule), 4);
+
+        let (sum, set_state) = container.read(sum_with_state_capsule);
+        assert_eq!(sum, 4);
+        set_state(1);
+        assert_eq!(container.read(sum_with_state_capsule).0, 5);
+    }
+
     #[test]
     fn dynamic_capsules_remain_isolated() {
         struct A(u8);
@@ -1061,6 +4069,7 @@ mod tests {
     mod side_effect_txns {
         use super::*;
 
+        #[allow(clippy::type_complexity)]
         fn two_side_effects_capsule(
             CapsuleHandle { register, .. }: CapsuleHandle,
         ) -> ((u8, impl CData + Fn(u8)), (u8, impl CData + Fn(u8))) {
@@ -1198,5 +4207,41 @@ mod tests {
             assert_eq!(s2, 123);
             assert_eq!(s3, 111);
         }
+
+        #[test]
+        fn current_txn_depth() {
+            let container = Container::new();
+            let run_txn = container.read(txn_runner_capsule);
+            let depths = std::sync::Mutex::new(Vec::new());
+            let record_depth = || {
+                depths
+                    .lock()
+                    .expect("Not poisoned")
+                    .push(container.current_txn_depth());
+            };
+
+            record_depth();
+            run_txn(Box::new(|| {
+                record_depth();
+                run_txn(Box::new(&record_depth));
+                record_depth();
+            }));
+            record_depth();
+
+            assert_eq!(*depths.lock().expect("Not poisoned"), vec![0, 1, 2, 1, 0]);
+        }
+
+        #[test]
+        fn current_txn_depth_after_panicking_txn() {
+            let container = Container::new();
+            let run_txn = container.read(txn_runner_capsule);
+
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                run_txn(Box::new(|| panic!("txn panicked")));
+            }));
+
+            assert!(result.is_err());
+            assert_eq!(container.current_txn_depth(), 0);
+        }
     }
 }
@@ -0,0 +1,657 @@
+use std::{
+    any::Any,
+    cell::{Cell, OnceCell, RefCell},
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    rc::Rc,
+};
+
+use crate::{CapsuleId, CapsuleIdMap, CapsuleIdSet, CapsuleKey, Error};
+
+/// Like [`Capsule`](crate::Capsule), but for a [`LocalContainer`],
+/// so its [`LocalCapsule::Data`] does not need to be `Send + Sync`.
+pub trait LocalCapsule: 'static {
+    /// The type of data associated with this capsule, which only needs to be `'static`
+    /// (so `Rc`-based data, like UI state, is allowed).
+    type Data: 'static;
+
+    /// Builds the capsule's immutable data using the given [`LocalCapsuleHandle`].
+    ///
+    /// Side effect state mutations requested during a build are deferred
+    /// until after the build (and any rebuilds in progress) finish.
+    fn build(&self, handle: LocalCapsuleHandle) -> Self::Data;
+
+    /// Returns whether or not a capsule's old data and new data are equivalent
+    /// (and thus whether or not we can skip rebuilding dependents as an optimization).
+    fn eq(old: &Self::Data, new: &Self::Data) -> bool;
+
+    /// Returns the key to use for this capsule; see [`Capsule::key`](crate::Capsule::key).
+    fn key(&self) -> impl CapsuleKey {
+        // NOTE: this default impl implicitly returns `()` (for static capsules)
+    }
+}
+impl<T, F> LocalCapsule for F
+where
+    T: 'static,
+    F: Fn(LocalCapsuleHandle) -> T + 'static,
+{
+    type Data = T;
+
+    fn build(&self, handle: LocalCapsuleHandle) -> Self::Data {
+        self(handle)
+    }
+
+    fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+        false
+    }
+}
+
+/// The handle given to [`LocalCapsule`]s in order to [`LocalCapsule::build`] their data.
+/// See [`LocalCapsuleReader`] and [`LocalSideEffectRegistrar`] for more.
+pub struct LocalCapsuleHandle<'a> {
+    pub get: LocalCapsuleReader<'a>,
+    pub register: LocalSideEffectRegistrar<'a>,
+}
+
+/// Allows [`LocalCapsule`]s to read the data of other [`LocalCapsule`]s.
+pub struct LocalCapsuleReader<'a> {
+    store: &'a Rc<LocalContainerStore>,
+    id: &'a CapsuleId,
+    // NOTE: we keep the read data alive here so that we can hand out references to it
+    dependency_data: Vec<Rc<dyn Any>>,
+}
+
+impl LocalCapsuleReader<'_> {
+    /// Reads the data of the supplied capsule, initializing it if needed,
+    /// and makes the capsule being built depend upon it.
+    ///
+    /// # Panics
+    /// Panics when a capsule reads itself (directly or via a cycle) on its first build.
+    pub fn as_ref<C: LocalCapsule>(&mut self, capsule: C) -> &C::Data {
+        let id = self.store.ensure_initialized(capsule);
+        let mut nodes = self.store.nodes.borrow_mut();
+        if id != *self.id {
+            node_mut(&mut nodes, self.id)
+                .dependencies
+                .insert(id.clone());
+            node_mut(&mut nodes, &id).dependents.insert(self.id.clone());
+        }
+        let data = Rc::clone(node_data(&nodes, &id));
+        drop(nodes);
+
+        self.dependency_data.push(data);
+        let data = self
+            .dependency_data
+            .last()
+            .expect("Data should've been pushed above");
+        downcast_capsule_data::<C>(data)
+    }
+}
+
+/// Like [`SideEffect`](crate::SideEffect), but for [`LocalCapsule`]s.
+pub trait LocalSideEffect {
+    /// The type exposed in the capsule build function when this side effect is registered.
+    type Api<'registrar>;
+
+    /// Construct this side effect's `Api` via the given [`LocalSideEffectRegistrar`].
+    fn build(self, registrar: LocalSideEffectRegistrar<'_>) -> Self::Api<'_>;
+}
+impl<T, F: FnOnce(LocalSideEffectRegistrar) -> T> LocalSideEffect for F {
+    type Api<'registrar> = T;
+    fn build(self, registrar: LocalSideEffectRegistrar<'_>) -> Self::Api<'_> {
+        self(registrar)
+    }
+}
+
+/// Registers the given [`LocalSideEffect`] and returns its build api.
+/// Like [`SideEffectRegistrar`](crate::SideEffectRegistrar),
+/// you can only call register once on purpose (it consumes self).
+pub struct LocalSideEffectRegistrar<'a> {
+    side_effect: &'a mut OnceCell<SideEffectState>,
+    mutation_runner: Rc<dyn Fn(SideEffectMutation)>,
+    capsule_type_name: &'static str,
+}
+
+impl<'a> LocalSideEffectRegistrar<'a> {
+    /// Registers the given side effect.
+    pub fn register<S: LocalSideEffect>(self, effect: S) -> S::Api<'a> {
+        effect.build(self)
+    }
+
+    /// The basic building block for all local side effects,
+    /// returning the side effect's state and a function to mutate it (and rebuild the capsule).
+    ///
+    /// # Panics
+    /// Panics when the supplied type `T` changes between builds.
+    /// Ensure T remains the same across builds (e.g., by calling this function unconditionally).
+    #[allow(
+        clippy::type_complexity,
+        reason = "Mirrors the return type of SideEffectRegistrar::raw"
+    )]
+    pub fn raw<T: 'static>(
+        self,
+        initial: T,
+    ) -> (&'a mut T, impl Clone + Fn(Box<dyn FnOnce(&mut T)>)) {
+        let capsule_type_name = self.capsule_type_name;
+        self.side_effect
+            .get_or_init(|| (std::any::type_name::<T>(), Box::new(initial)));
+        let (expected_type_name, state) = self
+            .side_effect
+            .get_mut()
+            .expect("Side effect should've been initialized in get_or_init above");
+        let state = state.downcast_mut::<T>().unwrap_or_else(|| {
+            Error::SideEffectTypeChanged {
+                capsule_type_name,
+                expected_type_name,
+                actual_type_name: std::any::type_name::<T>(),
+            }
+            .panic()
+        });
+
+        let mutation_runner = self.mutation_runner;
+        let rebuild = move |mutation: Box<dyn FnOnce(&mut T)>| {
+            mutation_runner(Box::new(|state| {
+                // NOTE: a changed state type panics on the next build instead
+                if let Some(state) = state.downcast_mut::<T>() {
+                    mutation(state);
+                }
+            }));
+        };
+
+        (state, rebuild)
+    }
+}
+
+/// A single-threaded container for [`LocalCapsule`]s, whose data need not be `Send + Sync`
+/// (such as `Rc`-based UI state that must stay on the main thread).
+///
+/// Unlike [`Container`](crate::Container), this uses `Rc`/`RefCell` internally instead of locks,
+/// and mutating side effect state rebuilds the capsule (and its dependents) immediately.
+///
+/// This is a much smaller engine than [`Container`](crate::Container), so it lacks:
+/// - disposal and idempotent garbage collection (capsules live until the container is dropped)
+/// - listeners (read capsules again after mutating their side effect state instead)
+/// - side effect transactions (mutations are only batched while a build is in progress,
+///   after which they are applied, and their capsules rebuilt, one at a time in order)
+///
+/// When a build panics, the panic propagates to whoever triggered it,
+/// and any side effect mutations still pending at that point are discarded.
+/// The capsule that panicked keeps its previous data (or, on its first build,
+/// is removed so that it can be built again), and the rest of the container keeps working.
+#[derive(Clone, Default)]
+pub struct LocalContainer(Rc<LocalContainerStore>);
+
+impl LocalContainer {
+    /// Initializes a new `LocalContainer`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the current data of the supplied capsule, initializing it if needed.
+    pub fn read<C: LocalCapsule>(&self, capsule: C) -> C::Data
+    where
+        C::Data: Clone,
+    {
+        self.read_ref(capsule, Clone::clone)
+    }
+
+    /// Reads the current data of the supplied capsule (by reference), initializing it if needed.
+    ///
+    /// The callback is invoked without borrowing the container,
+    /// so it may read other capsules or mutate side effect state.
+    pub fn read_ref<C: LocalCapsule, R>(
+        &self,
+        capsule: C,
+        callback: impl FnOnce(&C::Data) -> R,
+    ) -> R {
+        let id = self.0.run(|| self.0.ensure_initialized(capsule));
+        let data = Rc::clone(node_data(&self.0.nodes.borrow(), &id));
+        callback(downcast_capsule_data::<C>(&data))
+    }
+}
+
+type SideEffectMutation = Box<dyn FnOnce(&mut dyn Any)>;
+
+/// A capsule's side effect state, alongside the state's type name
+/// (for when the state's type changes between builds).
+type SideEffectState = (&'static str, Box<dyn Any>);
+
+#[derive(Default)]
+struct LocalContainerStore {
+    nodes: RefCell<CapsuleIdMap<LocalCapsuleManager>>,
+    is_busy: Cell<bool>,
+    pending_mutations: RefCell<VecDeque<(CapsuleId, SideEffectMutation)>>,
+}
+
+impl LocalContainerStore {
+    /// Runs `f` and then any side effect mutations requested in the meantime,
+    /// unless a call to `run` further up the stack will already handle those mutations.
+    fn run<R>(self: &Rc<Self>, f: impl FnOnce() -> R) -> R {
+        if self.is_busy.replace(true) {
+            return f();
+        }
+
+        // NOTE: resets is_busy on drop so that a panicking build doesn't wedge the container
+        let _busy_guard = BusyGuard(self);
+        let result = f();
+        loop {
+            let Some((id, mutation)) = self.pending_mutations.borrow_mut().pop_front() else {
+                break;
+            };
+            self.mutate(&id, mutation);
+        }
+        result
+    }
+
+    fn mutation_runner(self: &Rc<Self>, id: &CapsuleId) -> Rc<dyn Fn(SideEffectMutation)> {
+        let store = Rc::downgrade(self);
+        let id = id.clone();
+        Rc::new(move |mutation| {
+            let Some(store) = store.upgrade() else {
+                #[cfg(feature = "logging")]
+                log::warn!("Attempted to mutate side effect state after LocalContainer disposal");
+                return;
+            };
+            store
+                .pending_mutations
+                .borrow_mut()
+                .push_back((id.clone(), mutation));
+            store.run(|| {});
+        })
+    }
+
+    fn mutate(self: &Rc<Self>, id: &CapsuleId, mutation: SideEffectMutation) {
+        let mut side_effect = node_mut(&mut self.nodes.borrow_mut(), id)
+            .side_effect
+            .take()
+            .unwrap_or_else(|| Error::NodeInUse.panic());
+        if let Some((_, state)) = side_effect.get_mut() {
+            mutation(state.as_mut());
+        }
+        node_mut(&mut self.nodes.borrow_mut(), id).side_effect = Some(side_effect);
+
+        // Rebuild the mutated capsule, and then any dependents whose dependencies changed
+        let mut changed = CapsuleIdSet::default();
+        for node_id in self.topological_order(id) {
+            let is_stale = node_id == *id
+                || node(&self.nodes.borrow(), &node_id)
+                    .dependencies
+                    .iter()
+                    .any(|dependency| changed.contains(dependency));
+            if is_stale && self.build(&node_id) {
+                changed.insert(node_id);
+            }
+        }
+    }
+
+    /// Returns the given capsule and its transitive dependents, in a topological order.
+    fn topological_order(&self, id: &CapsuleId) -> Vec<CapsuleId> {
+        fn visit(
+            nodes: &CapsuleIdMap<LocalCapsuleManager>,
+            id: &CapsuleId,
+            visited: &mut CapsuleIdSet,
+            order: &mut Vec<CapsuleId>,
+        ) {
+            if !visited.insert(id.clone()) {
+                return;
+            }
+            for dependent in &node(nodes, id).dependents {
+                visit(nodes, dependent, visited, order);
+            }
+            order.push(id.clone());
+        }
+
+        let mut order = Vec::new();
+        visit(
+            &self.nodes.borrow(),
+            id,
+            &mut CapsuleIdSet::default(),
+            &mut order,
+        );
+        order.reverse();
+        order
+    }
+
+    /// Builds the given capsule if it is not yet initialized, and returns its id.
+    fn ensure_initialized<C: LocalCapsule>(self: &Rc<Self>, capsule: C) -> CapsuleId {
+        let id = CapsuleId::new::<C>(capsule.key());
+        if let Some(node) = self.nodes.borrow().get(&id) {
+            if node.data.is_none() {
+                Error::SelfReadOnFirstBuild {
+                    capsule_type_name: std::any::type_name::<C>(),
+                    id,
+                }
+                .panic();
+            }
+            return id;
+        }
+
+        self.nodes.borrow_mut().insert(
+            id.clone(),
+            LocalCapsuleManager {
+                capsule: Rc::new(capsule),
+                data: None,
+                side_effect: Some(OnceCell::new()),
+                dependencies: CapsuleIdSet::default(),
+                dependents: CapsuleIdSet::default(),
+            },
+        );
+        self.build(&id);
+        id
+    }
+
+    /// (Re)builds the given capsule, returning whether its data changed.
+    fn build(self: &Rc<Self>, id: &CapsuleId) -> bool {
+        let (capsule, mut side_effect) = {
+            let mut nodes = self.nodes.borrow_mut();
+            let node = node_mut(&mut nodes, id);
+            let side_effect = node
+                .side_effect
+                .take()
+                .unwrap_or_else(|| Error::NodeInUse.panic());
+            let capsule = Rc::clone(&node.capsule);
+            for dependency in std::mem::take(&mut node.dependencies) {
+                if let Some(dependency) = nodes.get_mut(&dependency) {
+                    dependency.dependents.remove(id);
+                }
+            }
+            (capsule, side_effect)
+        };
+
+        let new_data = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            capsule.build(LocalCapsuleHandle {
+                get: LocalCapsuleReader {
+                    store: self,
+                    id,
+                    dependency_data: Vec::new(),
+                },
+                register: LocalSideEffectRegistrar {
+                    side_effect: &mut side_effect,
+                    mutation_runner: self.mutation_runner(id),
+                    capsule_type_name: capsule.type_name(),
+                },
+            })
+        }));
+
+        let mut nodes = self.nodes.borrow_mut();
+        let node = node_mut(&mut nodes, id);
+        node.side_effect = Some(side_effect);
+        let new_data = match new_data {
+            Ok(new_data) => new_data,
+            Err(panic) => {
+                if node.data.is_none() {
+                    // The first build failed, so forget the capsule to allow building it again
+                    let node = nodes.remove(id).expect("Node should be in the graph");
+                    for dependency in node.dependencies {
+                        if let Some(dependency) = nodes.get_mut(&dependency) {
+                            dependency.dependents.remove(id);
+                        }
+                    }
+                }
+                drop(nodes);
+                std::panic::resume_unwind(panic)
+            }
+        };
+        let node = node_mut(&mut nodes, id);
+        let did_change = node
+            .data
+            .as_ref()
+            .is_none_or(|old_data| !capsule.data_eq(&**old_data, &*new_data));
+        node.data = Some(new_data);
+        did_change
+    }
+}
+
+/// Marks a [`LocalContainerStore`] as no longer busy once dropped,
+/// discarding any side effect mutations that were left pending by a panic.
+struct BusyGuard<'a>(&'a LocalContainerStore);
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        // NOTE: the mutations are dropped after releasing the borrow, in case they hold
+        // side effect state whose drop requests yet another mutation
+        let pending_mutations = std::mem::take(&mut *self.0.pending_mutations.borrow_mut());
+        drop(pending_mutations);
+        self.0.is_busy.set(false);
+    }
+}
+
+struct LocalCapsuleManager {
+    capsule: Rc<dyn DynLocalCapsule>,
+    data: Option<Rc<dyn Any>>,
+    side_effect: Option<OnceCell<SideEffectState>>,
+    dependencies: CapsuleIdSet,
+    dependents: CapsuleIdSet,
+}
+
+/// The type-erased [`LocalCapsule`] behavior that a [`LocalCapsuleManager`] needs.
+trait DynLocalCapsule {
+    fn build(&self, handle: LocalCapsuleHandle) -> Rc<dyn Any>;
+    fn data_eq(&self, old: &dyn Any, new: &dyn Any) -> bool;
+    fn type_name(&self) -> &'static str;
+}
+impl<C: LocalCapsule> DynLocalCapsule for C {
+    fn build(&self, handle: LocalCapsuleHandle) -> Rc<dyn Any> {
+        Rc::new(LocalCapsule::build(self, handle))
+    }
+
+    fn data_eq(&self, old: &dyn Any, new: &dyn Any) -> bool {
+        match (old.downcast_ref(), new.downcast_ref()) {
+            (Some(old), Some(new)) => C::eq(old, new),
+            _ => false,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<C>()
+    }
+}
+
+fn node<'a>(
+    nodes: &'a CapsuleIdMap<LocalCapsuleManager>,
+    id: &CapsuleId,
+) -> &'a LocalCapsuleManager {
+    nodes
+        .get(id)
+        .unwrap_or_else(|| Error::NodeNotInGraph { id: id.clone() }.panic())
+}
+
+fn node_mut<'a>(
+    nodes: &'a mut CapsuleIdMap<LocalCapsuleManager>,
+    id: &CapsuleId,
+) -> &'a mut LocalCapsuleManager {
+    nodes
+        .get_mut(id)
+        .unwrap_or_else(|| Error::NodeNotInGraph { id: id.clone() }.panic())
+}
+
+fn node_data<'a>(nodes: &'a CapsuleIdMap<LocalCapsuleManager>, id: &CapsuleId) -> &'a Rc<dyn Any> {
+    node(nodes, id)
+        .data
+        .as_ref()
+        .expect("Capsule should've been initialized")
+}
+
+fn downcast_capsule_data<C: LocalCapsule>(data: &Rc<dyn Any>) -> &C::Data {
+    data.downcast_ref::<C::Data>()
+        .expect("Types should be properly enforced due to generics")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{LocalCapsule, LocalCapsuleHandle, LocalContainer, LocalSideEffectRegistrar};
+
+    fn state<T: Clone + 'static>(
+        initial: T,
+    ) -> impl FnOnce(LocalSideEffectRegistrar) -> (T, Rc<dyn Fn(T)>) {
+        move |register: LocalSideEffectRegistrar| {
+            let (state, rebuild) = register.raw(initial);
+            let set_state = move |new_state| rebuild(Box::new(|state| *state = new_state));
+            (state.clone(), Rc::new(set_state))
+        }
+    }
+
+    fn count_capsule(
+        LocalCapsuleHandle { register, .. }: LocalCapsuleHandle,
+    ) -> (u8, Rc<dyn Fn(u8)>) {
+        register.register(state(0))
+    }
+
+    fn log_capsule(_: LocalCapsuleHandle) -> Rc<RefCell<Vec<u8>>> {
+        Rc::default()
+    }
+
+    fn logged_count_capsule(LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> Rc<u8> {
+        let count = get.as_ref(count_capsule).0;
+        get.as_ref(log_capsule).borrow_mut().push(count);
+        Rc::new(count)
+    }
+
+    #[test]
+    fn local_capsules_hold_non_send_data_and_rebuild_dependents() {
+        let container = LocalContainer::new();
+        assert_eq!(*container.read(logged_count_capsule), 0);
+
+        let (_, set_count) = container.read(count_capsule);
+        set_count(1);
+        set_count(2);
+        assert_eq!(*container.read(logged_count_capsule), 2);
+        assert_eq!(*container.read(log_capsule).borrow(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn local_capsules_skip_rebuilding_dependents_of_equal_data() {
+        struct ParityCapsule;
+        impl LocalCapsule for ParityCapsule {
+            type Data = bool;
+
+            fn build(&self, LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> bool {
+                get.as_ref(count_capsule).0 % 2 == 0
+            }
+
+            fn eq(old: &bool, new: &bool) -> bool {
+                old == new
+            }
+        }
+
+        fn parity_builds_capsule(
+            LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle,
+        ) -> Rc<RefCell<u8>> {
+            let builds = Rc::clone(get.as_ref(|_: LocalCapsuleHandle| Rc::new(RefCell::new(0))));
+            get.as_ref(ParityCapsule);
+            *builds.borrow_mut() += 1;
+            builds
+        }
+
+        let container = LocalContainer::new();
+        let builds = container.read(parity_builds_capsule);
+        let (_, set_count) = container.read(count_capsule);
+
+        set_count(2);
+        assert_eq!(*builds.borrow(), 1);
+        set_count(3);
+        assert_eq!(*builds.borrow(), 2);
+    }
+
+    #[test]
+    fn local_side_effect_mutations_during_builds_are_deferred() {
+        fn eager_capsule(LocalCapsuleHandle { register, .. }: LocalCapsuleHandle) -> u8 {
+            let (count, set_count) = register.register(state(0));
+            if count < 3 {
+                set_count(count + 1);
+            }
+            count
+        }
+
+        let container = LocalContainer::new();
+        assert_eq!(container.read(eager_capsule), 3);
+    }
+
+    #[test]
+    fn local_rebuilds_happen_in_topological_order() {
+        fn left_capsule(LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> u8 {
+            get.as_ref(log_capsule).borrow_mut().push(1);
+            get.as_ref(count_capsule).0
+        }
+
+        fn right_capsule(LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> u8 {
+            get.as_ref(log_capsule).borrow_mut().push(2);
+            get.as_ref(count_capsule).0
+        }
+
+        fn bottom_capsule(LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> u8 {
+            get.as_ref(log_capsule).borrow_mut().push(3);
+            let left = *get.as_ref(left_capsule);
+            left + get.as_ref(right_capsule)
+        }
+
+        let container = LocalContainer::new();
+        assert_eq!(container.read(bottom_capsule), 0);
+        container.read(log_capsule).borrow_mut().clear();
+
+        container.read(count_capsule).1(1);
+        assert_eq!(container.read(bottom_capsule), 2);
+        let mut log = container.read(log_capsule).borrow().clone();
+        assert_eq!(log.pop(), Some(3));
+        log.sort_unstable();
+        assert_eq!(log, vec![1, 2]);
+    }
+
+    #[test]
+    fn local_duplicate_side_effect_mutations_are_each_applied() {
+        fn incremented_capsule(LocalCapsuleHandle { register, .. }: LocalCapsuleHandle) -> u8 {
+            let (count, rebuild) = register.raw(0);
+            if *count == 0 {
+                rebuild(Box::new(|count| *count += 1));
+                rebuild(Box::new(|count| *count += 1));
+            }
+            *count
+        }
+
+        fn logged_incremented_capsule(
+            LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle,
+        ) -> u8 {
+            let count = *get.as_ref(incremented_capsule);
+            get.as_ref(log_capsule).borrow_mut().push(count);
+            count
+        }
+
+        let container = LocalContainer::new();
+        assert_eq!(container.read(logged_incremented_capsule), 2);
+        assert_eq!(*container.read(log_capsule).borrow(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn local_container_recovers_from_panicking_builds() {
+        fn odd_panicking_capsule(LocalCapsuleHandle { mut get, .. }: LocalCapsuleHandle) -> u8 {
+            let count = get.as_ref(count_capsule).0;
+            assert!(count % 2 == 0, "odd count");
+            count
+        }
+
+        let container = LocalContainer::new();
+        assert_eq!(container.read(odd_panicking_capsule), 0);
+
+        let set_count = container.read(count_capsule).1;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| set_count(1)));
+        assert!(result.is_err());
+        assert_eq!(container.read(count_capsule).0, 1);
+        assert_eq!(container.read(odd_panicking_capsule), 0);
+
+        set_count(2);
+        assert_eq!(container.read(odd_panicking_capsule), 2);
+
+        // A capsule whose first build panics can be built again later
+        let container = LocalContainer::new();
+        container.read(count_capsule).1(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            container.read(odd_panicking_capsule)
+        }));
+        assert!(result.is_err());
+        container.read(count_capsule).1(2);
+        assert_eq!(container.read(odd_panicking_capsule), 2);
+    }
+}
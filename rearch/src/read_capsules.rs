@@ -1,11 +1,36 @@
-use crate::{ArcContainerStore, Capsule, Container, CreateCapsuleId};
+use parking_lot::RwLockReadGuard;
+use std::{any::Any, ops::Deref, sync::Arc};
+
+use crate::{
+    ArcContainerStore, Capsule, CapsuleId, CapsuleIdMap, CapsuleReader, Container,
+    ContainerWriteTxn, CreateCapsuleId,
+};
+
+/// Describes how a [`Container::read_instrumented`] obtained its data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadPath {
+    /// All of the capsules were already initialized,
+    /// so the data was read under just a read lock (the fast path).
+    AllCached,
+    /// Some capsules were not yet initialized, so the read fell back to a write lock,
+    /// under which the contained number of capsules (including dependencies) were initialized.
+    ///
+    /// The number may be 0 when another thread initialized the capsules in the meantime.
+    InitializedSome(usize),
+}
 
 /// A list of capsules with cloneable data.
 /// This is either a singular capsule, like `foo_capsule`,
 /// or a tuple, like `(foo_capsule, bar_capsule)`.
-pub trait CapsulesWithCloneRead {
+///
+/// This trait is sealed (it is only implemented by `ReArch` itself).
+pub trait CapsulesWithCloneRead: sealed::Sealed {
     type Data;
     fn read(self, container: &Container) -> Self::Data;
+    #[doc(hidden)]
+    fn read_instrumented(self, container: &Container) -> (Self::Data, ReadPath);
+    #[doc(hidden)]
+    fn read_from(self, reader: &mut CapsuleReader) -> Self::Data;
 }
 macro_rules! generate_capsule_list_impl {
     ($($C:ident),+) => {
@@ -14,18 +39,31 @@ macro_rules! generate_capsule_list_impl {
             impl<$($C: Capsule),*> CapsulesWithCloneRead for ($($C),*) where $($C::Data: Clone),* {
                 type Data = ($($C::Data),*);
                 fn read(self, container: &Container) -> Self::Data {
+                    self.read_instrumented(container).0
+                }
+                fn read_instrumented(self, container: &Container) -> (Self::Data, ReadPath) {
                     let ($([<i $C>]),*) = self;
+                    #[cfg(debug_assertions)]
+                    container
+                        .0
+                        .debug_assert_unmodified_in_side_effect_txn(&[$([<i $C>].id()),*]);
                     let attempted_read_capsules = {
                         let txn = container.0.read_txn();
                         ($(txn.try_read(&[<i $C>])),*)
                     };
                     if let ($(Some([<i $C>])),*) = attempted_read_capsules {
-                        ($([<i $C>]),*)
+                        (($([<i $C>]),*), ReadPath::AllCached)
                     } else {
                         let mut txn = container.0.write_txn();
-                        ($(txn.read_or_init([<i $C>])),*)
+                        let node_count = txn.node_count();
+                        let data = ($(txn.read_or_init([<i $C>])),*);
+                        (data, ReadPath::InitializedSome(txn.node_count() - node_count))
                     }
                 }
+                fn read_from(self, reader: &mut CapsuleReader) -> Self::Data {
+                    let ($([<i $C>]),*) = self;
+                    ($(reader.snapshot([<i $C>])),*)
+                }
             }
         }
     };
@@ -42,8 +80,14 @@ generate_capsule_list_impl!(A, B, C, D, E, F, G, H);
 /// A list of capsules that can be read via a ref.
 /// This is either a singular capsule, like `foo_capsule`,
 /// or a tuple, like `(foo_capsule, bar_capsule)`.
-pub trait CapsulesWithRefRead {
-    type Data<'a>;
+///
+/// This trait is sealed (it is only implemented by `ReArch` itself).
+pub trait CapsulesWithRefRead: sealed::Sealed + Sized {
+    type Data<'a>
+    where
+        Self: 'a;
+    #[doc(hidden)]
+    type Ids;
     fn read<Callback, CallbackReturn>(
         self,
         container: &Container,
@@ -51,6 +95,12 @@ pub trait CapsulesWithRefRead {
     ) -> CallbackReturn
     where
         Callback: FnOnce(Self::Data<'_>) -> CallbackReturn;
+    #[doc(hidden)]
+    fn read_guard(self, container: &Container) -> RefReadGuard<'_, Self>;
+    #[doc(hidden)]
+    fn try_read_guard(self, container: &Container) -> Option<RefReadGuard<'_, Self>>;
+    #[doc(hidden)]
+    fn data_from_guard<'a>(guard: &'a RefReadGuard<'_, Self>) -> Self::Data<'a>;
 }
 macro_rules! generate_capsule_list_impl {
     ($($C:ident),+) => {
@@ -58,6 +108,7 @@ macro_rules! generate_capsule_list_impl {
             #[allow(non_snake_case, unused_parens)]
             impl<$($C: Capsule),*> CapsulesWithRefRead for ($($C),*) {
                 type Data<'a> = ($(&'a $C::Data),*);
+                type Ids = ($(<$C as sealed::CapsuleIdOf>::Id),*);
                 fn read<Callback, CallbackReturn>(
                     self,
                     container: &Container,
@@ -66,9 +117,22 @@ macro_rules! generate_capsule_list_impl {
                 where
                     Callback: FnOnce(Self::Data<'_>) -> CallbackReturn,
                 {
+                    callback(Self::data_from_guard(&self.read_guard(container)))
+                }
+
+                fn read_guard(self, container: &Container) -> RefReadGuard<'_, Self> {
                     let ($([<capsule $C>]),*) = self;
-                    let ($([<id $C>]),*) = ($([<capsule $C>].id()),*);
-                    let read_guard = Some(container.0.read_txn())
+                    let ids = ($([<capsule $C>].id()),*);
+                    #[cfg(debug_assertions)]
+                    {
+                        let ($([<id $C>]),*) = &ids;
+                        container
+                            .0
+                            .debug_assert_unmodified_in_side_effect_txn(
+                                &[$(CapsuleId::clone([<id $C>])),*],
+                            );
+                    }
+                    let data = Some(container.0.read_txn())
                         $(  .filter(|txn| txn.try_read_ref(&[<capsule $C>]).is_some())  )*
                         .unwrap_or_else(|| {
                             let mut txn = container.0.write_txn();
@@ -76,14 +140,32 @@ macro_rules! generate_capsule_list_impl {
                             txn.downgrade()
                         })
                         .data;
-                        callback((
-                            $(
-                                read_guard
-                                    .get(&[<id $C>])
-                                    .map(crate::downcast_capsule_data::<$C>)
-                                    .expect("Ensured initialization above")
-                            ),*
-                        ))
+                    RefReadGuard { data, ids }
+                }
+
+                fn try_read_guard(self, container: &Container) -> Option<RefReadGuard<'_, Self>> {
+                    let ($([<capsule $C>]),*) = self;
+                    let ids = ($([<capsule $C>].id()),*);
+                    let data = container.0.read_txn().data;
+                    let ($([<id $C>]),*) = &ids;
+                    if $(  data.contains_key([<id $C>])  )&&* {
+                        Some(RefReadGuard { data, ids })
+                    } else {
+                        None
+                    }
+                }
+
+                fn data_from_guard<'a>(guard: &'a RefReadGuard<'_, Self>) -> Self::Data<'a> {
+                    let ($([<id $C>]),*) = &guard.ids;
+                    (
+                        $(
+                            guard
+                                .data
+                                .get([<id $C>])
+                                .map(crate::downcast_capsule_data::<$C>)
+                                .expect("Ensured initialization above")
+                        ),*
+                    )
                 }
             }
         }
@@ -98,6 +180,112 @@ generate_capsule_list_impl!(A, B, C, D, E, F);
 generate_capsule_list_impl!(A, B, C, D, E, F, G);
 generate_capsule_list_impl!(A, B, C, D, E, F, G, H);
 
+/// Reads (clones) the data of the capsule with the supplied `id`, like [`Container::read`],
+/// invoking `ensure_initialized` under a write lock if the capsule is not yet initialized.
+///
+/// This is the read path of the handle types that already know their capsule's id
+/// ([`CapsuleRef`](crate::CapsuleRef) and [`CapsuleHandleRef`](crate::CapsuleHandleRef)).
+pub fn read_by_id<T: Clone + 'static>(
+    container: &Container,
+    id: &CapsuleId,
+    ensure_initialized: impl FnOnce(&mut ContainerWriteTxn),
+) -> T {
+    read_ref_by_id(container, id, ensure_initialized, T::clone)
+}
+
+/// Like [`read_by_id`], but invokes `callback` with a ref to the data (under a read lock),
+/// like [`Container::read_ref`].
+pub fn read_ref_by_id<T: 'static, R>(
+    container: &Container,
+    id: &CapsuleId,
+    ensure_initialized: impl FnOnce(&mut ContainerWriteTxn),
+    callback: impl FnOnce(&T) -> R,
+) -> R {
+    #[cfg(debug_assertions)]
+    container
+        .0
+        .debug_assert_unmodified_in_side_effect_txn(std::slice::from_ref(id));
+    let data = Some(container.0.read_txn())
+        .filter(|txn| txn.data.contains_key(id))
+        .unwrap_or_else(|| {
+            let mut txn = container.0.write_txn();
+            ensure_initialized(&mut txn);
+            txn.downgrade()
+        })
+        .data;
+    callback(
+        data.get(id)
+            .and_then(|data| data.downcast_ref::<T>())
+            .expect("Ensured initialization above and types are enforced by generics"),
+    )
+}
+
+mod sealed {
+    use crate::{Capsule, CapsuleId};
+
+    /// Prevents implementations of the capsule list traits outside of `ReArch`,
+    /// so that they can gain new (hidden) items without a breaking change.
+    pub trait Sealed {}
+    macro_rules! generate_sealed_impl {
+        ($($C:ident),+) => {
+            #[allow(unused_parens)]
+            impl<$($C: Capsule),*> Sealed for ($($C),*) {}
+        };
+    }
+    generate_sealed_impl!(A);
+    generate_sealed_impl!(A, B);
+    generate_sealed_impl!(A, B, C);
+    generate_sealed_impl!(A, B, C, D);
+    generate_sealed_impl!(A, B, C, D, E);
+    generate_sealed_impl!(A, B, C, D, E, F);
+    generate_sealed_impl!(A, B, C, D, E, F, G);
+    generate_sealed_impl!(A, B, C, D, E, F, G, H);
+
+    // Allows our macros to map each capsule type to a CapsuleId in type position
+    pub trait CapsuleIdOf {
+        type Id;
+    }
+    impl<C: Capsule> CapsuleIdOf for C {
+        type Id = CapsuleId;
+    }
+}
+
+/// A guard over a *consistent* (ref) read of some capsules, as created by [`Container::read_refs`].
+/// Use [`RefReadGuard::get`] to access the capsules' data.
+///
+/// Guards over a single capsule also implement [`Deref`] to that capsule's data.
+/// Guards over a tuple of capsules do not, because their data (such as `(&A, &B)`)
+/// is itself made of refs borrowed from the guard, and [`Deref::deref`] can only return
+/// a ref to data owned by the guard; use [`RefReadGuard::get`] for those instead.
+///
+/// # Concurrency
+/// The guard holds a read lock on the container for as long as it is alive,
+/// so be sure to drop it promptly (unless you don't mind blocking side effect updates
+/// and uninitialized reads).
+pub struct RefReadGuard<'a, Capsules: CapsulesWithRefRead> {
+    data: RwLockReadGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
+    ids: Capsules::Ids,
+}
+
+impl<Capsules: CapsulesWithRefRead> RefReadGuard<'_, Capsules> {
+    /// Returns refs to the data of the capsules supplied to [`Container::read_refs`].
+    #[must_use]
+    pub fn get(&self) -> Capsules::Data<'_> {
+        Capsules::data_from_guard(self)
+    }
+}
+
+impl<C: Capsule> Deref for RefReadGuard<'_, C> {
+    type Target = C::Data;
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+            .get(&self.ids)
+            .map(crate::downcast_capsule_data::<C>)
+            .expect("Ensured initialization when the guard was created")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{CapsuleHandle, Container};
@@ -127,6 +315,19 @@ mod tests {
         assert!(callback_called);
     }
 
+    #[test]
+    fn container_ref_read_guard() {
+        let container = Container::new();
+        let guard = container.read_refs((my_capsule, my_capsule));
+        let (data1, data2) = guard.get();
+        assert_eq!(data1, &123);
+        assert_eq!(data2, &123);
+        drop(guard);
+
+        assert_eq!(container.read_refs(my_capsule).get(), &123);
+        assert_eq!(*container.read_refs(my_capsule), 123);
+    }
+
     #[test]
     fn container_clone_read() {
         assert_eq!(Container::new().read(my_capsule), 123);
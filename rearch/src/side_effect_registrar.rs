@@ -1,22 +1,27 @@
-use std::{any::Any, cell::OnceCell};
+use std::{any::Any, cell::OnceCell, sync::Arc};
 
 use crate::{
-    CData, SideEffect, SideEffectStateMutationRunner, SideEffectTxnRunner, EFFECT_FAILED_CAST_MSG,
+    CData, ContainerFinalizerRegistrar, Error, SideEffect, SideEffectStateMutationRunner,
+    SideEffectTxnRunner,
 };
 
 /// Registers the given side effect and returns its build api.
 /// You can only call register once on purpose (it consumes self);
 /// to register multiple side effects, simply pass them in together!
+#[allow(clippy::struct_field_names)]
 pub struct SideEffectRegistrar<'a> {
     side_effect: &'a mut OnceCell<Box<dyn Any + Send>>,
     side_effect_state_mutation_runner: SideEffectStateMutationRunner,
     side_effect_txn_runner: SideEffectTxnRunner,
+    container_finalizer_registrar: ContainerFinalizerRegistrar,
+    capsule_type_name: &'static str,
 }
 
 impl<'a> SideEffectRegistrar<'a> {
     /// Creates a new `SideEffectRegistrar`.
     ///
-    /// This is public only to enable easier mocking in your code,
+    /// This is public only to enable easier mocking in your code
+    /// (although [`SideEffectRegistrar::mock`] is often simpler for that),
     /// or for other libraries looking to deeply integrate;
     /// do not use this method in other contexts.
     pub fn new(
@@ -28,6 +33,76 @@ impl<'a> SideEffectRegistrar<'a> {
             side_effect,
             side_effect_state_mutation_runner,
             side_effect_txn_runner,
+            container_finalizer_registrar: Arc::new(|_| {}),
+            capsule_type_name: "<unknown capsule>",
+        }
+    }
+
+    /// Creates a new `SideEffectRegistrar` for use in unit testing side effects
+    /// (and capsules) without a [`Container`](crate::Container).
+    ///
+    /// Side effect state is kept in the supplied `side_effect`, so passing the same `OnceCell`
+    /// into multiple mocked registrars will emulate multiple builds of the same capsule.
+    /// Requested side effect state mutations are ignored (they won't trigger a rebuild),
+    /// side effect transactions are run immediately,
+    /// and container finalizers are dropped without being run.
+    #[must_use]
+    pub fn mock(side_effect: &'a mut OnceCell<Box<dyn Any + Send>>) -> Self {
+        Self::new(side_effect, Arc::new(|_| {}), Arc::new(|txn| txn()))
+    }
+
+    /// Sets the type name of the capsule this `SideEffectRegistrar` is registering effects for,
+    /// which is used to provide more helpful panic messages.
+    ///
+    /// Like [`SideEffectRegistrar::new`], this is public only for libraries looking to deeply
+    /// integrate (to propagate the capsule type name onto any nested `SideEffectRegistrar`s).
+    #[must_use]
+    pub const fn with_capsule_type_name(mut self, capsule_type_name: &'static str) -> Self {
+        self.capsule_type_name = capsule_type_name;
+        self
+    }
+
+    /// Returns the type name of the capsule this `SideEffectRegistrar` is registering effects for.
+    #[must_use]
+    pub const fn capsule_type_name(&self) -> &'static str {
+        self.capsule_type_name
+    }
+
+    /// Sets the callback used to register finalizers that run when the
+    /// [`Container`](crate::Container) is dropped.
+    /// By default, finalizers are dropped without being run.
+    ///
+    /// Like [`SideEffectRegistrar::new`], this is public only for libraries looking to deeply
+    /// integrate (to propagate the finalizer registrar onto any nested `SideEffectRegistrar`s).
+    #[must_use]
+    pub fn with_container_finalizer_registrar(
+        mut self,
+        container_finalizer_registrar: ContainerFinalizerRegistrar,
+    ) -> Self {
+        self.container_finalizer_registrar = container_finalizer_registrar;
+        self
+    }
+
+    /// Returns the callback that registers a finalizer to run when the
+    /// [`Container`](crate::Container) is dropped (not when the capsule itself is disposed).
+    ///
+    /// Finalizers run in the reverse order of their registration.
+    /// When a capsule is disposed and later reinitialized,
+    /// the finalizers it registers replace those from its previous lifetime.
+    #[must_use]
+    pub fn container_finalizer_registrar(&self) -> ContainerFinalizerRegistrar {
+        Arc::clone(&self.container_finalizer_registrar)
+    }
+
+    /// Creates a (shorter-lived) `SideEffectRegistrar` over the same side effect state,
+    /// which is needed to build a capsule several times within one build.
+    pub(crate) fn reborrow(&mut self) -> SideEffectRegistrar<'_> {
+        SideEffectRegistrar {
+            side_effect: self.side_effect,
+            side_effect_state_mutation_runner: Arc::clone(&self.side_effect_state_mutation_runner),
+            side_effect_txn_runner: Arc::clone(&self.side_effect_txn_runner),
+            container_finalizer_registrar: Arc::clone(&self.container_finalizer_registrar),
+            capsule_type_name: self.capsule_type_name,
         }
     }
 
@@ -43,6 +118,7 @@ impl<'a> SideEffectRegistrar<'a> {
     /// # Panics
     /// Panics when the supplied type `T` changes between builds.
     /// Ensure T remains the same across builds (e.g., by calling this function unconditionally).
+    /// The panic message includes the capsule's type name and the expected/actual state types.
     #[allow(
         clippy::type_complexity,
         reason = "Return type refactor would require breaking change"
@@ -58,27 +134,182 @@ impl<'a> SideEffectRegistrar<'a> {
     where
         T: Send + 'static,
     {
-        self.side_effect.get_or_init(|| Box::new(initial));
+        let capsule_type_name = self.capsule_type_name;
+        self.side_effect
+            .get_or_init(|| Box::new(StoredState::new(initial, capsule_type_name)));
         let data = self
             .side_effect
             .get_mut()
-            .expect("Side effect should've been initialized in get_or_init above")
-            .downcast_mut::<T>()
-            .unwrap_or_else(|| panic!("{}", EFFECT_FAILED_CAST_MSG));
+            .expect("Side effect should've been initialized in get_or_init above");
+        let data = checked_stored_state::<T>(data.as_mut(), capsule_type_name);
 
+        #[cfg(feature = "dead-effect-warnings")]
+        let usage = data.usage();
         let mutation_runner = move |mutation: Box<dyn '_ + FnOnce(&mut T)>| {
+            #[cfg(feature = "dead-effect-warnings")]
+            usage.mark_mutated();
             (self.side_effect_state_mutation_runner)(Box::new(|data| {
-                let data = data
-                    .downcast_mut::<T>()
-                    .unwrap_or_else(|| panic!("{}", EFFECT_FAILED_CAST_MSG));
-                mutation(data);
+                mutation(checked_stored_state::<T>(data, capsule_type_name).get_mut());
             }));
         };
+        let data = data.get_mut();
 
         (data, mutation_runner, self.side_effect_txn_runner)
     }
 }
 
+#[cfg(not(feature = "dead-effect-warnings"))]
+use untracked::StoredState;
+#[cfg(not(feature = "dead-effect-warnings"))]
+mod untracked {
+    use std::any::Any;
+
+    /// The side effect state, as it is stored in a capsule's side effect `OnceCell`,
+    /// alongside the state's type name (for when the state's type changes between builds).
+    pub struct StoredState {
+        data: Box<dyn Any + Send>,
+        state_type_name: &'static str,
+    }
+
+    impl StoredState {
+        pub fn new<T: Send + 'static>(data: T, _capsule_type_name: &'static str) -> Self {
+            Self {
+                data: Box::new(data),
+                state_type_name: std::any::type_name::<T>(),
+            }
+        }
+
+        pub const fn state_type_name(&self) -> &'static str {
+            self.state_type_name
+        }
+
+        pub fn is<T: 'static>(&self) -> bool {
+            self.data.is::<T>()
+        }
+
+        pub fn get_mut<T: 'static>(&mut self) -> &mut T {
+            self.data
+                .downcast_mut()
+                .expect("State type should've been checked via StoredState::is")
+        }
+    }
+}
+
+#[cfg(feature = "dead-effect-warnings")]
+use dead_effects::StoredState;
+#[cfg(feature = "dead-effect-warnings")]
+mod dead_effects {
+    use std::{
+        any::Any,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    /// The side effect state, as it is stored in a capsule's side effect `OnceCell`,
+    /// alongside usage information to detect dead side effects.
+    ///
+    /// A side effect is considered dead when its mutation runner escapes a build
+    /// (i.e., it is handed out as part of the side effect's api)
+    /// but is never invoked throughout the lifetime of the capsule.
+    /// Such side effects can typically be replaced with a cheaper one that doesn't rebuild.
+    pub struct StoredState {
+        data: Box<dyn Any + Send>,
+        usage: Arc<EffectUsage>,
+    }
+
+    pub struct EffectUsage {
+        capsule_type_name: &'static str,
+        state_type_name: &'static str,
+        did_escape: AtomicBool,
+        did_mutate: AtomicBool,
+    }
+
+    impl EffectUsage {
+        pub fn mark_mutated(&self) {
+            self.did_mutate.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl StoredState {
+        pub fn new<T: Send + 'static>(data: T, capsule_type_name: &'static str) -> Self {
+            Self {
+                data: Box::new(data),
+                usage: Arc::new(EffectUsage {
+                    capsule_type_name,
+                    state_type_name: std::any::type_name::<T>(),
+                    did_escape: AtomicBool::new(false),
+                    did_mutate: AtomicBool::new(false),
+                }),
+            }
+        }
+
+        pub fn state_type_name(&self) -> &'static str {
+            self.usage.state_type_name
+        }
+
+        pub fn is<T: 'static>(&self) -> bool {
+            self.data.is::<T>()
+        }
+
+        pub fn get_mut<T: 'static>(&mut self) -> &mut T {
+            self.data
+                .downcast_mut()
+                .expect("State type should've been checked via StoredState::is")
+        }
+
+        /// Returns the usage to give to this build's mutation runner.
+        pub fn usage(&self) -> Arc<EffectUsage> {
+            self.update_did_escape();
+            Arc::clone(&self.usage)
+        }
+
+        pub fn is_dead(&self) -> bool {
+            self.update_did_escape();
+            self.usage.did_escape.load(Ordering::Relaxed)
+                && !self.usage.did_mutate.load(Ordering::Relaxed)
+        }
+
+        /// Any mutation runner still alive from a previous build must've escaped that build.
+        fn update_did_escape(&self) {
+            if Arc::strong_count(&self.usage) > 1 {
+                self.usage.did_escape.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    impl Drop for StoredState {
+        fn drop(&mut self) {
+            if self.is_dead() {
+                log::warn!(
+                    "Dead side effect in {}: its state ({}) can be mutated, \
+                    but was never mutated; consider using a side effect that doesn't rebuild",
+                    self.usage.capsule_type_name,
+                    self.usage.state_type_name,
+                );
+            }
+        }
+    }
+}
+
+/// Returns the [`StoredState`] in `state`,
+/// panicking if it does not hold state of type `T` (i.e., the side effect(s) changed).
+fn checked_stored_state<'a, T: 'static>(
+    state: &'a mut dyn Any,
+    capsule_type_name: &'static str,
+) -> &'a mut StoredState {
+    match state.downcast_mut::<StoredState>() {
+        Some(state) if state.is::<T>() => state,
+        state => Error::SideEffectTypeChanged {
+            capsule_type_name,
+            expected_type_name: state.map_or("<unknown>", |state| state.state_type_name()),
+            actual_type_name: std::any::type_name::<T>(),
+        }
+        .panic(),
+    }
+}
+
 // One arg register needs its own impl because tuples with one effect don't impl SideEffect
 #[cfg(feature = "experimental-api")]
 impl<'a, S: SideEffect> FnOnce<(S,)> for SideEffectRegistrar<'a> {
@@ -107,3 +338,75 @@ generate_side_effect_registrar_fn_impl!(A, B, C, D, E);
 generate_side_effect_registrar_fn_impl!(A, B, C, D, E, F);
 generate_side_effect_registrar_fn_impl!(A, B, C, D, E, F, G);
 generate_side_effect_registrar_fn_impl!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+
+    use crate::SideEffectRegistrar;
+
+    #[test]
+    fn mock_side_effect_registrar_keeps_state_across_builds() {
+        let mut state = OnceCell::new();
+
+        let (data, set_data, run_txn) = SideEffectRegistrar::mock(&mut state).raw(0);
+        assert_eq!(*data, 0);
+        *data = 1;
+        set_data(Box::new(|data| *data = 123));
+        run_txn(Box::new(|| set_data(Box::new(|data| *data = 123))));
+
+        let (data, _, _) = SideEffectRegistrar::mock(&mut state).raw(0);
+        assert_eq!(*data, 1);
+    }
+
+    #[test]
+    fn changed_side_effect_type_panics_with_expected_and_actual_types() {
+        let mut state = OnceCell::new();
+        _ = SideEffectRegistrar::mock(&mut state).raw(0_u8);
+
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            _ = SideEffectRegistrar::mock(&mut state)
+                .with_capsule_type_name("my_capsule")
+                .raw(0_u16);
+        }))
+        .expect_err("Changing the side effect state type should panic");
+        let message = payload
+            .downcast_ref::<String>()
+            .expect("Panic message should be a String");
+
+        assert_eq!(
+            *message,
+            String::from(
+                "You cannot change the side effect(s) passed to SideEffectRegistrar::register()! \
+                my_capsule expected side effect state of type u8, but found u16 instead."
+            ),
+        );
+    }
+
+    #[cfg(feature = "dead-effect-warnings")]
+    #[test]
+    fn dead_effects_are_detected() {
+        use super::StoredState;
+
+        fn is_dead(state: &mut OnceCell<Box<dyn std::any::Any + Send>>) -> bool {
+            state
+                .get_mut()
+                .and_then(|state| state.downcast_ref::<StoredState>())
+                .is_some_and(StoredState::is_dead)
+        }
+
+        // Mutation runner never leaves the build
+        let mut state = OnceCell::new();
+        drop(SideEffectRegistrar::mock(&mut state).raw(0_u8));
+        assert!(!is_dead(&mut state));
+
+        // Mutation runner leaves the build, but is never invoked
+        let mut state = OnceCell::new();
+        let (_, set_data, _) = SideEffectRegistrar::mock(&mut state).raw(0_u8);
+        assert!(is_dead(&mut state));
+
+        // Mutation runner leaves the build and is invoked
+        set_data(Box::new(|data| *data = 1));
+        assert!(!is_dead(&mut state));
+    }
+}
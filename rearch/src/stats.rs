@@ -0,0 +1,75 @@
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Aggregate, container-wide statistics, as returned by [`Container::stats`](crate::Container::stats).
+///
+/// With the `serde` feature, this implements `Serialize` (say, to return it from an endpoint).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ContainerStats {
+    /// The total number of capsule builds (including initializations) in the container.
+    pub total_builds: usize,
+    /// The total number of capsules disposed from the container.
+    pub total_disposals: usize,
+    /// The number of capsules currently in the container.
+    pub node_count: usize,
+    /// The total number of capsule builds, keyed by each capsule's type name.
+    pub builds_by_type: BTreeMap<&'static str, usize>,
+}
+
+/// The always-on counters backing [`ContainerStats`].
+///
+/// Recording a build only touches atomics; the per-type map is only locked to hand out
+/// a type's counter (once per node) and to take a snapshot.
+#[derive(Default)]
+pub struct StatsCounters {
+    builds: AtomicUsize,
+    disposals: AtomicUsize,
+    builds_by_type: Mutex<BTreeMap<&'static str, Arc<AtomicUsize>>>,
+}
+
+impl StatsCounters {
+    /// Returns the shared build counter for the capsule type with the given name,
+    /// which nodes hold onto and pass into [`StatsCounters::record_build`].
+    pub fn type_build_counter(&self, capsule_type_name: &'static str) -> Arc<AtomicUsize> {
+        Arc::clone(
+            self.builds_by_type
+                .lock()
+                .entry(capsule_type_name)
+                .or_default(),
+        )
+    }
+
+    pub fn record_build(&self, type_build_counter: &AtomicUsize) {
+        self.builds.fetch_add(1, Ordering::Relaxed);
+        type_build_counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_disposal(&self) {
+        self.disposals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn builds(&self) -> usize {
+        self.builds.load(Ordering::SeqCst)
+    }
+
+    pub fn snapshot(&self, node_count: usize) -> ContainerStats {
+        ContainerStats {
+            total_builds: self.builds(),
+            total_disposals: self.disposals.load(Ordering::SeqCst),
+            node_count,
+            builds_by_type: self
+                .builds_by_type
+                .lock()
+                .iter()
+                .map(|(&type_name, builds)| (type_name, builds.load(Ordering::SeqCst)))
+                .collect(),
+        }
+    }
+}
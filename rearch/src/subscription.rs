@@ -0,0 +1,58 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    temporary_capsule::TemporaryCapsule, ArcContainerStore, Capsule, CapsuleHandle, Container,
+    CreateCapsuleId, ListenerHandle,
+};
+
+/// A polling-based subscription onto a capsule's data, as created with [`Container::subscribe`].
+///
+/// This is handy for polling-based loops (like those in game engines or immediate mode UIs),
+/// where pushing data via a callback (like with [`Container::listen`]) is unnatural.
+///
+/// Dropping the `Subscription` removes its internal listener from the [`Container`].
+pub struct Subscription<T> {
+    latest_data: Arc<Mutex<Option<T>>>,
+    _listener_handle: ListenerHandle,
+}
+
+impl<T> Subscription<T> {
+    pub(crate) fn new<C>(container: &Container, capsule: &C) -> Self
+    where
+        C: Capsule<Data = T> + Clone,
+        T: Clone + Send + 'static,
+    {
+        let latest_data = Arc::new(Mutex::new(None));
+        let subscription_capsule = {
+            let latest_data = Arc::clone(&latest_data);
+            let capsule = capsule.clone();
+            TemporaryCapsule::new(move |CapsuleHandle { mut get, register }: CapsuleHandle| {
+                register.register(()); // subscriptions are listeners, so prevent the idempotent gc
+                *latest_data.lock() = Some(get.snapshot(capsule.clone()));
+            })
+        };
+        let id = subscription_capsule.id();
+        container
+            .0
+            .write_txn()
+            .ensure_initialized(subscription_capsule);
+
+        Self {
+            latest_data,
+            _listener_handle: ListenerHandle {
+                id,
+                store: Arc::downgrade(&container.0),
+            },
+        }
+    }
+
+    /// Returns the capsule's latest data if it has changed since the last poll,
+    /// or `None` otherwise.
+    ///
+    /// The first poll will always return the capsule's data as of subscribing (or newer).
+    #[must_use]
+    pub fn try_recv(&self) -> Option<T> {
+        self.latest_data.lock().take()
+    }
+}
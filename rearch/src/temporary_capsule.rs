@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Capsule, CapsuleHandle, CapsuleKey};
+
+/// A temporary capsule that invokes its closure on every build,
+/// as used by [`Container::subscribe`](crate::Container::subscribe).
+///
+/// Each temporary capsule gets its own unique key, so that several of them
+/// (even with the same closure type) can exist in a container simultaneously.
+///
+/// Note: the closure should register a side effect so that the temporary capsule
+/// is non-idempotent, which prevents the idempotent gc from disposing it.
+pub struct TemporaryCapsule<F> {
+    build: F,
+    key: u64,
+}
+
+impl<F> TemporaryCapsule<F> {
+    pub fn new(build: F) -> Self {
+        static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+        Self {
+            build,
+            key: NEXT_KEY.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl<F> Capsule for TemporaryCapsule<F>
+where
+    F: Fn(CapsuleHandle) + Send + 'static,
+{
+    type Data = ();
+
+    fn build(&self, handle: CapsuleHandle) -> Self::Data {
+        (self.build)(handle);
+    }
+
+    fn eq(_old: &Self::Data, _new: &Self::Data) -> bool {
+        false
+    }
+
+    fn key(&self) -> impl CapsuleKey {
+        self.key
+    }
+}
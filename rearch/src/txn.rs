@@ -1,23 +1,62 @@
-use parking_lot::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::{
-    any::Any,
+    any::{Any, TypeId},
     cell::OnceCell,
-    collections::{HashMap, HashSet},
+    sync::Arc,
 };
 
 use crate::{
-    Capsule, CapsuleId, CapsuleManager, CreateCapsuleId, SideEffectTxnOrchestrator,
-    EXCLUSIVE_OWNER_MSG,
+    BuildMiddleware, Capsule, CapsuleId, CapsuleIdMap, CapsuleIdSet, CapsuleManager,
+    CapsuleOverrides, CreateCapsuleId, Error, GcResult, SideEffectTxnOrchestrator, StatsCounters,
 };
 
+#[track_caller]
+fn node_not_in_graph(id: &CapsuleId) -> ! {
+    Error::NodeNotInGraph {
+        id: CapsuleId::clone(id),
+    }
+    .panic()
+}
+
+/// Returns the `actual` dependencies that are not `declared`,
+/// and the `declared` dependencies that are not `actual`, respectively.
+#[cfg(feature = "logging")]
+fn mismatched_dependencies<'a>(
+    declared: &[&'a str],
+    actual: &[&'a str],
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let matches = |type_name: &str, declared: &str| {
+        type_name
+            .strip_suffix(declared)
+            .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with("::"))
+    };
+    let undeclared = actual
+        .iter()
+        .filter(|type_name| !declared.iter().any(|name| matches(type_name, name)))
+        .copied()
+        .collect();
+    let unread = declared
+        .iter()
+        .filter(|name| !actual.iter().any(|type_name| matches(type_name, name)))
+        .copied()
+        .collect();
+    (undeclared, unread)
+}
+
+/// Runs `build` wrapped by all of the supplied `middlewares`, with the first as the outermost.
+fn run_build_middlewares(middlewares: &[BuildMiddleware], id: &CapsuleId, build: &mut dyn FnMut()) {
+    match middlewares.split_first() {
+        None => build(),
+        Some((middleware, rest)) => middleware(id, &mut || run_build_middlewares(rest, id, build)),
+    }
+}
+
 pub struct ContainerReadTxn<'a> {
-    pub(crate) data: RwLockReadGuard<'a, HashMap<CapsuleId, Box<dyn Any + Send + Sync>>>,
+    pub(crate) data: RwLockReadGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
 }
 
 impl<'a> ContainerReadTxn<'a> {
-    pub(crate) fn new(
-        data: RwLockReadGuard<'a, HashMap<CapsuleId, Box<dyn Any + Send + Sync>>>,
-    ) -> Self {
+    pub(crate) fn new(data: RwLockReadGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>) -> Self {
         Self { data }
     }
 }
@@ -33,32 +72,102 @@ impl ContainerReadTxn<'_> {
 
     #[must_use]
     pub fn try_read_ref<C: Capsule>(&self, capsule: &C) -> Option<&C::Data> {
-        self.data
-            .get(&capsule.id())
-            .map(crate::downcast_capsule_data::<C>)
+        self.try_read_ref_raw::<C>(&capsule.id())
+    }
+
+    pub(crate) fn try_read_ref_raw<C: Capsule>(&self, id: &CapsuleId) -> Option<&C::Data> {
+        self.data.get(id).map(crate::downcast_capsule_data::<C>)
+    }
+}
+
+/// *EXPERIMENTAL*: the txn types may become crate-private before 1.0,
+/// so this api may be changed or removed without a major version bump.
+#[cfg(feature = "experimental-api")]
+impl ContainerReadTxn<'_> {
+    /// Invokes `f` with this txn so that several consistent reads can be performed
+    /// under the one read guard held by this txn, without needing the capsule tuple traits.
+    ///
+    /// This is intended for interop crates looking to build their own consistent read layers;
+    /// see [`Container::with_read_txn`](crate::Container::with_read_txn) to obtain a txn.
+    pub fn read_with<R>(&self, f: impl FnOnce(&Self) -> R) -> R {
+        f(self)
     }
 }
 
 pub struct ContainerWriteTxn<'a> {
     pub(crate) side_effect_txn_orchestrator: SideEffectTxnOrchestrator,
-    pub(crate) data: RwLockWriteGuard<'a, HashMap<CapsuleId, Box<dyn Any + Send + Sync>>>,
-    nodes: MutexGuard<'a, HashMap<CapsuleId, CapsuleManager>>,
+    pub(crate) data: RwLockWriteGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
+    nodes: MutexGuard<'a, CapsuleIdMap<CapsuleManager>>,
+    stats: &'a StatsCounters,
+    overrides: &'a CapsuleOverrides,
+    build_middlewares: &'a RwLock<Arc<[BuildMiddleware]>>,
+    is_idempotent_gc_disabled: bool,
+    // NOTE: this must remain the last field so that it is dropped after the locks are released
+    disposed_nodes: DeferredDisposals,
+}
+
+/// Holds onto the nodes disposed during a [`ContainerWriteTxn`] so that they (and their side
+/// effects) are only dropped once the txn's locks have been released.
+/// This makes it safe for side effect cleanup (say, via a `Drop` impl) to mutate other capsules,
+/// which would otherwise deadlock.
+/// The ids of the disposed nodes are likewise held onto so that the container's dispose observer
+/// is only notified once the txn's locks have been released.
+struct DeferredDisposals {
+    nodes: Vec<CapsuleManager>,
+    disposed_ids: Vec<CapsuleId>,
+    side_effect_txn_orchestrator: SideEffectTxnOrchestrator,
+}
+
+impl Drop for DeferredDisposals {
+    fn drop(&mut self) {
+        if !self.nodes.is_empty() {
+            // Drop all of the nodes in one side effect txn so any resulting rebuilds happen together
+            let nodes = std::mem::take(&mut self.nodes);
+            let run_txn = self
+                .side_effect_txn_orchestrator
+                .clone()
+                .create_txn_runner();
+            run_txn(Box::new(move || drop(nodes)));
+        }
+
+        if !self.disposed_ids.is_empty() {
+            self.side_effect_txn_orchestrator
+                .notify_dispose_observer(&self.disposed_ids);
+        }
+    }
 }
 
 impl<'a> ContainerWriteTxn<'a> {
     pub(crate) fn new(
-        data: RwLockWriteGuard<'a, HashMap<CapsuleId, Box<dyn Any + Send + Sync>>>,
-        nodes: MutexGuard<'a, HashMap<CapsuleId, CapsuleManager>>,
+        data: RwLockWriteGuard<'a, CapsuleIdMap<Arc<dyn Any + Send + Sync>>>,
+        nodes: MutexGuard<'a, CapsuleIdMap<CapsuleManager>>,
         side_effect_txn_orchestrator: SideEffectTxnOrchestrator,
+        stats: &'a StatsCounters,
+        overrides: &'a CapsuleOverrides,
+        build_middlewares: &'a RwLock<Arc<[BuildMiddleware]>>,
+        is_idempotent_gc_disabled: bool,
     ) -> Self {
         Self {
+            disposed_nodes: DeferredDisposals {
+                nodes: Vec::new(),
+                disposed_ids: Vec::new(),
+                side_effect_txn_orchestrator: side_effect_txn_orchestrator.clone(),
+            },
             side_effect_txn_orchestrator,
             data,
             nodes,
+            stats,
+            overrides,
+            build_middlewares,
+            is_idempotent_gc_disabled,
         }
     }
 
     pub(crate) fn downgrade(self) -> ContainerReadTxn<'a> {
+        debug_assert!(
+            self.disposed_nodes.nodes.is_empty(),
+            "Disposed nodes must not be dropped while the data read lock is held"
+        );
         ContainerReadTxn::new(RwLockWriteGuard::downgrade(self.data))
     }
 }
@@ -76,6 +185,10 @@ impl ContainerWriteTxn<'_> {
         self.read_or_init_ref(capsule).clone()
     }
 
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "The capsule is always initialized before being read"
+    )]
     pub fn read_or_init_ref<C: Capsule>(&mut self, capsule: C) -> &C::Data {
         let id = capsule.id();
         self.ensure_initialized(capsule);
@@ -108,23 +221,113 @@ impl ContainerWriteTxn<'_> {
             #[cfg(feature = "logging")]
             log::debug!("Initializing {} ({:?})", std::any::type_name::<C>(), id);
 
-            e.insert(CapsuleManager::new(capsule));
+            e.insert(self.overrides.create_manager(&id, capsule));
             self.build_single_node(&id);
+
+            #[cfg(feature = "logging")]
+            self.check_declared_dependencies::<C>(&id);
         }
     }
 
+    /// Warns when the dependencies a capsule declared via [`Capsule::dependencies`]
+    /// do not match the dependencies it actually read.
+    #[cfg(feature = "logging")]
+    fn check_declared_dependencies<C: Capsule>(&self, id: &CapsuleId) {
+        let Some(declared) = C::dependencies() else {
+            return;
+        };
+        let node = self.nodes.get(id).unwrap_or_else(|| node_not_in_graph(id));
+        if node.type_name != std::any::type_name::<C>() {
+            return; // the capsule was overridden, so its dependencies are not its own
+        }
+
+        let actual = node
+            .dependencies
+            .iter()
+            .filter_map(|dep| self.nodes.get(dep))
+            .map(|dep| dep.type_name)
+            .collect::<Vec<_>>();
+        let (undeclared, unread) = mismatched_dependencies(declared, &actual);
+        if !undeclared.is_empty() || !unread.is_empty() {
+            log::warn!(
+                "{} ({id:?}) read undeclared dependencies {undeclared:?} \
+                and did not read declared dependencies {unread:?}",
+                std::any::type_name::<C>(),
+            );
+        }
+    }
+
+    /// Adds the supplied capsule to the graph with the supplied data (without building it),
+    /// if the capsule is not already initialized.
+    pub(crate) fn seed_if_uninitialized<C: Capsule>(&mut self, capsule: C, data: C::Data) {
+        let id = capsule.id();
+        if let std::collections::hash_map::Entry::Vacant(e) =
+            self.nodes.entry(CapsuleId::clone(&id))
+        {
+            e.insert(self.overrides.create_manager(&id, capsule));
+            self.data.insert(id, Arc::new(data));
+        }
+    }
+
+    /// Replaces the requested node (if it is in the graph) with the supplied `manager`,
+    /// keeping the node's place in the graph, and then rebuilds it and its dependents.
+    pub(crate) fn replace_node_if_present(&mut self, id: &CapsuleId, mut manager: CapsuleManager) {
+        let Some(old_node) = self.node(id) else {
+            return;
+        };
+        manager.dependencies = std::mem::take(&mut old_node.dependencies);
+        manager.dependents = std::mem::take(&mut old_node.dependents);
+        let old_node = std::mem::replace(old_node, manager);
+        self.disposed_nodes.nodes.push(old_node);
+
+        let ids = std::iter::once(CapsuleId::clone(id)).collect();
+        self.invalidate_capsules_or_panic(&ids);
+    }
+
     /// Forcefully disposes only the requested node, cleaning up the node's direct dependencies.
     /// Panics if the node or one of its dependencies is not in the graph.
     pub(crate) fn dispose_node(&mut self, id: &CapsuleId) {
         self.data.remove(id);
-        self.nodes
+        self.stats.record_disposal();
+        let node = self
+            .nodes
             .remove(id)
-            .expect("Node should be in graph")
-            .dependencies
+            .unwrap_or_else(|| node_not_in_graph(id));
+        for dep in &node.dependencies {
+            self.node_or_panic(dep).dependents.remove(id);
+        }
+        self.disposed_nodes.nodes.push(node);
+        self.disposed_nodes.disposed_ids.push(CapsuleId::clone(id));
+    }
+
+    /// Forcefully disposes only the requested node, returning whether it was in the graph.
+    ///
+    /// The node's dependents are detached from it and then rebuilt,
+    /// so that they (re-)initialize the node from scratch instead of holding stale data.
+    pub(crate) fn dispose_node_and_rebuild_dependents(&mut self, id: &CapsuleId) -> bool {
+        let Some(node) = self.node(id) else {
+            return false;
+        };
+
+        let dependents = core::mem::take(&mut node.dependents)
             .iter()
-            .for_each(|dep| {
-                self.node_or_panic(dep).dependents.remove(id);
-            });
+            .cloned()
+            .collect::<CapsuleIdSet>();
+        for dependent in &dependents {
+            self.node_or_panic(dependent).dependencies.remove(id);
+        }
+        self.dispose_node(id);
+        self.build_queued_capsules(dependents);
+        true
+    }
+
+    /// Disposes the requested node if it is in the graph, returning whether it was.
+    pub(crate) fn dispose_node_if_present(&mut self, id: &CapsuleId) -> bool {
+        let is_present = self.node(id).is_some();
+        if is_present {
+            self.dispose_node(id);
+        }
+        is_present
     }
 
     pub(crate) fn add_dependency_relationship(
@@ -135,9 +338,10 @@ impl ContainerWriteTxn<'_> {
         self.node_or_panic(dependency)
             .dependents
             .insert(CapsuleId::clone(dependent));
-        self.node_or_panic(dependent)
-            .dependencies
-            .insert(CapsuleId::clone(dependency));
+        let dependent = self.node_or_panic(dependent);
+        dependent.dependencies.insert(CapsuleId::clone(dependency));
+        #[cfg(feature = "read-tracking")]
+        dependent.last_reads.push(CapsuleId::clone(dependency));
     }
 
     pub(crate) fn take_capsule_and_side_effect(
@@ -145,8 +349,14 @@ impl ContainerWriteTxn<'_> {
         id: &CapsuleId,
     ) -> (Box<dyn Any + Send>, OnceCell<Box<dyn Any + Send>>) {
         let node = self.node_or_panic(id);
-        let capsule = node.capsule.take().expect(EXCLUSIVE_OWNER_MSG);
-        let side_effect = node.side_effect.take().expect(EXCLUSIVE_OWNER_MSG);
+        let capsule = node
+            .capsule
+            .take()
+            .unwrap_or_else(|| Error::NodeInUse.panic());
+        let side_effect = node
+            .side_effect
+            .take()
+            .unwrap_or_else(|| Error::NodeInUse.panic());
         (capsule, side_effect)
     }
 
@@ -169,10 +379,121 @@ impl ContainerWriteTxn<'_> {
     ///
     /// # Panics
     /// Panics if any of the nodes are not in the graph
-    pub(crate) fn build_capsules_or_panic(&mut self, ids: &HashSet<CapsuleId>) {
-        let build_order_stack = self.create_build_order_stack(ids);
+    pub(crate) fn build_capsules_or_panic(&mut self, ids: &CapsuleIdSet) {
+        self.build_capsules_impl(ids, false);
+    }
+
+    /// Builds the capsules with the supplied (previously queued) ids,
+    /// skipping any that were disposed from the graph since they were queued.
+    pub(crate) fn build_queued_capsules(&mut self, mut ids: CapsuleIdSet) {
+        ids.retain(|id| self.nodes.contains_key(id));
+        if !ids.is_empty() {
+            self.build_capsules_or_panic(&ids);
+        }
+    }
+
+    /// Forcefully builds the capsules with the supplied ids,
+    /// treating them as changed (regardless of [`Capsule::eq`]) so that their dependents rebuild.
+    ///
+    /// # Panics
+    /// Panics if any of the nodes are not in the graph
+    pub(crate) fn invalidate_capsules_or_panic(&mut self, ids: &CapsuleIdSet) {
+        self.build_capsules_impl(ids, true);
+    }
+
+    /// Disposes every idempotent capsule in the graph with no nonidempotent downstream capsules,
+    /// returning the number of capsules that were disposed.
+    pub(crate) fn collect_garbage(&mut self) -> usize {
+        let all_ids = self.nodes.keys().cloned().collect();
+        let build_order_stack = self.create_build_order_stack(&all_ids);
+        let disposable_nodes = self.get_disposable_nodes_from_build_order_stack(&build_order_stack);
+        for id in &disposable_nodes {
+            self.dispose_single_node(id);
+        }
+        disposable_nodes.len()
+    }
+
+    /// Disposes the requested node along with all of its downstream dependents,
+    /// but only when all of them are idempotent.
+    pub(crate) fn try_garbage_collect(&mut self, id: &CapsuleId) -> GcResult {
+        if self.node(id).is_none() {
+            return GcResult::NotPresent;
+        }
+
+        let start = std::iter::once(CapsuleId::clone(id)).collect();
+        let build_order_stack = self.create_build_order_stack(&start);
         let disposable_nodes = self.get_disposable_nodes_from_build_order_stack(&build_order_stack);
-        let mut changed_nodes = HashSet::new();
+        if disposable_nodes.len() != build_order_stack.len() {
+            return GcResult::ValidationFailed;
+        }
+
+        // NOTE: the build order stack has dependents before their dependencies,
+        // so every node is disposed while its dependencies are still in the graph
+        for id in &build_order_stack {
+            self.dispose_node(id);
+        }
+        GcResult::Success
+    }
+
+    /// Copies every node that is not affected by the supplied `overridden_ids`
+    /// (i.e., that is neither overridden nor downstream of an overridden node)
+    /// into the `preview` txn, as placeholders that keep their current data.
+    pub(crate) fn copy_unaffected_nodes_into(
+        &mut self,
+        overridden_ids: &CapsuleIdSet,
+        preview: &mut ContainerWriteTxn,
+    ) {
+        let overridden_ids = overridden_ids
+            .iter()
+            .filter(|id| self.nodes.contains_key(*id))
+            .cloned()
+            .collect();
+        let affected_ids = self
+            .create_build_order_stack(&overridden_ids)
+            .into_iter()
+            .collect::<CapsuleIdSet>();
+        for (id, node) in &*self.nodes {
+            if affected_ids.contains(id) {
+                continue;
+            }
+            if let Some(data) = self.data.get(id) {
+                preview.data.insert(CapsuleId::clone(id), Arc::clone(data));
+                preview.nodes.insert(
+                    CapsuleId::clone(id),
+                    CapsuleManager::preview_placeholder(node),
+                );
+            }
+        }
+    }
+
+    /// Returns the number of dependents of the requested node, or 0 if it is not in the graph.
+    pub(crate) fn dependent_count(&self, id: &CapsuleId) -> usize {
+        self.nodes.get(id).map_or(0, |node| node.dependents.len())
+    }
+
+    /// Returns the number of capsules that are currently in the graph.
+    pub(crate) fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the ids of all capsules of the given type that are currently in the graph.
+    pub(crate) fn ids_of_type<C: Capsule>(&self) -> CapsuleIdSet {
+        self.nodes
+            .keys()
+            .filter(|id| id.capsule_type() == TypeId::of::<C>())
+            .cloned()
+            .collect()
+    }
+
+    fn build_capsules_impl(&mut self, ids: &CapsuleIdSet, are_ids_invalidated: bool) {
+        let build_order_stack = self.create_build_order_stack(ids);
+        let disposable_nodes = if self.is_idempotent_gc_disabled {
+            CapsuleIdSet::default()
+        } else {
+            self.get_disposable_nodes_from_build_order_stack(&build_order_stack)
+        };
+        let mut changed_nodes = CapsuleIdSet::default();
+        let mut rebuilt_nodes = CapsuleIdSet::default();
         for curr_id in build_order_stack.into_iter().rev() {
             let node = self.node_or_panic(&curr_id);
 
@@ -181,9 +502,15 @@ impl ContainerWriteTxn<'_> {
                 .dependencies
                 .iter()
                 .any(|dep| changed_nodes.contains(dep));
-            if !build_is_required && !have_deps_changed {
+            let have_deps_rebuilt = node.always_rebuild
+                && node
+                    .dependencies
+                    .iter()
+                    .any(|dep| rebuilt_nodes.contains(dep));
+            if !build_is_required && !have_deps_changed && !have_deps_rebuilt {
                 continue;
             }
+            rebuilt_nodes.insert(CapsuleId::clone(&curr_id));
 
             if disposable_nodes.contains(&curr_id) {
                 // NOTE: dependency/dependent relationships will be ok after this,
@@ -193,7 +520,7 @@ impl ContainerWriteTxn<'_> {
                 changed_nodes.insert(curr_id);
             } else {
                 let did_node_change = self.build_single_node(&curr_id);
-                if did_node_change {
+                if did_node_change || (are_ids_invalidated && build_is_required) {
                     changed_nodes.insert(curr_id);
                 }
             }
@@ -207,7 +534,7 @@ impl ContainerWriteTxn<'_> {
 
     /// Gets the requested node or panics if it is not in the graph
     fn node_or_panic(&mut self, id: &CapsuleId) -> &mut CapsuleManager {
-        self.node(id).expect("Node should be in graph")
+        self.node(id).unwrap_or_else(|| node_not_in_graph(id))
     }
 
     /// Builds only the requested node.
@@ -219,12 +546,42 @@ impl ContainerWriteTxn<'_> {
         // We use mem::take below to prevent needing a clone on the existing dependencies
         let node = self.node_or_panic(id);
         let old_deps = core::mem::take(&mut node.dependencies);
+        #[cfg(feature = "read-tracking")]
+        node.last_reads.clear();
         for dep in old_deps {
             self.node_or_panic(&dep).dependents.remove(id);
         }
 
         // Trigger the build (which also populates its new dependencies in self)
-        (self.node_or_panic(id).build)(CapsuleId::clone(id), self)
+        let stats = self.stats;
+        let node = self.node_or_panic(id);
+        let build = node.build;
+        stats.record_build(
+            node.build_counter
+                .get_or_insert_with(|| stats.type_build_counter(node.type_name)),
+        );
+
+        let middlewares = Arc::clone(&self.build_middlewares.read());
+        if middlewares.is_empty() {
+            return build(CapsuleId::clone(id), self);
+        }
+
+        let mut did_change = None;
+        run_build_middlewares(&middlewares, id, &mut || {
+            if did_change.is_some() {
+                Error::BuildInvokedRepeatedly {
+                    id: CapsuleId::clone(id),
+                }
+                .panic();
+            }
+            did_change = Some(build(CapsuleId::clone(id), self));
+        });
+        did_change.unwrap_or_else(|| {
+            Error::BuildNotInvoked {
+                id: CapsuleId::clone(id),
+            }
+            .panic()
+        })
     }
 
     /// Disposes just the supplied node, and *attempts* to clean up the node's direct dependencies.
@@ -234,21 +591,23 @@ impl ContainerWriteTxn<'_> {
     /// In all other cases, [`dispose_node`] is likely the proper method to use.
     fn dispose_single_node(&mut self, id: &CapsuleId) {
         self.data.remove(id);
-        self.nodes
+        self.stats.record_disposal();
+        let node = self
+            .nodes
             .remove(id)
-            .expect("Node should be in graph")
-            .dependencies
-            .iter()
-            .for_each(|dep| {
-                if let Some(node) = self.node(dep) {
-                    node.dependents.remove(id);
-                }
-            });
+            .unwrap_or_else(|| node_not_in_graph(id));
+        for dep in &node.dependencies {
+            if let Some(dep_node) = self.node(dep) {
+                dep_node.dependents.remove(id);
+            }
+        }
+        self.disposed_nodes.nodes.push(node);
+        self.disposed_nodes.disposed_ids.push(CapsuleId::clone(id));
     }
 
     /// Creates the start nodes' dependent subgraph build order, including start, *as a stack*.
     /// Thus, proper iteration order is done by popping off of the stack (in reverse order)!
-    fn create_build_order_stack(&mut self, start: &HashSet<CapsuleId>) -> Vec<CapsuleId> {
+    fn create_build_order_stack(&mut self, start: &CapsuleIdSet) -> Vec<CapsuleId> {
         // We need some more information alongside each node in order to do the topological sort
         // - False is for the first visit, which adds all deps to be visited and then self again
         // - True is for the second visit, which pushes node to the build order
@@ -257,7 +616,7 @@ impl ContainerWriteTxn<'_> {
             .cloned()
             .map(|id| (false, id))
             .collect::<Vec<_>>();
-        let mut visited = HashSet::new();
+        let mut visited = CapsuleIdSet::default();
         let mut build_order_stack = Vec::new();
 
         while let Some((has_visited_before, node)) = to_visit_stack.pop() {
@@ -289,8 +648,8 @@ impl ContainerWriteTxn<'_> {
     fn get_disposable_nodes_from_build_order_stack(
         &mut self,
         build_order_stack: &Vec<CapsuleId>,
-    ) -> HashSet<CapsuleId> {
-        let mut disposable = HashSet::new();
+    ) -> CapsuleIdSet {
+        let mut disposable = CapsuleIdSet::default();
 
         for id in build_order_stack {
             let node = self.node_or_panic(id);
@@ -304,3 +663,24 @@ impl ContainerWriteTxn<'_> {
         disposable
     }
 }
+
+#[cfg(all(test, feature = "logging"))]
+mod tests {
+    use super::mismatched_dependencies;
+
+    #[test]
+    fn mismatched_dependencies_matches_name_suffixes() {
+        let actual = ["app::counter::count_capsule", "app::FibonacciCapsule"];
+        assert_eq!(
+            mismatched_dependencies(&["count_capsule", "app::FibonacciCapsule"], &actual),
+            (vec![], vec![])
+        );
+        assert_eq!(
+            mismatched_dependencies(&["unt_capsule", "other_capsule"], &actual),
+            (
+                vec!["app::counter::count_capsule", "app::FibonacciCapsule"],
+                vec!["unt_capsule", "other_capsule"]
+            )
+        );
+    }
+}